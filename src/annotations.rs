@@ -0,0 +1,169 @@
+//Copyright (C) 2025-  plusmouse and other contributors
+//
+//This program is free software: you can redistribute it and/or modify
+//it under the terms of the GNU General Public License as published by
+//the Free Software Foundation, either version 3 of the License, or
+//(at your option) any later version.
+//
+//This program is distributed in the hope that it will be useful,
+//but WITHOUT ANY WARRANTY; without even the implied warranty of
+//MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//GNU General Public License for more details.
+//
+//You should have received a copy of the GNU General Public License
+//along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use crate::syntax::{SyntaxKind, SyntaxNode, SyntaxToken};
+
+/// A `---@param name[?] type` annotation, with the type split on `|`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Param {
+    pub name: String,
+    pub optional: bool,
+    pub types: Vec<String>,
+}
+
+/// The `---` comments directly above a statement, in source order
+pub fn doc_comments(statement: &SyntaxNode) -> Vec<String> {
+    // Comments after the last statement end up inside it, so walk back over
+    // tokens rather than siblings
+    let mut comments = Vec::new();
+    let mut current = statement.first_token().and_then(|t| t.prev_token());
+    while let Some(token) = current {
+        match token.kind() {
+            SyntaxKind::Whitespace | SyntaxKind::Newline => (),
+            SyntaxKind::Comment if token.text().starts_with("---") && starts_line(&token) => {
+                comments.push(token.text().to_string());
+            }
+            _ => break,
+        }
+        current = token.prev_token();
+    }
+    comments.reverse();
+    comments
+}
+
+/// Whether only whitespace comes before the token on its line, so it isn't
+/// a comment trailing some code
+fn starts_line(token: &SyntaxToken) -> bool {
+    let mut current = token.prev_token();
+    while let Some(t) = current {
+        match t.kind() {
+            SyntaxKind::Whitespace => current = t.prev_token(),
+            SyntaxKind::Newline => return true,
+            _ => return false,
+        }
+    }
+    true
+}
+
+pub fn parse_param(comment: &str) -> Option<Param> {
+    let rest = comment.strip_prefix("---")?.trim_start().strip_prefix("@param")?;
+    let mut parts = rest.split_whitespace();
+    let mut name = parts.next()?;
+    let mut optional = false;
+    if let Some(stripped) = name.strip_suffix('?') {
+        name = stripped;
+        optional = true;
+    }
+    let types = match parts.next() {
+        Some(t) => t.split('|').map(|t| {
+            if let Some(stripped) = t.strip_suffix('?') {
+                optional = true;
+                stripped.to_string()
+            } else {
+                t.to_string()
+            }
+        }).collect(),
+        None => Vec::new(),
+    };
+    Some(Param { name: name.to_string(), optional, types })
+}
+
+pub fn params(statement: &SyntaxNode) -> Vec<Param> {
+    doc_comments(statement).iter().filter_map(|c| parse_param(c)).collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiagnosticAction {
+    Disable,
+    Enable,
+    DisableLine,
+    DisableNextLine,
+}
+
+/// A `---@diagnostic action: code, code` comment
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiagnosticDirective {
+    pub action: DiagnosticAction,
+    /// Each code with its offset into the comment, none meaning every code
+    pub codes: Vec<(String, usize)>,
+}
+
+pub fn parse_diagnostic(comment: &str) -> Option<DiagnosticDirective> {
+    let rest = comment.strip_prefix("---")?.trim_start().strip_prefix("@diagnostic")?;
+    let (action, codes) = match rest.split_once(':') {
+        Some((action, codes)) => (action.trim(), Some(codes)),
+        None => (rest.trim(), None),
+    };
+    let action = match action {
+        "disable" => DiagnosticAction::Disable,
+        "enable" => DiagnosticAction::Enable,
+        "disable-line" => DiagnosticAction::DisableLine,
+        "disable-next-line" => DiagnosticAction::DisableNextLine,
+        _ => return None,
+    };
+    let mut parsed = Vec::new();
+    if let Some(codes) = codes {
+        let mut offset = comment.len() - codes.len();
+        for code in codes.split(',') {
+            let trimmed = code.trim();
+            if !trimmed.is_empty() {
+                let start = offset + code.find(trimmed).unwrap_or(0);
+                parsed.push((trimmed.to_string(), start));
+            }
+            offset += code.len() + 1;
+        }
+    }
+    Some(DiagnosticDirective { action, codes: parsed })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::syntax::syntax::Generator;
+
+    fn comments_before_last(text: &str) -> Vec<String> {
+        let root = SyntaxNode::new_root(Generator::new(text).process_all());
+        doc_comments(&root.children().last().unwrap())
+    }
+
+    #[test]
+    fn reads_comments_above() {
+        let text = "---@param a number\n---@param b? string\nlocal function f(a, b) end\n";
+        assert_eq!(comments_before_last(text), ["---@param a number", "---@param b? string"]);
+    }
+
+    #[test]
+    fn reads_comments_after_another_statement() {
+        let text = "local w = 1\n---@param p? string\nlocal function g(p) end\n";
+        assert_eq!(comments_before_last(text), ["---@param p? string"]);
+    }
+
+    #[test]
+    fn skips_comments_trailing_code() {
+        let text = "local w = 1 ---@param p? string\nlocal function g(p) end\n";
+        assert_eq!(comments_before_last(text), Vec::<String>::new());
+    }
+
+    #[test]
+    fn parses_params() {
+        assert_eq!(parse_param("---@param name? string|number"), Some(Param {
+            name: String::from("name"),
+            optional: true,
+            types: vec![String::from("string"), String::from("number")],
+        }));
+        assert_eq!(parse_param("---@param count integer?").map(|p| p.optional), Some(true));
+        assert_eq!(parse_param("-- @param name string"), None);
+    }
+}
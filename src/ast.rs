@@ -1,7 +1,9 @@
 use rowan::NodeOrToken;
 
 use crate::syntax::SyntaxNode;
+use crate::syntax::SyntaxToken;
 use crate::syntax::SyntaxKind;
+use crate::syntax::literals;
 
 pub trait AstNode {
     fn cast(node: SyntaxNode) -> Option<Self>
@@ -22,12 +24,15 @@ pub enum Statement {
     ForCountLoop(ForCountLoop),
     ForInLoop(ForInLoop),
     FunctionDefinition(FunctionDefinition),
+    Return(ReturnStatement),
+    Break(BreakStatement),
+    Empty(EmptyStatement),
 }
 
 impl AstNode for Statement {
     fn cast(node: SyntaxNode) -> Option<Self> {
         match node.kind() {
-            SyntaxKind::Assign => Some(Self::Assign(Assign{node})),
+            SyntaxKind::AssignStatement => Some(Self::Assign(Assign{node})),
             SyntaxKind::LocalAssignStatement => Some(Self::LocalAssign(LocalAssign{node})),
             SyntaxKind::FunctionCall => Some(Self::FunctionCall(FunctionCall{node})),
             SyntaxKind::DoBlock => Some(Self::Do(DoGroup{node})),
@@ -37,6 +42,9 @@ impl AstNode for Statement {
             SyntaxKind::ForCountLoop => Some(Self::ForCountLoop(ForCountLoop{node})),
             SyntaxKind::ForInLoop => Some(Self::ForInLoop(ForInLoop{node})),
             SyntaxKind::FunctionDefinition => Some(Self::FunctionDefinition(FunctionDefinition{node})),
+            SyntaxKind::ReturnStatement => Some(Self::Return(ReturnStatement{node})),
+            SyntaxKind::BreakStatement => Some(Self::Break(BreakStatement{node})),
+            SyntaxKind::EmptyStatement => Some(Self::Empty(EmptyStatement{node})),
             _ => None,
         }
     }
@@ -52,6 +60,9 @@ impl AstNode for Statement {
             Self::ForCountLoop(x) => x.syntax(),
             Self::ForInLoop(x) => x.syntax(),
             Self::FunctionDefinition(x) => x.syntax(),
+            Self::Return(x) => x.syntax(),
+            Self::Break(x) => x.syntax(),
+            Self::Empty(x) => x.syntax(),
         }
     }
 }
@@ -72,14 +83,22 @@ impl AstNode for FunctionDefinition {
 }
 impl FunctionDefinition {
     pub fn is_local(&self) -> bool {
-        match self.node.first_child_or_token_by_kind(&|k| k == SyntaxKind::LocalKeyword) {
-            Some(_) => true,
-            _ => false,
-        }
+        self.node.first_child_or_token_by_kind(&|k| k == SyntaxKind::LocalKeyword).is_some()
     }
     pub fn identifier(&self) -> Option<Identifier> {
         self.node.children().find_map(Identifier::cast)
     }
+    /// Name of a `local function`, which isn't wrapped in an `Identifier`
+    pub fn local_name(&self) -> Option<String> {
+        self.node.children_with_tokens().find_map(|n|
+            match n {
+                NodeOrToken::Token(t) => match t.kind() {
+                    SyntaxKind::Name => Some(t.text().to_string()),
+                    _ => None
+                }
+                _ => None
+            })
+    }
     pub fn params(&self) -> Option<ParameterList> {
         self.node.children().find_map(ParameterList::cast)
     }
@@ -105,17 +124,55 @@ impl AstNode for ParameterList {
 }
 
 impl ParameterList {
-    pub fn parameters(&self) -> Vec<String> {
-        self.node.children_with_tokens().filter_map(|t| match t  {
-            NodeOrToken::Token(t) => if t.kind() == SyntaxKind::Parameter { Some(t.text().to_string()) } else { None },
-            _ => None,
-        }).collect()
+    pub fn parameters(&self) -> Vec<Parameter> {
+        self.node.children_with_tokens().filter_map(|t| t.into_token().and_then(Parameter::cast)).collect()
+    }
+    pub fn names(&self) -> Vec<String> {
+        self.parameters().iter().map(|p| p.name().to_string()).collect()
+    }
+    /// The `...` at the end, if the function takes any number of arguments
+    pub fn varargs(&self) -> Option<ParameterVarArgs> {
+        self.node.children_with_tokens().find_map(|t| t.into_token().and_then(ParameterVarArgs::cast))
     }
     pub fn ellipsis(&self) -> bool {
-        self.node.children_with_tokens().any(|t| match t  {
-            NodeOrToken::Token(t) => t.kind() == SyntaxKind::ParameterVarArgs,
-            _ => false,
-        })
+        self.varargs().is_some()
+    }
+}
+
+/// A named parameter. The parser makes these tokens rather than nodes
+pub struct Parameter {
+    token: SyntaxToken
+}
+
+impl Parameter {
+    pub fn cast(token: SyntaxToken) -> Option<Self> {
+        match token.kind() {
+            SyntaxKind::Parameter => Some(Self{token}),
+            _ => None,
+        }
+    }
+    pub fn syntax(&self) -> &SyntaxToken {
+        &self.token
+    }
+    pub fn name(&self) -> &str {
+        self.token.text()
+    }
+}
+
+/// The `...` of `function(a, ...)`
+pub struct ParameterVarArgs {
+    token: SyntaxToken
+}
+
+impl ParameterVarArgs {
+    pub fn cast(token: SyntaxToken) -> Option<Self> {
+        match token.kind() {
+            SyntaxKind::ParameterVarArgs => Some(Self{token}),
+            _ => None,
+        }
+    }
+    pub fn syntax(&self) -> &SyntaxToken {
+        &self.token
     }
 }
 
@@ -157,8 +214,52 @@ impl Identifier {
     pub fn is_indexed_expression(&self) -> bool {
         self.node.children().any(|n| n.kind() == SyntaxKind::Expression)
     }
-    pub fn final_expression(&self) -> Option<Expression> {
-        self.node.children().find_map(Expression::cast)
+    /// The names of a plain `a` or `a.b.c`, `None` for anything with a call,
+    /// method or `[key]` in it
+    pub fn name_chain(&self) -> Option<Vec<String>> {
+        let mut names = Vec::new();
+        for n in self.node.children_with_tokens() {
+            match n {
+                NodeOrToken::Token(t) => match t.kind() {
+                    SyntaxKind::Name => names.push(t.text().to_string()),
+                    SyntaxKind::Dot | SyntaxKind::Whitespace | SyntaxKind::Newline | SyntaxKind::Comment => (),
+                    _ => return None,
+                }
+                _ => return None,
+            }
+        }
+        if names.is_empty() {
+            return None
+        }
+        Some(names)
+    }
+    /// What's being indexed when the identifier doesn't start with a name, as
+    /// the `a[1]` of `a[1].b` or the `f()` of `f():m()`
+    pub fn prefix(&self) -> Option<Expression> {
+        let first = self.node.children_with_tokens().find(|n|
+            !matches!(n.kind(), SyntaxKind::Whitespace | SyntaxKind::Newline | SyntaxKind::Comment)
+        )?;
+        first.into_node().and_then(Expression::cast)
+    }
+    /// The key of `prefix[key]`
+    pub fn key(&self) -> Option<Expression> {
+        self.node.children().find(|n| n.kind() == SyntaxKind::Expression).and_then(Expression::cast)
+    }
+    /// Names after the `.` following a prefix, as the `b.c` of `f().b.c`
+    pub fn fields(&self) -> Vec<String> {
+        match self.node.children().skip(1).find_map(Identifier::cast) {
+            Some(i) => i.names(),
+            None => Vec::new(),
+        }
+    }
+    /// The name after `:` in a method call
+    pub fn method(&self) -> Option<String> {
+        self.node.children_with_tokens()
+            .skip_while(|n| n.kind() != SyntaxKind::Colon)
+            .find_map(|n| match n {
+                NodeOrToken::Token(t) if t.kind() == SyntaxKind::Name => Some(t.text().to_string()),
+                _ => None,
+            })
     }
 }
 
@@ -251,6 +352,13 @@ impl ExpressionList {
     pub fn expressions(&self) -> Vec<Expression> {
         self.node.children().filter_map(Expression::cast).collect()
     }
+    /// Whether the list finishes with `...`, which can expand to any number of values
+    pub fn ends_with_varargs(&self) -> bool {
+        let last = self.node.children_with_tokens().filter(|n|
+            !matches!(n.kind(), SyntaxKind::Whitespace | SyntaxKind::Newline | SyntaxKind::Comment)
+        ).last();
+        matches!(last, Some(n) if n.kind() == SyntaxKind::TripleDot)
+    }
 }
 
 pub struct Literal {
@@ -270,9 +378,14 @@ impl AstNode for Literal {
 }
 
 impl Literal {
+    /// The string's value with quotes removed and escapes decoded, bytes
+    /// that aren't UTF-8 replaced
     pub fn get_string(&self) -> Option<String> {
-        self.node.children_with_tokens().find_map(|t| match t.kind() {
-            SyntaxKind::String => Some(String::from(self.node.text())),
+        self.string_bytes().map(|b| String::from_utf8_lossy(&b).into_owned())
+    }
+    pub fn string_bytes(&self) -> Option<Vec<u8>> {
+        self.node.children_with_tokens().find_map(|t| match t {
+            NodeOrToken::Token(t) if t.kind() == SyntaxKind::String => Some(literals::decode_string(t.text()).bytes),
             _ => None
         })
     }
@@ -282,6 +395,13 @@ impl Literal {
             _ => None
         })
     }
+    /// The value of a number literal
+    pub fn number_value(&self) -> Option<f64> {
+        self.node.children_with_tokens().find_map(|t| match t {
+            NodeOrToken::Token(t) if t.kind() == SyntaxKind::Number => literals::parse_number(t.text()),
+            _ => None
+        })
+    }
     pub fn get_bool(&self) -> Option<String> {
         self.node.children_with_tokens().find_map(|t| match t.kind() {
             SyntaxKind::TrueKeyword => Some(String::from(self.node.text())),
@@ -290,10 +410,7 @@ impl Literal {
         })
     }
     pub fn is_nil(&self) -> bool {
-        self.node.children_with_tokens().any(|t| match t.kind() {
-            SyntaxKind::NilKeyword => true,
-            _ => false
-        })
+        self.node.children_with_tokens().any(|t| t.kind() == SyntaxKind::NilKeyword)
     }
 }
 
@@ -302,12 +419,18 @@ pub enum Expression {
     BinaryExpression(BinaryExpression),
     GroupedExpression(GroupedExpression),
 
+    PrefixExpression(PrefixExpression),
+
     Identifier(Identifier),
+    Index(Index),
     Literal(Literal),
     Function(FunctionDefinition),
+    FunctionCall(FunctionCall),
+    MethodCall(MethodCall),
     TableConstructor(TableConstructor),
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Operator {
     Or, And, Not,
     LessThan, GreaterThan, LessThanOrEquals, GreaterThanOrEquals, NotEquals, Equals,
@@ -324,10 +447,16 @@ impl AstNode for Expression {
             SyntaxKind::UnaryExpression => Some(Self::UnaryExpression(UnaryExpression{node})),
             SyntaxKind::BinaryExpression => Some(Self::BinaryExpression(BinaryExpression{node})),
             SyntaxKind::GroupedExpression => Some(Self::GroupedExpression(GroupedExpression{node})),
+            SyntaxKind::PrefixExpression => Some(Self::PrefixExpression(PrefixExpression{node})),
+            SyntaxKind::Identifier if is_index(&node) => Some(Self::Index(Index{node})),
             SyntaxKind::Identifier => Some(Self::Identifier(Identifier{node})),
             SyntaxKind::Literal => Some(Self::Literal(Literal{node})),
             SyntaxKind::FunctionDefinition => Some(Self::Function(FunctionDefinition{node})),
+            SyntaxKind::FunctionCall if is_method_call(&node) => Some(Self::MethodCall(MethodCall{node})),
+            SyntaxKind::FunctionCall => Some(Self::FunctionCall(FunctionCall{node})),
             SyntaxKind::TableConstructor => Some(Self::TableConstructor(TableConstructor{node})),
+            // The parser wraps compound prefix expressions, unwrap to the actual term
+            SyntaxKind::Expression => node.children().find_map(Expression::cast),
             _ => None,
         }
     }
@@ -336,14 +465,48 @@ impl AstNode for Expression {
             Self::UnaryExpression(x) => x.syntax(),
             Self::BinaryExpression(x) => x.syntax(),
             Self::GroupedExpression(x) => x.syntax(),
+            Self::PrefixExpression(x) => x.syntax(),
             Self::Identifier(x) => x.syntax(),
+            Self::Index(x) => x.syntax(),
             Self::Literal(x) => x.syntax(),
             Self::Function(x) => x.syntax(),
+            Self::FunctionCall(x) => x.syntax(),
+            Self::MethodCall(x) => x.syntax(),
             Self::TableConstructor(x) => x.syntax(),
         }
     }
 }
 
+impl Expression {
+    /// The number the expression comes to when it's built only from number
+    /// literals, folded the same way Lua would at runtime
+    pub fn constant(&self) -> Option<f64> {
+        match self {
+            Expression::Literal(l) => l.number_value(),
+            Expression::GroupedExpression(g) => g.get_expression()?.constant(),
+            Expression::PrefixExpression(p) => p.expression()?.constant(),
+            Expression::BinaryExpression(b) => b.constant(),
+            Expression::UnaryExpression(u) => u.constant(),
+            _ => None,
+        }
+    }
+    /// Calls of either sort, as the plain call they come down to
+    pub fn as_call(&self) -> Option<FunctionCall> {
+        match self {
+            Expression::FunctionCall(c) => FunctionCall::cast(c.syntax().clone()),
+            Expression::MethodCall(m) => Some(m.call()),
+            _ => None,
+        }
+    }
+    /// As `constant`, but also taking strings Lua coerces in arithmetic, like `"10" * 2`
+    fn arithmetic_operand(&self) -> Option<f64> {
+        match self {
+            Expression::Literal(l) => l.number_value().or_else(|| literals::parse_number(&l.get_string()?)),
+            _ => self.constant(),
+        }
+    }
+}
+
 pub struct UnaryExpression {
     node: SyntaxNode
 }
@@ -351,7 +514,7 @@ pub struct UnaryExpression {
 impl AstNode for UnaryExpression {
     fn cast(node: SyntaxNode) -> Option<Self> {
         match node.kind() {
-            SyntaxKind::ExpressionList => Some(Self{node}),
+            SyntaxKind::UnaryExpression => Some(Self{node}),
             _ => None,
         }
     }
@@ -379,6 +542,17 @@ impl UnaryExpression {
     pub fn get_terms(&self) -> Vec<Expression> {
         self.node.children().filter_map(Expression::cast).collect()
     }
+    pub fn constant(&self) -> Option<f64> {
+        let term = self.get_terms().into_iter().next()?;
+        match self.kind() {
+            Operator::Subtract => Some(-term.arithmetic_operand()?),
+            Operator::ArrayLength => match term {
+                Expression::Literal(l) => Some(l.string_bytes()?.len() as f64),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
 }
 
 pub struct BinaryExpression {
@@ -388,7 +562,7 @@ pub struct BinaryExpression {
 impl AstNode for BinaryExpression {
     fn cast(node: SyntaxNode) -> Option<Self> {
         match node.kind() {
-            SyntaxKind::ExpressionList => Some(Self{node}),
+            SyntaxKind::BinaryExpression => Some(Self{node}),
             _ => None,
         }
     }
@@ -398,29 +572,52 @@ impl AstNode for BinaryExpression {
 }
 
 impl BinaryExpression {
+    fn operator(kind: SyntaxKind) -> Option<Operator> {
+        match kind {
+            SyntaxKind::OrKeyword => Some(Operator::Or),
+            SyntaxKind::AndKeyword => Some(Operator::And),
+            SyntaxKind::LessThan => Some(Operator::LessThan),
+            SyntaxKind::GreaterThan => Some(Operator::GreaterThan),
+            SyntaxKind::LessThanOrEquals => Some(Operator::LessThanOrEquals),
+            SyntaxKind::GreaterThanOrEquals => Some(Operator::GreaterThanOrEquals),
+            SyntaxKind::NotEqualsBoolean => Some(Operator::NotEquals),
+            SyntaxKind::EqualsBoolean => Some(Operator::Equals),
+            SyntaxKind::DoubleDot => Some(Operator::Concatenate),
+            SyntaxKind::Plus => Some(Operator::Add),
+            SyntaxKind::Minus => Some(Operator::Subtract),
+            SyntaxKind::Asterisk => Some(Operator::Multiply),
+            SyntaxKind::Slash => Some(Operator::Divide),
+            SyntaxKind::Modulo => Some(Operator::Modulo),
+            SyntaxKind::Hat => Some(Operator::Hat),
+            _ => None,
+        }
+    }
     pub fn kind(&self) -> Operator {
-        let some_op = self.node.children_with_tokens().find_map(|node|
-            match node.kind() {
-                SyntaxKind::OrKeyword => Some(Operator::Or),
-                SyntaxKind::AndKeyword => Some(Operator::And),
-                SyntaxKind::LessThan => Some(Operator::LessThan),
-                SyntaxKind::GreaterThan => Some(Operator::GreaterThan),
-                SyntaxKind::LessThanOrEquals => Some(Operator::LessThanOrEquals),
-                SyntaxKind::GreaterThanOrEquals => Some(Operator::GreaterThanOrEquals),
-                SyntaxKind::NotEqualsBoolean => Some(Operator::NotEquals),
-                SyntaxKind::EqualsBoolean => Some(Operator::Equals),
-                SyntaxKind::DoubleDot => Some(Operator::Concatenate),
-                SyntaxKind::Plus => Some(Operator::Add),
-                SyntaxKind::Minus => Some(Operator::Subtract),
-                SyntaxKind::Asterisk => Some(Operator::Multiply),
-                SyntaxKind::Slash => Some(Operator::Divide),
-                SyntaxKind::Modulo => Some(Operator::Modulo),
-                _ => None,
-            }
-        );
-        match some_op {
-            Some(x) => x,
-            None => Operator::None,
+        self.node.children_with_tokens().find_map(|node| Self::operator(node.kind())).unwrap_or(Operator::None)
+    }
+    pub fn get_terms(&self) -> Vec<Expression> {
+        self.node.children().filter_map(Expression::cast).collect()
+    }
+    pub fn constant(&self) -> Option<f64> {
+        // Broken code can leave more or fewer pieces, which can't be trusted to fold
+        let operators = self.node.children_with_tokens().filter(|n| Self::operator(n.kind()).is_some()).count();
+        let terms = self.get_terms();
+        let [left, right] = terms.as_slice() else {
+            return None
+        };
+        if operators != 1 {
+            return None
+        }
+        let (a, b) = (left.arithmetic_operand()?, right.arithmetic_operand()?);
+        match self.kind() {
+            Operator::Add => Some(a + b),
+            Operator::Subtract => Some(a - b),
+            Operator::Multiply => Some(a * b),
+            Operator::Divide => Some(a / b),
+            // Lua 5.1 defines `%` through floor, so the result takes the divisor's sign
+            Operator::Modulo => Some(a - (a / b).floor() * b),
+            Operator::Hat => Some(a.powf(b)),
+            _ => None,
         }
     }
 }
@@ -450,6 +647,135 @@ impl GroupedExpression {
     }
 }
 
+/// A prefix expression left wrapped in its own node, standing for whatever
+/// it wraps
+pub struct PrefixExpression {
+    node: SyntaxNode
+}
+
+impl AstNode for PrefixExpression {
+    fn cast(node: SyntaxNode) -> Option<Self> {
+        match node.kind() {
+            SyntaxKind::PrefixExpression => Some(Self{node}),
+            _ => None,
+        }
+    }
+    fn syntax(&self) -> &SyntaxNode {
+        &self.node
+    }
+}
+
+impl PrefixExpression {
+    pub fn expression(&self) -> Option<Expression> {
+        self.node.children().find_map(Expression::cast)
+    }
+}
+
+fn is_index(node: &SyntaxNode) -> bool {
+    node.children_with_tokens().any(|n| n.kind() == SyntaxKind::LeftSquareBracket)
+}
+
+/// `prefix[key]`. The parser builds these as `Identifier` nodes
+pub struct Index {
+    node: SyntaxNode
+}
+
+impl AstNode for Index {
+    fn cast(node: SyntaxNode) -> Option<Self> {
+        match node.kind() {
+            SyntaxKind::Identifier if is_index(&node) => Some(Self{node}),
+            _ => None,
+        }
+    }
+    fn syntax(&self) -> &SyntaxNode {
+        &self.node
+    }
+}
+
+impl Index {
+    /// The same node seen as an identifier, for walking it the same way
+    pub fn identifier(&self) -> Identifier {
+        Identifier{node: self.node.clone()}
+    }
+    /// What's indexed, as the `a.b` of `a.b[1]`
+    pub fn prefix(&self) -> Option<Expression> {
+        self.node.children().next().and_then(Expression::cast)
+    }
+    pub fn key(&self) -> Option<Expression> {
+        self.identifier().key()
+    }
+}
+
+fn is_method_call(node: &SyntaxNode) -> bool {
+    node.children().find_map(Identifier::cast).is_some_and(|i| i.is_call_to_self())
+}
+
+/// `object:method(arguments)`, a call passing what's before the `:` as `self`
+pub struct MethodCall {
+    node: SyntaxNode
+}
+
+impl AstNode for MethodCall {
+    fn cast(node: SyntaxNode) -> Option<Self> {
+        match node.kind() {
+            SyntaxKind::FunctionCall if is_method_call(&node) => Some(Self{node}),
+            _ => None,
+        }
+    }
+    fn syntax(&self) -> &SyntaxNode {
+        &self.node
+    }
+}
+
+impl MethodCall {
+    /// The same node seen as a plain call, which it is once `self` is passed
+    pub fn call(&self) -> FunctionCall {
+        FunctionCall{node: self.node.clone()}
+    }
+    /// What the method is looked up on, when that's more than names, as the
+    /// `a[1]` of `a[1]:m()`
+    pub fn object(&self) -> Option<Expression> {
+        self.call().identifier()?.prefix()
+    }
+    /// The names before the `:` of `a.b:m()`, empty when `object` is set
+    pub fn object_names(&self) -> Vec<String> {
+        let mut names = self.call().identifier().map(|i| i.names()).unwrap_or_default();
+        names.pop();
+        names
+    }
+    pub fn method(&self) -> Option<String> {
+        self.call().method()
+    }
+    pub fn arguments(&self) -> Option<ArgumentList> {
+        self.call().arguments()
+    }
+}
+
+/// Stands in for a token the parser expected but didn't find, like the `end`
+/// of a function cut off by the end of the file
+pub struct Missing {
+    node: SyntaxNode
+}
+
+impl AstNode for Missing {
+    fn cast(node: SyntaxNode) -> Option<Self> {
+        match node.kind() {
+            SyntaxKind::Missing => Some(Self{node}),
+            _ => None,
+        }
+    }
+    fn syntax(&self) -> &SyntaxNode {
+        &self.node
+    }
+}
+
+impl Missing {
+    /// The kind of token that should have been here
+    pub fn expected(&self) -> Option<SyntaxKind> {
+        self.node.first_token().map(|t| t.kind())
+    }
+}
+
 pub struct LocalAssign {
     node: SyntaxNode
 }
@@ -520,11 +846,106 @@ impl FunctionCall {
     pub fn identifier(&self) -> Option<Identifier> {
         self.node.children().find_map(Identifier::cast)
     }
-    pub fn arguments(&self) -> Option<ExpressionList> {
+    pub fn arguments(&self) -> Option<ArgumentList> {
+        self.node.children().find_map(ArgumentList::cast)
+    }
+    /// The call being called again, as in `f()()`
+    pub fn inner_call(&self) -> Option<FunctionCall> {
+        self.node.children().find_map(FunctionCall::cast)
+    }
+    /// Whatever is being called, a name, indexing, another call or `(f)`
+    pub fn callee(&self) -> Option<Expression> {
+        self.node.children().find(|n| n.kind() != SyntaxKind::ArgumentList).and_then(Expression::cast)
+    }
+    /// The method name of `a:m()`
+    pub fn method(&self) -> Option<String> {
+        self.identifier().and_then(|i| i.method())
+    }
+}
+
+pub struct ArgumentList {
+    node: SyntaxNode
+}
+
+impl AstNode for ArgumentList {
+    fn cast(node: SyntaxNode) -> Option<Self> {
+        match node.kind() {
+            SyntaxKind::ArgumentList => Some(Self{node}),
+            _ => None,
+        }
+    }
+    fn syntax(&self) -> &SyntaxNode {
+        &self.node
+    }
+}
+
+impl ArgumentList {
+    pub fn expressions(&self) -> Vec<Expression> {
+        self.node.children().filter_map(Expression::cast).collect()
+    }
+    pub fn ends_with_varargs(&self) -> bool {
+        let last = self.node.children_with_tokens().filter(|n|
+            !matches!(n.kind(), SyntaxKind::Whitespace | SyntaxKind::Newline | SyntaxKind::Comment | SyntaxKind::RightBracket)
+        ).last();
+        matches!(last, Some(n) if n.kind() == SyntaxKind::TripleDot)
+    }
+}
+
+pub struct ReturnStatement {
+    node: SyntaxNode
+}
+
+impl AstNode for ReturnStatement {
+    fn cast(node: SyntaxNode) -> Option<Self> {
+        match node.kind() {
+            SyntaxKind::ReturnStatement => Some(Self{node}),
+            _ => None,
+        }
+    }
+    fn syntax(&self) -> &SyntaxNode {
+        &self.node
+    }
+}
+
+impl ReturnStatement {
+    pub fn expression_list(&self) -> Option<ExpressionList> {
         self.node.children().find_map(ExpressionList::cast)
     }
 }
 
+pub struct BreakStatement {
+    node: SyntaxNode
+}
+
+impl AstNode for BreakStatement {
+    fn cast(node: SyntaxNode) -> Option<Self> {
+        match node.kind() {
+            SyntaxKind::BreakStatement => Some(Self{node}),
+            _ => None,
+        }
+    }
+    fn syntax(&self) -> &SyntaxNode {
+        &self.node
+    }
+}
+
+/// A lone `;` between statements
+pub struct EmptyStatement {
+    node: SyntaxNode
+}
+
+impl AstNode for EmptyStatement {
+    fn cast(node: SyntaxNode) -> Option<Self> {
+        match node.kind() {
+            SyntaxKind::EmptyStatement => Some(Self{node}),
+            _ => None,
+        }
+    }
+    fn syntax(&self) -> &SyntaxNode {
+        &self.node
+    }
+}
+
 pub struct DoGroup {
     node: SyntaxNode
 }
@@ -565,7 +986,7 @@ impl AstNode for WhileLoop {
 
 impl WhileLoop {
     pub fn condition(&self) -> Option<Expression> {
-        self.node.children().find_map(Expression::cast)
+        self.node.children().find_map(Condition::cast).and_then(|c| c.expression())
     }
     pub fn block(&self) -> Option<Block> {
         self.node.children().find_map(Block::cast)
@@ -590,7 +1011,7 @@ impl AstNode for RepeatUntilLoop {
 
 impl RepeatUntilLoop {
     pub fn condition(&self) -> Option<Expression> {
-        self.node.children().find_map(Expression::cast)
+        self.node.children().find_map(Condition::cast).and_then(|c| c.expression())
     }
     pub fn block(&self) -> Option<Block> {
         self.node.children().find_map(Block::cast)
@@ -703,7 +1124,7 @@ impl AstNode for IfBranch {
 
 impl IfBranch {
     pub fn expression(&self) -> Option<Expression> {
-        self.node.children().find_map(Expression::cast)
+        self.node.children().find_map(Condition::cast).and_then(|c| c.expression())
     }
     pub fn block(&self) -> Option<Block> {
         self.node.children().find_map(Block::cast)
@@ -752,7 +1173,212 @@ impl AstNode for TableConstructor {
 }
 
 impl TableConstructor {
-    pub fn expression_list(&self) -> Option<ExpressionList> {
-        self.node.children().find_map(ExpressionList::cast)
+    pub fn field_list(&self) -> Option<FieldList> {
+        self.node.children().find_map(FieldList::cast)
+    }
+    pub fn fields(&self) -> Vec<Field> {
+        match self.field_list() {
+            Some(l) => l.fields(),
+            None => Vec::new(),
+        }
+    }
+}
+
+pub struct FieldList {
+    node: SyntaxNode
+}
+
+impl AstNode for FieldList {
+    fn cast(node: SyntaxNode) -> Option<Self> {
+        match node.kind() {
+            SyntaxKind::FieldList => Some(Self{node}),
+            _ => None,
+        }
+    }
+    fn syntax(&self) -> &SyntaxNode {
+        &self.node
+    }
+}
+
+impl FieldList {
+    pub fn fields(&self) -> Vec<Field> {
+        self.node.children().filter_map(Field::cast).collect()
+    }
+}
+
+pub struct Field {
+    node: SyntaxNode
+}
+
+impl AstNode for Field {
+    fn cast(node: SyntaxNode) -> Option<Self> {
+        match node.kind() {
+            SyntaxKind::Field => Some(Self{node}),
+            _ => None,
+        }
+    }
+    fn syntax(&self) -> &SyntaxNode {
+        &self.node
+    }
+}
+
+impl Field {
+    fn has_token(&self, kind: SyntaxKind) -> bool {
+        self.node.children_with_tokens().any(|n| n.kind() == kind)
+    }
+    /// Whether the field is `[key] = value`
+    pub fn is_keyed(&self) -> bool {
+        self.has_token(SyntaxKind::LeftSquareBracket)
+    }
+    /// Whether the field is just a value, taking the next array index
+    pub fn is_positional(&self) -> bool {
+        !self.has_token(SyntaxKind::Assign)
+    }
+    /// The name of a `name = value` field
+    pub fn name(&self) -> Option<String> {
+        if self.is_keyed() || self.is_positional() {
+            return None
+        }
+        let names = self.node.children().find_map(Identifier::cast)?.names();
+        match names.as_slice() {
+            [name] => Some(name.clone()),
+            _ => None,
+        }
+    }
+    /// The key expression of a `[key] = value` field
+    pub fn key(&self) -> Option<Expression> {
+        if !self.is_keyed() {
+            return None
+        }
+        self.node.children().find_map(Expression::cast)
+    }
+    pub fn value(&self) -> Option<Expression> {
+        if self.is_positional() {
+            return self.node.children().find_map(Expression::cast)
+        }
+        self.node.children().filter_map(Expression::cast).nth(1)
+    }
+}
+
+/// The expression deciding whether an `if`, `while` or `repeat` runs its block
+pub struct Condition {
+    node: SyntaxNode
+}
+
+impl AstNode for Condition {
+    fn cast(node: SyntaxNode) -> Option<Self> {
+        match node.kind() {
+            SyntaxKind::Condition => Some(Self{node}),
+            _ => None,
+        }
+    }
+    fn syntax(&self) -> &SyntaxNode {
+        &self.node
+    }
+}
+
+impl Condition {
+    pub fn expression(&self) -> Option<Expression> {
+        self.node.children().find_map(Expression::cast)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::syntax::syntax::Generator;
+
+    fn value(expression: &str) -> Option<Expression> {
+        let text = format!("local x = {expression}\n");
+        let root = SyntaxNode::new_root(Generator::new(&text).process_all());
+        let list = root.descendants().find_map(ExpressionList::cast)?;
+        list.expressions().into_iter().next()
+    }
+
+    fn constant(expression: &str) -> Option<f64> {
+        value(expression)?.constant()
+    }
+
+    #[test]
+    fn folds_with_lua_precedence() {
+        assert_eq!(constant("2+3*4"), Some(14.0));
+        assert_eq!(constant("2*3+4"), Some(10.0));
+        assert_eq!(constant("10 - 4 - 3"), Some(3.0));
+        assert_eq!(constant("(2+3)*4"), Some(20.0));
+    }
+
+    #[test]
+    fn folds_powers_before_unary_minus() {
+        assert_eq!(constant("-2^2"), Some(-4.0));
+        assert_eq!(constant("2^-1"), Some(0.5));
+    }
+
+    #[test]
+    fn folds_powers_from_the_right() {
+        assert_eq!(constant("2^3^2"), Some(512.0));
+    }
+
+    #[test]
+    fn folds_like_lua_at_runtime() {
+        assert_eq!(constant("60 * 60 * 24"), Some(86400.0));
+        assert_eq!(constant("0x10 + .5"), Some(16.5));
+        assert_eq!(constant("\"10\" * 2"), Some(20.0));
+        assert_eq!(constant("-7 % 3"), Some(2.0));
+        assert_eq!(constant("#\"abc\""), Some(3.0));
+        assert_eq!(constant("1 .. 2"), None);
+    }
+
+    #[test]
+    fn leaves_broken_expressions() {
+        assert_eq!(constant("2 + * 3"), None);
+        assert_eq!(constant("x + 1"), None);
+    }
+
+    #[test]
+    fn indexing_is_its_own_expression() {
+        let Some(Expression::Index(index)) = value("t.a[1]") else {
+            panic!("expected an index")
+        };
+        assert!(matches!(index.prefix(), Some(Expression::Identifier(i)) if i.name_chain() == Some(vec![String::from("t"), String::from("a")])));
+        assert_eq!(index.key().and_then(|k| k.constant()), Some(1.0));
+        assert!(matches!(value("t.a"), Some(Expression::Identifier(_))));
+    }
+
+    #[test]
+    fn method_calls_are_their_own_expression() {
+        let Some(Expression::MethodCall(call)) = value("a.b:m(1, 2)") else {
+            panic!("expected a method call")
+        };
+        assert_eq!(call.method().as_deref(), Some("m"));
+        assert_eq!(call.object_names(), ["a", "b"]);
+        assert!(call.object().is_none());
+        assert_eq!(call.arguments().map(|a| a.expressions().len()), Some(2));
+
+        let Some(Expression::MethodCall(call)) = value("t[1]:m()") else {
+            panic!("expected a method call")
+        };
+        assert!(matches!(call.object(), Some(Expression::Index(_))));
+        assert!(matches!(value("f(1)"), Some(Expression::FunctionCall(_))));
+    }
+
+    #[test]
+    fn reads_parameters() {
+        let Some(Expression::Function(f)) = value("function(a, b, ...) end") else {
+            panic!("expected a function")
+        };
+        let params = f.params().unwrap();
+        assert_eq!(params.names(), ["a", "b"]);
+        assert_eq!(params.varargs().map(|v| v.syntax().text().to_string()).as_deref(), Some("..."));
+        assert!(value("function(a) end").and_then(|e| match e {
+            Expression::Function(f) => f.params(),
+            _ => None,
+        }).is_some_and(|p| !p.ellipsis()));
+    }
+
+    #[test]
+    fn marks_missing_tokens() {
+        let root = SyntaxNode::new_root(Generator::new("local function f()\n").process_all());
+        let missing: Vec<_> = root.descendants().filter_map(Missing::cast).filter_map(|m| m.expected()).collect();
+        assert_eq!(missing, [SyntaxKind::EndKeyword]);
     }
 }
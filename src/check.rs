@@ -0,0 +1,342 @@
+//Copyright (C) 2025-  plusmouse and other contributors
+//
+//This program is free software: you can redistribute it and/or modify
+//it under the terms of the GNU General Public License as published by
+//the Free Software Foundation, either version 3 of the License, or
+//(at your option) any later version.
+//
+//This program is distributed in the hope that it will be useful,
+//but WITHOUT ANY WARRANTY; without even the implied warranty of
+//MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//GNU General Public License for more details.
+//
+//You should have received a copy of the GNU General Public License
+//along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! `wow_ls check <paths...>`: the server's diagnostics over files on disk,
+//! printed the way rustc prints them or in one of the formats in [`crate::formats`]
+
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+
+use serde_json::Value;
+
+use crate::diagnostics::{self, Diagnostic, DiagnosticKind, Severity};
+use crate::files::Listing;
+use crate::formats::{self, Format};
+use crate::settings::{self, Settings, PROJECT_FILE};
+use crate::state::State;
+use crate::symbols;
+
+/// One checked file and what was found in it, already filtered by the config
+pub struct FileReport {
+    pub path: PathBuf,
+    pub text: String,
+    pub diagnostics: Vec<(Diagnostic, Severity)>,
+}
+
+/// A file to report on, read or known to be missing
+enum Checked {
+    Read { path: PathBuf, text: String, references: Vec<String> },
+    Missing { path: PathBuf, listing: Listing },
+}
+
+/// The game reads files as bytes, so don't refuse ones with a stray bad character
+fn read_lossy(path: &Path) -> std::io::Result<String> {
+    Ok(String::from_utf8_lossy(&std::fs::read(path)?).into_owned())
+}
+
+/// Checks the files the way the server does, each seeing the globals the
+/// others and the libraries define
+pub fn check_files(paths: &[PathBuf], settings: &Settings) -> Result<Vec<FileReport>, String> {
+    for path in paths {
+        if !path.exists() {
+            return Err(format!("{}: no such file or directory", path.display()))
+        }
+    }
+    let files = crate::files::expand(paths, Some(settings.flavor)).map_err(|e| e.to_string())?;
+    let mut state = State::default();
+    let mut checked = Vec::new();
+    for (path, listing) in files {
+        match (read_lossy(&path), listing) {
+            (Ok(text), _) => {
+                let summary = symbols::summarize(&text);
+                let references = summary.references.clone();
+                state.set_file(path.to_string_lossy().into_owned(), summary, None);
+                checked.push(Checked::Read { path, text, references });
+            }
+            // A `.toc` naming a file that isn't there doesn't stop the rest loading
+            (Err(_), Some(listing)) => checked.push(Checked::Missing { path, listing }),
+            (Err(e), None) => return Err(format!("{}: {}", path.display(), e)),
+        }
+    }
+    // Libraries only lend their globals, as in the editor
+    for root in settings.library_roots() {
+        for path in crate::files::workspace_files(root, Some(settings.flavor)).unwrap_or_default() {
+            let key = path.to_string_lossy().into_owned();
+            if !state.contains(&key) && let Ok(text) = read_lossy(&path) {
+                state.set_file(key, symbols::summarize_file(&path, &text), None);
+            }
+        }
+    }
+    state.set_known(settings.globals.clone());
+    let globals = state.globals();
+
+    let config = &settings.diagnostics;
+    let mut reports: Vec<FileReport> = Vec::new();
+    for file in checked {
+        match file {
+            Checked::Read { path, text, references } => {
+                let name = path.to_string_lossy().into_owned();
+                let types = globals.types_for(&name, &references);
+                let diagnostics = diagnostics::check_with_globals(&text, &name, &types).into_iter()
+                    .filter_map(|d| config.severity(d.kind).map(|s| (d, s)))
+                    .collect();
+                reports.push(FileReport { path, text, diagnostics });
+            }
+            Checked::Missing { path, listing } => {
+                let Some(severity) = config.severity(DiagnosticKind::MissingFile) else {
+                    continue
+                };
+                let message = format!("{} doesn't exist", path.display());
+                let diagnostic = Diagnostic::new(DiagnosticKind::MissingFile, listing.start, listing.end, message);
+                match reports.iter_mut().find(|r| r.path == listing.file) {
+                    Some(report) => report.diagnostics.push((diagnostic, severity)),
+                    None => {
+                        let text = read_lossy(&listing.file).unwrap_or_default();
+                        reports.push(FileReport { path: listing.file, text, diagnostics: vec![(diagnostic, severity)] });
+                    }
+                }
+            }
+        }
+    }
+    Ok(reports)
+}
+
+/// The nearest directory holding a project file, going up from the first
+/// path, or the current one. Relative paths in the settings are from here
+fn project_root(paths: &[PathBuf]) -> PathBuf {
+    let current = std::env::current_dir().unwrap_or_default();
+    let first = paths.first().map(|p| current.join(p)).unwrap_or_else(|| current.clone());
+    first.ancestors().find(|dir| dir.join(PROJECT_FILE).is_file()).map(Path::to_path_buf).unwrap_or(current)
+}
+
+/// The settings the server would use with `root` as its workspace, with
+/// nothing from an editor
+pub fn load_settings(root: &Path) -> Result<Settings, String> {
+    let (merged, errors) = settings::combine(&Value::Null, &Value::Null, &[root.to_path_buf()]);
+    if let Some(error) = errors.into_iter().next() {
+        return Err(error)
+    }
+    let (settings, problems) = Settings::from_json(&merged, Some(root));
+    for problem in problems {
+        eprintln!("warning: {}", problem);
+    }
+    Ok(settings)
+}
+
+/// Byte offsets of where each line starts
+pub fn line_starts(text: &str) -> Vec<usize> {
+    std::iter::once(0).chain(text.match_indices('\n').map(|(i, _)| i + 1)).collect()
+}
+
+/// Zero based line and the byte offset into it
+fn locate(starts: &[usize], offset: usize) -> (usize, usize) {
+    let line = starts.partition_point(|s| *s <= offset) - 1;
+    (line, offset - starts[line])
+}
+
+fn line_text<'a>(text: &'a str, starts: &[usize], line: usize) -> &'a str {
+    let end = starts.get(line + 1).copied().unwrap_or(text.len());
+    text[starts[line]..end].trim_end_matches(['\n', '\r'])
+}
+
+/// Columns are counted in characters, which is what a terminal shows
+fn column(line: &str, byte: usize) -> usize {
+    line[..byte.min(line.len())].chars().count()
+}
+
+/// One based line and character column, as editors and CI tools count them
+pub fn line_column(text: &str, starts: &[usize], offset: usize) -> (usize, usize) {
+    let (line, byte) = locate(starts, offset.min(text.len()));
+    (line + 1, column(line_text(text, starts, line), byte) + 1)
+}
+
+/// A span to underline, `^` for the diagnostic itself and `-` for related ones
+struct Label<'a> {
+    line: usize,
+    from: usize,
+    to: usize,
+    mark: char,
+    message: &'a str,
+}
+
+fn label<'a>(text: &str, starts: &[usize], start: usize, end: usize, mark: char, message: &'a str) -> Label<'a> {
+    let (line, byte) = locate(starts, start.min(text.len()));
+    let content = line_text(text, starts, line);
+    let from = column(content, byte);
+    // Spans running over several lines get underlined to the end of the first
+    let to = if end > start && locate(starts, end.min(text.len())).0 == line {
+        column(content, end - starts[line])
+    } else if end > start {
+        content.chars().count()
+    } else {
+        from
+    };
+    Label { line, from, to: to.max(from + 1), mark, message }
+}
+
+pub fn render_human(report: &FileReport, diagnostic: &Diagnostic, severity: Severity, out: &mut String) {
+    let text = report.text.as_str();
+    let starts = line_starts(text);
+    let mut labels = vec![label(text, &starts, diagnostic.start, diagnostic.end, '^', "")];
+    if let Some(related) = &diagnostic.related {
+        labels.push(label(text, &starts, related.start, related.end, '-', &related.message));
+    }
+    labels.sort_by_key(|l| (l.line, l.from));
+    let primary = labels.iter().find(|l| l.mark == '^').expect("added above");
+    let width = (labels.iter().map(|l| l.line).max().unwrap_or(0) + 1).to_string().len();
+    let gutter = " ".repeat(width);
+
+    let _ = writeln!(out, "{}[{}]: {}", severity.name(), diagnostic.kind.code(), diagnostic.message);
+    let _ = writeln!(out, "{}--> {}:{}:{}", gutter, report.path.display(), primary.line + 1, primary.from + 1);
+    let _ = writeln!(out, "{} |", gutter);
+    let mut previous: Option<usize> = None;
+    for l in &labels {
+        if previous.is_some_and(|p| l.line > p + 1) {
+            let _ = writeln!(out, "...");
+        }
+        if previous != Some(l.line) {
+            let content = line_text(text, &starts, l.line).replace('\t', "    ");
+            let _ = writeln!(out, "{:>width$} | {}", l.line + 1, content, width = width);
+        }
+        // Tabs were widened above, so widen them in the underline's indent too
+        let indent: usize = line_text(text, &starts, l.line).chars().take(l.from)
+            .map(|c| if c == '\t' { 4 } else { 1 }).sum();
+        let underline = l.mark.to_string().repeat(l.to - l.from);
+        let line = format!("{} | {}{} {}", gutter, " ".repeat(indent), underline, l.message);
+        let _ = writeln!(out, "{}", line.trim_end());
+        previous = Some(l.line);
+    }
+    let _ = writeln!(out);
+}
+
+const USAGE: &str = "usage: wow_ls check [--format human|json|sarif|checkstyle] <paths...>
+
+paths can be Lua files, directories or an addon's .toc. Settings are read
+from the nearest .wowls.toml going up from the first path";
+
+/// Returns whether any errors were found, for the exit code
+pub fn run(args: &[String]) -> Result<bool, String> {
+    let mut paths = Vec::new();
+    let mut format = Format::Human;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return Ok(false)
+            }
+            "--format" => {
+                let name = args.next().ok_or_else(|| format!("--format needs a value\n\n{}", USAGE))?;
+                format = Format::from_name(name).ok_or_else(|| format!("unknown format '{}'\n\n{}", name, USAGE))?;
+            }
+            _ if arg.starts_with('-') => return Err(format!("unknown option '{}'\n\n{}", arg, USAGE)),
+            _ => paths.push(PathBuf::from(arg)),
+        }
+    }
+    if paths.is_empty() {
+        return Err(String::from(USAGE))
+    }
+
+    let settings = load_settings(&project_root(&paths))?;
+    let reports = check_files(&paths, &settings)?;
+    let errors = reports.iter().flat_map(|r| &r.diagnostics).filter(|(_, s)| *s == Severity::Error).count();
+    match format {
+        Format::Json => print!("{}", formats::json_lines(&reports)),
+        Format::Sarif => print!("{}", formats::sarif(&reports)),
+        Format::Checkstyle => print!("{}", formats::checkstyle(&reports)),
+        Format::Human => {
+            let mut out = String::new();
+            for report in &reports {
+                for (diagnostic, severity) in &report.diagnostics {
+                    render_human(report, diagnostic, *severity, &mut out);
+                }
+            }
+            print!("{}", out);
+            let warnings = reports.iter().flat_map(|r| &r.diagnostics).filter(|(_, s)| *s == Severity::Warning).count();
+            let plural = |n: usize, word: &str| format!("{} {}{}", n, word, if n == 1 { "" } else { "s" });
+            if errors > 0 {
+                println!("error: checked {}, found {} and {}", plural(reports.len(), "file"), plural(errors, "error"), plural(warnings, "warning"));
+            } else if warnings > 0 {
+                println!("warning: checked {}, found {}", plural(reports.len(), "file"), plural(warnings, "warning"));
+            } else {
+                println!("checked {}, no problems found", plural(reports.len(), "file"));
+            }
+        }
+    }
+    Ok(errors > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A fresh directory holding `files`
+    fn addon(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let root = std::env::temp_dir().join(format!("wowls-check-{}-{name}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        for (path, text) in files {
+            let path = root.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, text).unwrap();
+        }
+        root
+    }
+
+    fn codes(reports: &[FileReport]) -> Vec<(String, &'static str)> {
+        reports.iter().flat_map(|r| r.diagnostics.iter().map(|(d, _)| {
+            (r.path.file_name().unwrap().to_string_lossy().into_owned(), d.kind.code())
+        })).collect()
+    }
+
+    #[test]
+    fn sees_globals_from_the_other_files() {
+        let root = addon("globals", &[("A.toc", "Core.lua\nUse.lua\n"), ("Core.lua", "Count = 5\n"), ("Use.lua", "Count()\n")]);
+        let reports = check_files(&[root.join("A.toc")], &load_settings(&root).unwrap()).unwrap();
+        std::fs::remove_dir_all(&root).unwrap();
+        assert_eq!(codes(&reports), [(String::from("Use.lua"), "call-non-function")]);
+    }
+
+    #[test]
+    fn reads_the_project_file() {
+        let root = addon("project", &[
+            (PROJECT_FILE, "[diagnostics]\ndisable = [\"concat-table\"]\n"),
+            ("Core.lua", "local s = 'a' .. {}\nreturn s\n"),
+        ]);
+        assert_eq!(project_root(&[root.join("Core.lua")]), root);
+        let reports = check_files(std::slice::from_ref(&root), &load_settings(&root).unwrap()).unwrap();
+        std::fs::remove_dir_all(&root).unwrap();
+        assert_eq!(codes(&reports), []);
+    }
+
+    #[test]
+    fn reports_missing_files_and_checks_the_rest() {
+        let root = addon("missing", &[
+            ("A.toc", "## Title: A\nGone.lua\nUI.xml\nCore.lua\n"),
+            ("UI.xml", "<Ui>\n    <Script file=\"Lost.lua\"/>\n</Ui>\n"),
+            ("Core.lua", "local n = 1\nn()\n"),
+        ]);
+        let reports = check_files(&[root.join("A.toc")], &load_settings(&root).unwrap()).unwrap();
+        std::fs::remove_dir_all(&root).unwrap();
+        assert_eq!(codes(&reports), [
+            (String::from("A.toc"), "missing-file"),
+            (String::from("UI.xml"), "missing-file"),
+            (String::from("Core.lua"), "call-non-function"),
+        ]);
+        let (gone, _) = &reports[0].diagnostics[0];
+        assert_eq!(&reports[0].text[gone.start..gone.end], "Gone.lua");
+        let (lost, _) = &reports[1].diagnostics[0];
+        assert_eq!(&reports[1].text[lost.start..lost.end], "Lost.lua");
+    }
+}
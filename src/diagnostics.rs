@@ -1,3 +1,50 @@
+use std::collections::HashMap;
+
+use crate::annotations::{self, DiagnosticAction};
+use crate::symbols::GlobalTypes;
+use crate::syntax::{SyntaxKind, SyntaxNode};
+use crate::syntax::syntax::{Error, ErrorKind};
+
+/// Codes LuaLS checks for that we don't, so `---@diagnostic` comments written
+/// for it aren't reported as unknown
+const FOREIGN_CODES: &[&str] = &[
+    "ambiguity-1", "assign-type-mismatch", "await-in-sync", "cast-local-type",
+    "cast-type-mismatch", "circle-doc-class", "close-non-object", "codestyle-check",
+    "count-down-loop", "deprecated", "different-requires", "discard-returns",
+    "doc-field-no-class", "duplicate-doc-alias", "duplicate-doc-field", "duplicate-doc-param",
+    "duplicate-index", "duplicate-set-field", "empty-block", "global-element",
+    "global-in-nil-env", "incomplete-signature-doc", "inject-field", "invisible",
+    "lowercase-global", "missing-fields", "missing-global-doc", "missing-local-export-doc",
+    "missing-parameter", "missing-return", "missing-return-value", "name-style-check",
+    "newfield-call", "newline-call", "no-unknown", "not-yieldable", "param-type-mismatch",
+    "redefined-local", "redundant-return", "redundant-return-value",
+    "redundant-value", "return-type-mismatch", "spell-check", "trailing-space",
+    "unbalanced-assignments", "undefined-doc-class", "undefined-doc-name",
+    "undefined-doc-param", "undefined-env-child", "undefined-field", "undefined-global",
+    "unknown-cast-variable", "unknown-operator", "unnecessary-assert", "unreachable-code",
+    "unused-function", "unused-label", "unused-local", "unused-vararg",
+    // Its syntax errors, which can be turned off the same way
+    "ambiguous-syntax", "args-after-dots", "crash", "err-assign-as-eq",
+    "err-c-long-comment", "err-comment-prefix", "err-do-as-then", "err-eq-as-assign",
+    "err-lcomment-end", "err-lstring-end", "err-nonstandard-symbol", "err-then-as-do",
+    "err-ueq", "exp-in-action", "index-in-func-name", "jump-local-scope", "keyword", "miss-end",
+    "miss-esc-x", "miss-exp", "miss-exponent", "miss-field", "miss-loop-max", "miss-loop-min",
+    "miss-method", "miss-name", "miss-sep-in-table", "miss-space-between", "miss-symbol",
+    "multi-tag", "must-x16", "need-paren", "nesting-long-mark", "no-visible-label",
+    "redefine-label", "set-const", "unexpect-efunc-name", "unexpect-lfunc-name",
+    "unexpect-symbol", "unicode-name", "unknown", "unknown-attribute", "unknown-symbol",
+    "unknown-tag", "unsupport-symbol", "utf8-max", "utf8-small", "luadoc-error-diag-mode",
+    "luadoc-miss-alias-extends", "luadoc-miss-alias-name", "luadoc-miss-arg-name",
+    "luadoc-miss-cate-name", "luadoc-miss-class-extends-name", "luadoc-miss-class-name",
+    "luadoc-miss-diag-mode", "luadoc-miss-diag-name", "luadoc-miss-extends-symbol",
+    "luadoc-miss-field-extends", "luadoc-miss-field-name", "luadoc-miss-fun-after-overload",
+    "luadoc-miss-generic-extends-name", "luadoc-miss-generic-name", "luadoc-miss-local-name",
+    "luadoc-miss-module-name", "luadoc-miss-operator-name", "luadoc-miss-param-extends",
+    "luadoc-miss-param-name", "luadoc-miss-see-name", "luadoc-miss-sign-name",
+    "luadoc-miss-symbol", "luadoc-miss-type-name", "luadoc-miss-vararg-type",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum DiagnosticKind {
     NotClosedBlock,
     NotClosedComment,
@@ -16,14 +63,530 @@ pub enum DiagnosticKind {
     ExpectingClosingBracket,
     ExpectingFunctionCall,
     ExpectingExpression,
+    ExpectingOperator,
 
     InvalidName,
     InvalidFunction,
     InvalidNumberFormat,
+
+    VarargOutsideVarargFunction,
+    BreakOutsideLoop,
+    StatementAfterReturn,
+    StatementAfterBreak,
+    TooManyLocals,
+    TooManyUpvalues,
+    InvalidEscape,
+    EscapeTooLarge,
+
+    ArithmeticOnNonNumber,
+    CallingNonFunction,
+    IndexingNonTable,
+    ConcatenatingTable,
+    TooManyArguments,
+    PossiblyNil,
+
+    UnknownDiagnosticCode,
+
+    MissingFile,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    Hint,
+    Information,
+    Warning,
+    Error,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Category {
+    /// The file can't be parsed as it stands
+    Syntax,
+    /// A value is used in a way its type doesn't allow
+    Type,
+    /// A value might be nil where nil isn't allowed
+    Nil,
+    /// A `---@` comment is malformed
+    Annotation,
+    /// The addon's files don't fit together, like a `.toc` naming a file that isn't there
+    Addon,
+}
+
+impl Severity {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Severity::Hint => "hint",
+            Severity::Information => "information",
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        }
+    }
+
+    /// Reads a severity as written in configuration, `Some(None)` being "off"
+    pub fn from_name(name: &str) -> Option<Option<Severity>> {
+        match name.to_ascii_lowercase().as_str() {
+            "hint" => Some(Some(Severity::Hint)),
+            "information" | "info" => Some(Some(Severity::Information)),
+            "warning" | "warn" => Some(Some(Severity::Warning)),
+            "error" => Some(Some(Severity::Error)),
+            "off" | "none" => Some(None),
+            _ => None,
+        }
+    }
+}
+
+impl Category {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Category::Syntax => "syntax",
+            Category::Type => "type",
+            Category::Nil => "nil",
+            Category::Annotation => "annotation",
+            Category::Addon => "addon",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Category> {
+        [Category::Syntax, Category::Type, Category::Nil, Category::Annotation, Category::Addon].into_iter().find(|c| c.name() == name)
+    }
+}
+
+impl DiagnosticKind {
+    pub const ALL: &'static [DiagnosticKind] = &[
+        DiagnosticKind::NotClosedBlock,
+        DiagnosticKind::NotClosedComment,
+        DiagnosticKind::NotTerminatedString,
+        DiagnosticKind::UnexpectedKeyword,
+        DiagnosticKind::UnexpectedToken,
+        DiagnosticKind::UnexpectedOperator,
+        DiagnosticKind::ExpectingComma,
+        DiagnosticKind::ExpectingCommaOrBracket,
+        DiagnosticKind::ExpectingThen,
+        DiagnosticKind::ExpectingDo,
+        DiagnosticKind::ExpectingToken,
+        DiagnosticKind::ExpectingName,
+        DiagnosticKind::ExpectingClosingBracket,
+        DiagnosticKind::ExpectingFunctionCall,
+        DiagnosticKind::ExpectingExpression,
+        DiagnosticKind::ExpectingOperator,
+        DiagnosticKind::InvalidName,
+        DiagnosticKind::InvalidFunction,
+        DiagnosticKind::InvalidNumberFormat,
+        DiagnosticKind::VarargOutsideVarargFunction,
+        DiagnosticKind::BreakOutsideLoop,
+        DiagnosticKind::StatementAfterReturn,
+        DiagnosticKind::StatementAfterBreak,
+        DiagnosticKind::TooManyLocals,
+        DiagnosticKind::TooManyUpvalues,
+        DiagnosticKind::InvalidEscape,
+        DiagnosticKind::EscapeTooLarge,
+        DiagnosticKind::ArithmeticOnNonNumber,
+        DiagnosticKind::CallingNonFunction,
+        DiagnosticKind::IndexingNonTable,
+        DiagnosticKind::ConcatenatingTable,
+        DiagnosticKind::TooManyArguments,
+        DiagnosticKind::PossiblyNil,
+        DiagnosticKind::UnknownDiagnosticCode,
+        DiagnosticKind::MissingFile,
+    ];
+
+    /// The name used to refer to the rule in configuration and output, these
+    /// shouldn't change once released
+    pub fn code(&self) -> &'static str {
+        match self {
+            DiagnosticKind::NotClosedBlock => "unclosed-block",
+            DiagnosticKind::NotClosedComment => "unclosed-comment",
+            DiagnosticKind::NotTerminatedString => "unclosed-string",
+            DiagnosticKind::UnexpectedKeyword => "unexpected-keyword",
+            DiagnosticKind::UnexpectedToken => "unexpected-token",
+            DiagnosticKind::UnexpectedOperator => "unexpected-operator",
+            DiagnosticKind::ExpectingComma => "expected-comma",
+            DiagnosticKind::ExpectingCommaOrBracket => "expected-comma-or-bracket",
+            DiagnosticKind::ExpectingThen => "expected-then",
+            DiagnosticKind::ExpectingDo => "expected-do",
+            DiagnosticKind::ExpectingToken => "expected-token",
+            DiagnosticKind::ExpectingName => "expected-name",
+            DiagnosticKind::ExpectingClosingBracket => "expected-closing-bracket",
+            DiagnosticKind::ExpectingFunctionCall => "expected-call",
+            DiagnosticKind::ExpectingExpression => "expected-expression",
+            DiagnosticKind::ExpectingOperator => "expected-assignment",
+            DiagnosticKind::InvalidName => "invalid-name",
+            DiagnosticKind::InvalidFunction => "invalid-function",
+            DiagnosticKind::InvalidNumberFormat => "malformed-number",
+            DiagnosticKind::VarargOutsideVarargFunction => "unexpect-dots",
+            DiagnosticKind::BreakOutsideLoop => "break-outside",
+            DiagnosticKind::StatementAfterReturn => "action-after-return",
+            DiagnosticKind::StatementAfterBreak => "action-after-break",
+            DiagnosticKind::TooManyLocals => "local-limit",
+            DiagnosticKind::TooManyUpvalues => "upvalue-limit",
+            DiagnosticKind::InvalidEscape => "err-esc",
+            DiagnosticKind::EscapeTooLarge => "escape-too-large",
+            DiagnosticKind::ArithmeticOnNonNumber => "arithmetic-on-non-number",
+            DiagnosticKind::CallingNonFunction => "call-non-function",
+            DiagnosticKind::IndexingNonTable => "index-non-table",
+            DiagnosticKind::ConcatenatingTable => "concat-table",
+            DiagnosticKind::TooManyArguments => "redundant-parameter",
+            DiagnosticKind::PossiblyNil => "need-check-nil",
+            DiagnosticKind::UnknownDiagnosticCode => "unknown-diag-code",
+            DiagnosticKind::MissingFile => "missing-file",
+        }
+    }
+
+    pub fn from_code(code: &str) -> Option<DiagnosticKind> {
+        DiagnosticKind::ALL.iter().copied().find(|k| k.code() == code)
+    }
+
+    pub fn category(&self) -> Category {
+        match self {
+            DiagnosticKind::ArithmeticOnNonNumber
+            | DiagnosticKind::CallingNonFunction
+            | DiagnosticKind::IndexingNonTable
+            | DiagnosticKind::ConcatenatingTable
+            | DiagnosticKind::TooManyArguments => Category::Type,
+            DiagnosticKind::PossiblyNil => Category::Nil,
+            DiagnosticKind::UnknownDiagnosticCode => Category::Annotation,
+            DiagnosticKind::MissingFile => Category::Addon,
+            _ => Category::Syntax,
+        }
+    }
+
+    /// Severity used when the configuration doesn't say otherwise
+    pub fn default_severity(&self) -> Severity {
+        if *self == DiagnosticKind::InvalidEscape {
+            return Severity::Warning
+        }
+        match self.category() {
+            Category::Syntax | Category::Addon => Severity::Error,
+            Category::Type | Category::Nil | Category::Annotation => Severity::Warning,
+        }
+    }
+
+    /// What the rule catches, for documentation and `--explain` style output
+    pub fn description(&self) -> &'static str {
+        match self {
+            DiagnosticKind::NotClosedBlock => "A `function`, `do`, `if`, loop or `repeat` block reaches the end of the file without its `end` or `until`.",
+            DiagnosticKind::NotClosedComment => "A long comment `--[[` is never closed.",
+            DiagnosticKind::NotTerminatedString => "A string literal runs to the end of the line or file without its closing quote.",
+            DiagnosticKind::UnexpectedKeyword => "A keyword appears where it can't be used, like `end` with nothing to close.",
+            DiagnosticKind::UnexpectedToken => "Something that can't start a statement appears where a statement should be.",
+            DiagnosticKind::UnexpectedOperator => "An operator or punctuation appears where it can't be used.",
+            DiagnosticKind::ExpectingComma => "Two items of a list aren't separated by a comma.",
+            DiagnosticKind::ExpectingCommaOrBracket => "A parameter list continues without a comma or closing bracket.",
+            DiagnosticKind::ExpectingThen => "An `if` or `elseif` condition isn't followed by `then`.",
+            DiagnosticKind::ExpectingDo => "A `while` or `for` loop header isn't followed by `do`.",
+            DiagnosticKind::ExpectingToken => "A required keyword, like the `in` of a `for` loop, is missing.",
+            DiagnosticKind::ExpectingName => "A name is needed here, like after `.`, `:` or `local`.",
+            DiagnosticKind::ExpectingClosingBracket => "A `(`, `[` or `{` is never closed.",
+            DiagnosticKind::ExpectingFunctionCall => "A method name after `:` isn't followed by call arguments.",
+            DiagnosticKind::ExpectingExpression => "A value is needed here, like after `=` or an operator.",
+            DiagnosticKind::ExpectingOperator => "A statement is just an expression, it must be a call or an assignment.",
+            DiagnosticKind::InvalidName => "A keyword is used as a variable or field name.",
+            DiagnosticKind::InvalidFunction => "A `function` statement has neither a name nor parameters.",
+            DiagnosticKind::InvalidNumberFormat => "A number literal isn't a valid Lua number.",
+            DiagnosticKind::VarargOutsideVarargFunction => "`...` is used in a function that doesn't take `...` parameters.",
+            DiagnosticKind::BreakOutsideLoop => "`break` is used outside of any loop in its function.",
+            DiagnosticKind::StatementAfterReturn => "A statement follows `return` in the same block, Lua 5.1 only allows `return` last.",
+            DiagnosticKind::StatementAfterBreak => "A statement follows `break` in the same block, Lua 5.1 only allows `break` last.",
+            DiagnosticKind::TooManyLocals => "A function has more than 200 local variables in scope at once.",
+            DiagnosticKind::TooManyUpvalues => "A function uses more than 60 locals from the functions around it.",
+            DiagnosticKind::InvalidEscape => "A string has an escape Lua 5.1 doesn't know, like `\\q`, which reads as just the character.",
+            DiagnosticKind::EscapeTooLarge => "A decimal escape `\\ddd` in a string is over 255.",
+            DiagnosticKind::ArithmeticOnNonNumber => "Arithmetic is done on a value that can never be a number.",
+            DiagnosticKind::CallingNonFunction => "A value that can never be a function is called.",
+            DiagnosticKind::IndexingNonTable => "A value that can never be a table is indexed.",
+            DiagnosticKind::ConcatenatingTable => "A table is concatenated with `..`, which errors without a metatable.",
+            DiagnosticKind::TooManyArguments => "A function is called with more arguments than it takes.",
+            DiagnosticKind::PossiblyNil => "A value that may be nil is indexed or called without a check.",
+            DiagnosticKind::UnknownDiagnosticCode => "A `---@diagnostic` comment names a code that doesn't exist.",
+            DiagnosticKind::MissingFile => "A `.toc` or `.xml` loads a file that doesn't exist.",
+        }
+    }
 }
 
+impl From<ErrorKind> for DiagnosticKind {
+    fn from(kind: ErrorKind) -> DiagnosticKind {
+        match kind {
+            ErrorKind::NotClosedBlock => DiagnosticKind::NotClosedBlock,
+            ErrorKind::NotClosedComment => DiagnosticKind::NotClosedComment,
+            ErrorKind::NotTerminatedString => DiagnosticKind::NotTerminatedString,
+            ErrorKind::InvalidNumberFormat => DiagnosticKind::InvalidNumberFormat,
+            ErrorKind::UnexpectedKeyword => DiagnosticKind::UnexpectedKeyword,
+            ErrorKind::UnexpectedToken => DiagnosticKind::UnexpectedToken,
+            ErrorKind::UnexpectedOperator => DiagnosticKind::UnexpectedOperator,
+            ErrorKind::ExpectingComma => DiagnosticKind::ExpectingComma,
+            ErrorKind::ExpectingCommaOrBracket => DiagnosticKind::ExpectingCommaOrBracket,
+            ErrorKind::ExpectingThen => DiagnosticKind::ExpectingThen,
+            ErrorKind::ExpectingDo => DiagnosticKind::ExpectingDo,
+            ErrorKind::ExpectingToken => DiagnosticKind::ExpectingToken,
+            ErrorKind::ExpectingName => DiagnosticKind::ExpectingName,
+            ErrorKind::ExpectingClosingBracket => DiagnosticKind::ExpectingClosingBracket,
+            ErrorKind::ExpectingFunctionCall => DiagnosticKind::ExpectingFunctionCall,
+            ErrorKind::ExpectingExpression => DiagnosticKind::ExpectingExpression,
+            ErrorKind::ExpectingOperator => DiagnosticKind::ExpectingOperator,
+            ErrorKind::InvalidName => DiagnosticKind::InvalidName,
+            ErrorKind::InvalidFunction => DiagnosticKind::InvalidFunction,
+        }
+    }
+}
+
+/// Another place in the file worth looking at to understand a diagnostic
+#[derive(Debug, Clone)]
+pub struct Related {
+    pub start: usize,
+    pub end: usize,
+    pub message: String,
+}
+
+#[derive(Debug, Clone)]
 pub struct Diagnostic {
-    kind: DiagnosticKind,
-    start: u32,
-    end: u32,
+    pub kind: DiagnosticKind,
+    pub start: usize,
+    pub end: usize,
+    pub message: String,
+    pub related: Option<Related>,
+}
+
+impl Diagnostic {
+    pub fn new(kind: DiagnosticKind, start: usize, end: usize, message: String) -> Diagnostic {
+        Diagnostic { kind, start, end, message, related: None }
+    }
+
+    pub fn from_syntax(error: &Error, text: &str) -> Diagnostic {
+        let related = error.opener.map(|(start, end)| Related {
+            start,
+            end,
+            message: format!("'{}' starts here", &text[start..end]),
+        });
+        Diagnostic {
+            kind: error.kind.into(),
+            start: error.start,
+            end: error.end,
+            message: error.message(text),
+            related,
+        }
+    }
+}
+
+/// Per rule severities, overriding the defaults. A rule mapped to `None` is off
+#[derive(Debug, Clone, Default)]
+pub struct Config {
+    rules: HashMap<DiagnosticKind, Option<Severity>>,
+    categories: HashMap<Category, Option<Severity>>,
+}
+
+impl Config {
+    /// Reads overrides from a JSON object like `{"need-check-nil": "off", "type": "error"}`,
+    /// keys being rule codes or category names. Returns the keys that weren't
+    /// understood alongside
+    pub fn from_json(value: &serde_json::Value) -> (Config, Vec<String>) {
+        let mut config = Config::default();
+        let mut unknown = Vec::new();
+        let Some(object) = value.as_object() else {
+            return (config, unknown)
+        };
+        for (key, value) in object {
+            let Some(severity) = value.as_str().and_then(Severity::from_name) else {
+                unknown.push(key.clone());
+                continue
+            };
+            if let Some(kind) = DiagnosticKind::from_code(key) {
+                config.rules.insert(kind, severity);
+            } else if let Some(category) = Category::from_name(key) {
+                config.categories.insert(category, severity);
+            } else {
+                unknown.push(key.clone());
+            }
+        }
+        (config, unknown)
+    }
+
+    /// The severity to report `kind` with, `None` if it's turned off
+    pub fn severity(&self, kind: DiagnosticKind) -> Option<Severity> {
+        if let Some(severity) = self.rules.get(&kind) {
+            return *severity
+        }
+        if let Some(severity) = self.categories.get(&kind.category()) {
+            return *severity
+        }
+        Some(kind.default_severity())
+    }
+}
+
+/// Which codes are turned off, everything but `kinds` when `all` is set
+#[derive(Debug, Clone, Default)]
+struct Disabled {
+    all: bool,
+    kinds: Vec<DiagnosticKind>,
+}
+
+impl Disabled {
+    fn covers(&self, kind: DiagnosticKind) -> bool {
+        self.all != self.kinds.contains(&kind)
+    }
+
+    fn disable(&mut self, kinds: &Option<Vec<DiagnosticKind>>) {
+        match kinds {
+            None => *self = Disabled { all: true, kinds: Vec::new() },
+            Some(kinds) if self.all => self.kinds.retain(|k| !kinds.contains(k)),
+            Some(kinds) => self.kinds.extend(kinds),
+        }
+    }
+
+    fn enable(&mut self, kinds: &Option<Vec<DiagnosticKind>>) {
+        match kinds {
+            None => *self = Disabled::default(),
+            Some(kinds) if self.all => self.kinds.extend(kinds),
+            Some(kinds) => self.kinds.retain(|k| !kinds.contains(k)),
+        }
+    }
+}
+
+/// Drops diagnostics turned off by `---@diagnostic` comments, adding warnings
+/// for codes in those comments that don't exist
+fn suppress(root: &SyntaxNode, text: &str, diagnostics: Vec<Diagnostic>) -> Vec<Diagnostic> {
+    let line_starts: Vec<usize> = std::iter::once(0).chain(text.match_indices('\n').map(|(i, _)| i + 1)).collect();
+    let line_range = |line: usize| {
+        let start = line_starts.get(line).copied().unwrap_or(text.len());
+        let end = line_starts.get(line + 1).copied().unwrap_or(text.len());
+        (start, end)
+    };
+
+    let mut unknown = Vec::new();
+    // Disable and enable comments in file order, with the line ones separate
+    let mut toggles = Vec::new();
+    let mut lines = Vec::new();
+    for token in root.descendants_with_tokens().filter_map(|e| e.into_token()) {
+        if token.kind() != SyntaxKind::Comment {
+            continue
+        }
+        let Some(directive) = annotations::parse_diagnostic(token.text()) else {
+            continue
+        };
+        let offset = usize::from(token.text_range().start());
+        let kinds = if directive.codes.is_empty() {
+            None
+        } else {
+            let mut kinds = Vec::new();
+            for (code, start) in &directive.codes {
+                if let Some(kind) = DiagnosticKind::from_code(code) {
+                    kinds.push(kind);
+                } else if !FOREIGN_CODES.contains(&code.as_str()) {
+                    let start = offset + start;
+                    let message = format!("unknown diagnostic code '{}'", code);
+                    unknown.push(Diagnostic::new(DiagnosticKind::UnknownDiagnosticCode, start, start + code.len(), message));
+                }
+            }
+            Some(kinds)
+        };
+        let line = line_starts.partition_point(|&s| s <= offset) - 1;
+        match directive.action {
+            DiagnosticAction::Disable | DiagnosticAction::Enable => toggles.push((offset, directive.action, kinds)),
+            DiagnosticAction::DisableLine => lines.push((line_range(line), kinds)),
+            DiagnosticAction::DisableNextLine => lines.push((line_range(line + 1), kinds)),
+        }
+    }
+
+    let mut diagnostics: Vec<Diagnostic> = diagnostics.into_iter().chain(unknown).collect();
+    diagnostics.sort_by_key(|d| (d.start, d.end));
+
+    let mut disabled = Disabled::default();
+    let mut toggles = toggles.into_iter().peekable();
+    diagnostics.retain(|d| {
+        while let Some((_, action, kinds)) = toggles.next_if(|(offset, _, _)| *offset <= d.start) {
+            if action == DiagnosticAction::Disable {
+                disabled.disable(&kinds);
+            } else {
+                disabled.enable(&kinds);
+            }
+        }
+        if disabled.covers(d.kind) {
+            return false
+        }
+        !lines.iter().any(|((start, end), kinds)| {
+            d.start >= *start && d.start < *end && kinds.as_ref().is_none_or(|k| k.contains(&d.kind))
+        })
+    });
+    diagnostics
+}
+
+/// Parses and scans a file, returning everything wrong with it in file order
+pub fn check(text: &str, filename: &str) -> Vec<Diagnostic> {
+    check_with_globals(text, filename, &GlobalTypes::new())
+}
+
+/// Checks the file as part of an addon, whose other files set `globals`
+pub fn check_with_globals(text: &str, filename: &str, globals: &GlobalTypes) -> Vec<Diagnostic> {
+    let mut parser = crate::syntax::syntax::Generator::new(text);
+    let green = parser.process_all();
+    let root = SyntaxNode::new_root(green.clone());
+    let mut diagnostics: Vec<Diagnostic> = parser.errors().iter().map(|e| Diagnostic::from_syntax(e, text)).collect();
+    diagnostics.extend(crate::validate::check(&root, text));
+    diagnostics.extend(crate::variables::get_diagnostics(green, filename, globals));
+    suppress(&root, text, diagnostics)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn codes(text: &str) -> Vec<&'static str> {
+        check(text, "test.lua").iter().map(|d| d.kind.code()).collect()
+    }
+
+    #[test]
+    fn accepts_luals_codes() {
+        assert_eq!(codes("---@diagnostic disable: miss-symbol, unknown-symbol, lowercase-global\nlocal x = 1\n"), Vec::<&str>::new());
+        assert_eq!(codes("---@diagnostic disable-next-line: luadoc-miss-type-name\nlocal x = 1\n"), Vec::<&str>::new());
+    }
+
+    #[test]
+    fn foreign_codes_are_not_ours() {
+        for kind in DiagnosticKind::ALL {
+            assert!(!FOREIGN_CODES.contains(&kind.code()), "{} is checked here", kind.code());
+        }
+    }
+
+    #[test]
+    fn reports_misspelt_codes() {
+        assert_eq!(codes("---@diagnostic disable: need-check-nill\nlocal x = 1\n"), ["unknown-diag-code"]);
+    }
+
+    #[test]
+    fn disables_the_next_line() {
+        let text = "local n = 1\n---@diagnostic disable-next-line: call-non-function\nn()\nn()\n";
+        assert_eq!(check(text, "test.lua").iter().map(|d| d.start).collect::<Vec<_>>(), [text.rfind("n()").unwrap()]);
+    }
+
+    #[test]
+    fn disables_the_same_line() {
+        assert_eq!(codes("local n = 1\nn() ---@diagnostic disable-line: call-non-function\n"), Vec::<&str>::new());
+    }
+
+    #[test]
+    fn disables_until_enabled() {
+        let text = "local n = 1\n---@diagnostic disable: call-non-function\nn()\nn()\n\
+            ---@diagnostic enable: call-non-function\nn()\n";
+        assert_eq!(codes(text), ["call-non-function"]);
+    }
+
+    #[test]
+    fn disables_everything_without_codes() {
+        let text = "local n = 1\nn()\nlocal s = 'a' .. {}\nreturn s\n";
+        assert_eq!(codes(text), ["call-non-function", "concat-table"]);
+        assert_eq!(codes(&format!("---@diagnostic disable\n{text}")), Vec::<&str>::new());
+    }
+
+    #[test]
+    fn luals_suppressions_cover_our_checks() {
+        let text = "while true do\n    break\n    ---@diagnostic disable-next-line: action-after-break\n    print(1)\nend\n";
+        assert_eq!(codes(text), Vec::<&str>::new());
+        let text = "local function f(a) return a end\n---@diagnostic disable-next-line: redundant-parameter\nf(1, 2)\n";
+        assert_eq!(codes(text), Vec::<&str>::new());
+    }
+
+    #[test]
+    fn leaves_other_codes() {
+        let text = "local n = 1\n---@diagnostic disable-next-line: concat-table\nn()\n";
+        assert_eq!(codes(text), ["call-non-function"]);
+    }
 }
@@ -0,0 +1,402 @@
+//Copyright (C) 2025-  plusmouse and other contributors
+//
+//This program is free software: you can redistribute it and/or modify
+//it under the terms of the GNU General Public License as published by
+//the Free Software Foundation, either version 3 of the License, or
+//(at your option) any later version.
+//
+//This program is distributed in the hope that it will be useful,
+//but WITHOUT ANY WARRANTY; without even the implied warranty of
+//MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//GNU General Public License for more details.
+//
+//You should have received a copy of the GNU General Public License
+//along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! `wow_ls dump <file>`: the parsed tree of a file, for tooling and golden tests.
+//!
+//! `tree` is the concrete tree, every node and token as `Kind@start..end`.
+//! `ast` is the typed view from [`crate::ast`], only what the analysis sees.
+//! `json` is the concrete tree with `kind`, `start`, `end` and `children` or `text`
+
+use std::fmt::Write;
+use std::io::Read;
+
+use crate::ast::{AstNode, Block, Expression, FunctionCall, FunctionDefinition, Identifier, MethodCall, Missing, Statement};
+use crate::syntax::{self, SyntaxNode};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Tree,
+    Ast,
+    Json,
+}
+
+struct AstPrinter {
+    out: String,
+}
+
+impl AstPrinter {
+    fn line(&mut self, depth: usize, node: &SyntaxNode, label: &str, detail: &str) {
+        let _ = write!(self.out, "{}", "  ".repeat(depth));
+        if !label.is_empty() {
+            let _ = write!(self.out, "{}: ", label);
+        }
+        let _ = write!(self.out, "{:?}@{:?}", node.kind(), node.text_range());
+        if !detail.is_empty() {
+            let _ = write!(self.out, " {}", detail);
+        }
+        // Tokens the parser had to make up, like the `end` of an unfinished function
+        for missing in node.children().filter_map(Missing::cast) {
+            if let Some(kind) = missing.expected() {
+                let _ = write!(self.out, " missing={:?}", kind);
+            }
+        }
+        self.out.push('\n');
+    }
+
+    /// For children that are `Option`s, so a missing one still shows up
+    fn missing(&mut self, depth: usize, label: &str) {
+        let _ = writeln!(self.out, "{}{}: <missing>", "  ".repeat(depth), label);
+    }
+
+    fn block(&mut self, depth: usize, label: &str, block: Option<Block>) {
+        let Some(block) = block else {
+            return self.missing(depth, label)
+        };
+        self.line(depth, block.syntax(), label, "");
+        for statement in block.statements() {
+            self.statement(depth + 1, &statement);
+        }
+    }
+
+    fn expressions(&mut self, depth: usize, label: &str, expressions: Vec<Expression>) {
+        for (i, expression) in expressions.iter().enumerate() {
+            self.expression(depth, &format!("{}[{}]", label, i), Some(expression));
+        }
+    }
+
+    fn function(&mut self, depth: usize, label: &str, function: &FunctionDefinition) {
+        let mut detail = String::new();
+        if function.is_local() {
+            detail.push_str("local ");
+        }
+        if let Some(name) = function.local_name().filter(|_| function.is_local()) {
+            let _ = write!(detail, "name={} ", name);
+        }
+        if let Some(params) = function.params() {
+            let mut parameters = params.names();
+            if let Some(varargs) = params.varargs() {
+                parameters.push(varargs.syntax().text().to_string());
+            }
+            let _ = write!(detail, "params=[{}]", parameters.join(", "));
+        }
+        self.line(depth, function.syntax(), label, detail.trim_end());
+        if let Some(identifier) = function.identifier() {
+            self.identifier(depth + 1, "name", &identifier);
+        }
+        self.block(depth + 1, "body", function.block());
+    }
+
+    fn identifier(&mut self, depth: usize, label: &str, identifier: &Identifier) {
+        if let Some(names) = identifier.name_chain() {
+            return self.line(depth, identifier.syntax(), label, &names.join("."))
+        }
+        let mut detail = String::new();
+        let fields = identifier.fields();
+        if !fields.is_empty() {
+            let _ = write!(detail, "fields={} ", fields.join("."));
+        }
+        if let Some(method) = identifier.method() {
+            let _ = write!(detail, "method={}", method);
+        }
+        self.line(depth, identifier.syntax(), label, detail.trim_end());
+        match identifier.prefix() {
+            Some(prefix) => self.expression(depth + 1, "prefix", Some(&prefix)),
+            None => {
+                let names = identifier.names();
+                if !names.is_empty() {
+                    let _ = writeln!(self.out, "{}names: {}", "  ".repeat(depth + 1), names.join("."));
+                }
+            }
+        }
+        if let Some(key) = identifier.key() {
+            self.expression(depth + 1, "key", Some(&key));
+        }
+    }
+
+    fn call(&mut self, depth: usize, label: &str, call: &FunctionCall) {
+        let detail = call.method().map(|m| format!("method={}", m)).unwrap_or_default();
+        self.line(depth, call.syntax(), label, &detail);
+        self.expression(depth + 1, "callee", call.callee().as_ref());
+        if let Some(arguments) = call.arguments() {
+            self.expressions(depth + 1, "args", arguments.expressions());
+        }
+    }
+
+    fn method_call(&mut self, depth: usize, label: &str, call: &MethodCall) {
+        let detail = call.method().map(|m| format!("method={}", m)).unwrap_or_default();
+        self.line(depth, call.syntax(), label, &detail);
+        match call.object() {
+            Some(object) => self.expression(depth + 1, "object", Some(&object)),
+            None => {
+                let _ = writeln!(self.out, "{}object: {}", "  ".repeat(depth + 1), call.object_names().join("."));
+            }
+        }
+        if let Some(arguments) = call.arguments() {
+            self.expressions(depth + 1, "args", arguments.expressions());
+        }
+    }
+
+    fn expression(&mut self, depth: usize, label: &str, expression: Option<&Expression>) {
+        let Some(expression) = expression else {
+            return self.missing(depth, label)
+        };
+        match expression {
+            Expression::UnaryExpression(u) => {
+                self.line(depth, u.syntax(), label, &format!("{:?}", u.kind()));
+                self.expressions(depth + 1, "operand", u.get_terms());
+            }
+            Expression::BinaryExpression(b) => {
+                self.line(depth, b.syntax(), label, &format!("{:?}", b.kind()));
+                self.expressions(depth + 1, "operand", b.get_terms());
+            }
+            Expression::GroupedExpression(g) => {
+                self.line(depth, g.syntax(), label, "");
+                self.expression(depth + 1, "inner", g.get_expression().as_ref());
+            }
+            Expression::PrefixExpression(p) => {
+                self.line(depth, p.syntax(), label, "");
+                self.expression(depth + 1, "inner", p.expression().as_ref());
+            }
+            Expression::Identifier(i) => self.identifier(depth, label, i),
+            Expression::Index(x) => {
+                self.line(depth, x.syntax(), label, "");
+                self.expression(depth + 1, "prefix", x.prefix().as_ref());
+                self.expression(depth + 1, "key", x.key().as_ref());
+            }
+            Expression::Literal(l) => {
+                let detail = if let Some(s) = l.get_string() {
+                    format!("string {:?}", s)
+                } else if let Some(n) = l.get_number() {
+                    format!("number {}", n.trim())
+                } else if let Some(b) = l.get_bool() {
+                    format!("boolean {}", b.trim())
+                } else if l.is_nil() {
+                    String::from("nil")
+                } else {
+                    l.syntax().text().to_string().trim().to_string()
+                };
+                self.line(depth, l.syntax(), label, &detail);
+            }
+            Expression::Function(f) => self.function(depth, label, f),
+            Expression::FunctionCall(c) => self.call(depth, label, c),
+            Expression::MethodCall(m) => self.method_call(depth, label, m),
+            Expression::TableConstructor(t) => {
+                self.line(depth, t.syntax(), label, "");
+                for (i, field) in t.fields().iter().enumerate() {
+                    let detail = match field.name() {
+                        Some(name) => format!("name={}", name),
+                        None if field.is_positional() => String::from("positional"),
+                        None => String::new(),
+                    };
+                    self.line(depth + 1, field.syntax(), &format!("field[{}]", i), &detail);
+                    if field.is_keyed() {
+                        self.expression(depth + 2, "key", field.key().as_ref());
+                    }
+                    self.expression(depth + 2, "value", field.value().as_ref());
+                }
+            }
+        }
+    }
+
+    fn statement(&mut self, depth: usize, statement: &Statement) {
+        match statement {
+            Statement::Assign(a) => {
+                self.line(depth, a.syntax(), "", "");
+                for (i, identifier) in a.variable_list().map(|l| l.identifiers()).unwrap_or_default().iter().enumerate() {
+                    self.identifier(depth + 1, &format!("target[{}]", i), identifier);
+                }
+                self.expressions(depth + 1, "value", a.expression_list().map(|l| l.expressions()).unwrap_or_default());
+            }
+            Statement::LocalAssign(a) => {
+                let names = a.name_list().map(|l| l.names()).unwrap_or_default();
+                self.line(depth, a.syntax(), "", &format!("names=[{}]", names.join(", ")));
+                self.expressions(depth + 1, "value", a.expression_list().map(|l| l.expressions()).unwrap_or_default());
+            }
+            Statement::FunctionCall(c) => self.call(depth, "", c),
+            Statement::Do(d) => {
+                self.line(depth, d.syntax(), "", "");
+                self.block(depth + 1, "body", d.block());
+            }
+            Statement::While(w) => {
+                self.line(depth, w.syntax(), "", "");
+                self.expression(depth + 1, "condition", w.condition().as_ref());
+                self.block(depth + 1, "body", w.block());
+            }
+            Statement::Repeat(r) => {
+                self.line(depth, r.syntax(), "", "");
+                self.block(depth + 1, "body", r.block());
+                self.expression(depth + 1, "condition", r.condition().as_ref());
+            }
+            Statement::If(i) => {
+                self.line(depth, i.syntax(), "", "");
+                for branch in i.if_branches() {
+                    self.line(depth + 1, branch.syntax(), "", "");
+                    self.expression(depth + 2, "condition", branch.expression().as_ref());
+                    self.block(depth + 2, "body", branch.block());
+                }
+                if let Some(branch) = i.else_branch() {
+                    self.line(depth + 1, branch.syntax(), "", "");
+                    if let Some(condition) = branch.expression() {
+                        self.expression(depth + 2, "condition", Some(&condition));
+                    }
+                    self.block(depth + 2, "body", branch.block());
+                }
+            }
+            Statement::ForCountLoop(f) => {
+                self.line(depth, f.syntax(), "", &format!("name={}", f.name().unwrap_or_default()));
+                self.expressions(depth + 1, "range", f.expression_list().map(|l| l.expressions()).unwrap_or_default());
+                self.block(depth + 1, "body", f.block());
+            }
+            Statement::ForInLoop(f) => {
+                let names = f.name_list().map(|l| l.names()).unwrap_or_default();
+                self.line(depth, f.syntax(), "", &format!("names=[{}]", names.join(", ")));
+                self.expressions(depth + 1, "iterator", f.expression_list().map(|l| l.expressions()).unwrap_or_default());
+                self.block(depth + 1, "body", f.block());
+            }
+            Statement::FunctionDefinition(f) => self.function(depth, "", f),
+            Statement::Return(r) => {
+                self.line(depth, r.syntax(), "", "");
+                self.expressions(depth + 1, "value", r.expression_list().map(|l| l.expressions()).unwrap_or_default());
+            }
+            Statement::Break(b) => self.line(depth, b.syntax(), "", ""),
+            Statement::Empty(e) => self.line(depth, e.syntax(), "", ""),
+        }
+    }
+}
+
+pub fn ast_string(root: &SyntaxNode) -> String {
+    let mut printer = AstPrinter { out: String::new() };
+    printer.block(0, "", Block::cast(root.clone()));
+    printer.out
+}
+
+const USAGE: &str = "usage: wow_ls dump [--format tree|ast|json] <file>
+
+tree is the default, - reads the file from stdin";
+
+pub fn run(args: &[String]) -> Result<(), String> {
+    let mut format = Format::Tree;
+    let mut path = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return Ok(())
+            }
+            "--format" => {
+                format = match args.next().map(String::as_str) {
+                    Some("tree") => Format::Tree,
+                    Some("ast") => Format::Ast,
+                    Some("json") => Format::Json,
+                    Some(name) => return Err(format!("unknown format '{}'\n\n{}", name, USAGE)),
+                    None => return Err(format!("--format needs a value\n\n{}", USAGE)),
+                };
+            }
+            _ if arg.starts_with('-') && arg != "-" => return Err(format!("unknown option '{}'\n\n{}", arg, USAGE)),
+            _ if path.is_some() => return Err(format!("only one file can be dumped\n\n{}", USAGE)),
+            _ => path = Some(arg.as_str()),
+        }
+    }
+    let Some(path) = path else {
+        return Err(String::from(USAGE))
+    };
+    let text = if path == "-" {
+        let mut text = String::new();
+        std::io::stdin().read_to_string(&mut text).map_err(|e| format!("stdin: {}", e))?;
+        text
+    } else {
+        std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?
+    };
+
+    print!("{}", dump(&text, format));
+    Ok(())
+}
+
+fn dump(text: &str, format: Format) -> String {
+    let mut parser = syntax::syntax::Generator::new(text);
+    let root = SyntaxNode::new_root(parser.process_all());
+    match format {
+        Format::Tree => syntax::debug::tree_string(&root),
+        Format::Ast => ast_string(&root),
+        Format::Json => format!("{:#}\n", syntax::debug::tree_json(&root)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dumps_the_tree() {
+        assert_eq!(dump("x = 1\n", Format::Tree), "\
+Block@0..6
+  AssignStatement@0..6
+    VariableList@0..2
+      Identifier@0..2
+        Name@0..1 \"x\"
+        Whitespace@1..2 \" \"
+    Assign@2..3 \"=\"
+    ExpressionList@3..6
+      Whitespace@3..4 \" \"
+      Literal@4..5
+        Number@4..5 \"1\"
+      Newline@5..6 \"\\n\"
+");
+    }
+
+    #[test]
+    fn dumps_the_ast() {
+        assert_eq!(dump("local t = { 1 }\nt.f(x)\n", Format::Ast), "\
+Block@0..23
+  LocalAssignStatement@0..16 names=[t]
+    value[0]: TableConstructor@10..15
+      field[0]: Field@12..14 positional
+        value: Literal@12..13 number 1
+  FunctionCall@16..22
+    callee: Identifier@16..19 t.f
+    args[0]: Identifier@20..21 x
+");
+    }
+
+    #[test]
+    fn dumps_made_up_tokens() {
+        assert_eq!(dump("local function f(a, ...)\n", Format::Ast), "\
+Block@0..25
+  FunctionDefinition@0..25 local name=f params=[a, ...] missing=EndKeyword
+    body: Block@25..25
+");
+    }
+
+    #[test]
+    fn dumps_json() {
+        let text = "local t = { 1 }\nt.f(x)\n";
+        let out = dump(text, Format::Json);
+        let root: serde_json::Value = serde_json::from_str(&out).unwrap();
+        assert_eq!(root["kind"], "Block");
+        assert_eq!((root["start"].as_u64(), root["end"].as_u64()), (Some(0), Some(text.len() as u64)));
+        assert_eq!(root["children"][1]["kind"], "FunctionCall");
+        // The tokens' text in order is the file again
+        fn texts(node: &serde_json::Value, out: &mut String) {
+            match node["text"].as_str() {
+                Some(text) => out.push_str(text),
+                None => node["children"].as_array().unwrap().iter().for_each(|c| texts(c, out)),
+            }
+        }
+        let mut round_trip = String::new();
+        texts(&root, &mut round_trip);
+        assert_eq!(round_trip, text);
+    }
+}
@@ -0,0 +1,189 @@
+//Copyright (C) 2025-  plusmouse and other contributors
+//
+//This program is free software: you can redistribute it and/or modify
+//it under the terms of the GNU General Public License as published by
+//the Free Software Foundation, either version 3 of the License, or
+//(at your option) any later version.
+//
+//This program is distributed in the hope that it will be useful,
+//but WITHOUT ANY WARRANTY; without even the implied warranty of
+//MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//GNU General Public License for more details.
+//
+//You should have received a copy of the GNU General Public License
+//along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Finding the Lua files an addon is made of
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use crate::settings::Flavor;
+
+fn has_extension(path: &Path, extension: &str) -> bool {
+    path.extension().is_some_and(|e| e.eq_ignore_ascii_case(extension))
+}
+
+/// Every `.lua` file at or under `path`, sorted so runs are comparable.
+/// Hidden directories like `.git` are skipped
+pub fn lua_files(path: &Path) -> std::io::Result<Vec<PathBuf>> {
+    find_files(path, &["lua"])
+}
+
+fn find_files(path: &Path, extensions: &[&str]) -> std::io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    if path.is_file() {
+        files.push(path.to_path_buf());
+        return Ok(files)
+    }
+    let mut pending = vec![path.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        for entry in std::fs::read_dir(&dir)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                if !path.file_name().is_some_and(|n| n.to_string_lossy().starts_with('.')) {
+                    pending.push(path);
+                }
+            } else if extensions.iter().any(|e| has_extension(&path, e)) {
+                files.push(path);
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// The `.lua`, `.xml` and `.toc` files of a workspace, along with any Lua
+/// files its `.toc`s load from outside it
+pub fn workspace_files(root: &Path, flavor: Option<Flavor>) -> std::io::Result<Vec<PathBuf>> {
+    let mut files = find_files(root, &["lua", "xml", "toc"])?;
+    let mut seen: HashSet<PathBuf> = files.iter().cloned().collect();
+    let tocs: Vec<PathBuf> = files.iter().filter(|f| has_extension(f, "toc")).cloned().collect();
+    for toc in tocs {
+        for file in toc_files(&toc, flavor).unwrap_or_default() {
+            if file.is_file() && seen.insert(file.clone()) {
+                files.push(file);
+            }
+        }
+    }
+    Ok(files)
+}
+
+/// TOC and XML paths are written Windows style, relative to the file naming them
+fn resolve(base: &Path, entry: &str) -> PathBuf {
+    let mut path = base.parent().unwrap_or(Path::new("")).to_path_buf();
+    for part in entry.trim().split(['\\', '/']).filter(|p| !p.is_empty()) {
+        path.push(part);
+    }
+    path
+}
+
+/// Where a `.toc` or `.xml` names a file, as byte offsets into it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Listing {
+    pub file: PathBuf,
+    pub start: usize,
+    pub end: usize,
+}
+
+/// The Lua files a `.toc` loads, in load order, following `.xml` includes.
+/// `[Family]` and `[Game]` are filled in from the flavor when there is one
+pub fn toc_files(toc: &Path, flavor: Option<Flavor>) -> std::io::Result<Vec<PathBuf>> {
+    Ok(toc_entries(toc, flavor)?.into_iter().map(|(path, _)| path).collect())
+}
+
+/// [`toc_files`] along with where each one is listed. Files are included
+/// whether they exist or not
+pub fn toc_entries(toc: &Path, flavor: Option<Flavor>) -> std::io::Result<Vec<(PathBuf, Listing)>> {
+    let text = std::fs::read_to_string(toc)?;
+    let mut files = Vec::new();
+    let mut seen = HashSet::new();
+    let mut offset = 0;
+    for raw in text.split_inclusive('\n') {
+        let start = offset + raw.len() - raw.trim_start().len();
+        offset += raw.len();
+        let line = raw.trim();
+        // `##` lines are metadata, `#` ones comments
+        if line.is_empty() || line.starts_with('#') {
+            continue
+        }
+        let listing = Listing { file: toc.to_path_buf(), start, end: start + line.len() };
+        let line = match flavor {
+            Some(flavor) => line.replace("[Family]", flavor.family()).replace("[Game]", flavor.game()),
+            None => String::from(line),
+        };
+        // Other substitutions depend on the client so can't be followed here
+        if line.contains('[') {
+            continue
+        }
+        add_entry(&resolve(toc, &line), listing, &mut files, &mut seen);
+    }
+    Ok(files)
+}
+
+fn add_entry(path: &Path, listing: Listing, files: &mut Vec<(PathBuf, Listing)>, seen: &mut HashSet<PathBuf>) {
+    if !seen.insert(path.to_path_buf()) {
+        return
+    }
+    if has_extension(path, "lua") {
+        files.push((path.to_path_buf(), listing));
+    } else if has_extension(path, "xml") {
+        // Missing XML files are the game's problem to report, not ours
+        let Ok(text) = std::fs::read_to_string(path) else {
+            return
+        };
+        for (start, include) in xml_includes(&text) {
+            let listing = Listing { file: path.to_path_buf(), start, end: start + include.len() };
+            add_entry(&resolve(path, include), listing, files, seen);
+        }
+    }
+}
+
+/// The `file` attributes of `<Script>` and `<Include>` tags, in order, with
+/// the offset of each
+fn xml_includes(text: &str) -> Vec<(usize, &str)> {
+    let mut includes = Vec::new();
+    let mut rest = text;
+    while let Some(open) = rest.find('<') {
+        rest = &rest[open + 1..];
+        let tag_end = rest.find('>').unwrap_or(rest.len());
+        let tag = &rest[..tag_end];
+        rest = &rest[tag_end..];
+        let name = tag.split(|c: char| c.is_whitespace() || c == '/').next().unwrap_or("");
+        if !name.eq_ignore_ascii_case("Script") && !name.eq_ignore_ascii_case("Include") {
+            continue
+        }
+        let Some(attribute) = tag.find("file=") else {
+            continue
+        };
+        let value = &tag[attribute + 5..];
+        let Some(quote) = value.chars().next().filter(|c| *c == '"' || *c == '\'') else {
+            continue
+        };
+        if let Some(end) = value[1..].find(quote) {
+            let start = text.len() - rest.len() - tag.len() + attribute + 6;
+            includes.push((start, &value[1..end + 1]));
+        }
+    }
+    includes
+}
+
+/// Expands files, directories and `.toc`s into the Lua files they cover,
+/// each only once, with where the `.toc` ones were listed
+pub fn expand(paths: &[PathBuf], flavor: Option<Flavor>) -> std::io::Result<Vec<(PathBuf, Option<Listing>)>> {
+    let mut files = Vec::new();
+    let mut seen = HashSet::new();
+    for path in paths {
+        let found: Vec<(PathBuf, Option<Listing>)> = if path.is_file() && has_extension(path, "toc") {
+            toc_entries(path, flavor)?.into_iter().map(|(file, listing)| (file, Some(listing))).collect()
+        } else {
+            lua_files(path)?.into_iter().map(|file| (file, None)).collect()
+        };
+        for (file, listing) in found {
+            if seen.insert(file.clone()) {
+                files.push((file, listing));
+            }
+        }
+    }
+    Ok(files)
+}
@@ -0,0 +1,288 @@
+//Copyright (C) 2025-  plusmouse and other contributors
+//
+//This program is free software: you can redistribute it and/or modify
+//it under the terms of the GNU General Public License as published by
+//the Free Software Foundation, either version 3 of the License, or
+//(at your option) any later version.
+//
+//This program is distributed in the hope that it will be useful,
+//but WITHOUT ANY WARRANTY; without even the implied warranty of
+//MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//GNU General Public License for more details.
+//
+//You should have received a copy of the GNU General Public License
+//along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Machine readable output for `check`: JSON lines, SARIF 2.1 and checkstyle XML.
+//! Lines and columns are one based everywhere, columns counting characters
+
+use std::fmt::Write;
+
+use serde_json::{json, Value};
+
+use crate::check::{line_column, line_starts, FileReport};
+use crate::diagnostics::{DiagnosticKind, Severity};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Human,
+    Json,
+    Sarif,
+    Checkstyle,
+}
+
+impl Format {
+    pub fn from_name(name: &str) -> Option<Format> {
+        match name {
+            "human" => Some(Format::Human),
+            "json" => Some(Format::Json),
+            "sarif" => Some(Format::Sarif),
+            "checkstyle" => Some(Format::Checkstyle),
+            _ => None,
+        }
+    }
+}
+
+/// Paths as forward slashed, which all three formats expect
+fn display_path(report: &FileReport) -> String {
+    report.path.to_string_lossy().replace('\\', "/")
+}
+
+struct Span {
+    start_line: usize,
+    start_column: usize,
+    end_line: usize,
+    end_column: usize,
+}
+
+fn span(text: &str, starts: &[usize], start: usize, end: usize) -> Span {
+    let (start_line, start_column) = line_column(text, starts, start);
+    let (end_line, end_column) = line_column(text, starts, end.max(start));
+    Span { start_line, start_column, end_line, end_column }
+}
+
+/// One JSON object per diagnostic, one per line
+pub fn json_lines(reports: &[FileReport]) -> String {
+    let mut out = String::new();
+    for report in reports {
+        let starts = line_starts(&report.text);
+        for (d, severity) in &report.diagnostics {
+            let s = span(&report.text, &starts, d.start, d.end);
+            let related = d.related.as_ref().map(|r| {
+                let s = span(&report.text, &starts, r.start, r.end);
+                json!({
+                    "message": r.message,
+                    "line": s.start_line,
+                    "column": s.start_column,
+                    "end_line": s.end_line,
+                    "end_column": s.end_column,
+                })
+            });
+            let value = json!({
+                "file": display_path(report),
+                "line": s.start_line,
+                "column": s.start_column,
+                "end_line": s.end_line,
+                "end_column": s.end_column,
+                "severity": severity.name(),
+                "code": d.kind.code(),
+                "category": d.kind.category().name(),
+                "message": d.message,
+                "related": related,
+            });
+            let _ = writeln!(out, "{}", value);
+        }
+    }
+    out
+}
+
+fn sarif_level(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Error => "error",
+        Severity::Warning => "warning",
+        Severity::Information | Severity::Hint => "note",
+    }
+}
+
+/// Enough of RFC 3986 for relative file paths
+fn sarif_uri(path: &str) -> String {
+    let mut uri = String::new();
+    for b in path.bytes() {
+        if b.is_ascii_alphanumeric() || b"/-._~".contains(&b) {
+            uri.push(b as char);
+        } else {
+            let _ = write!(uri, "%{:02X}", b);
+        }
+    }
+    uri
+}
+
+fn sarif_location(uri: &str, s: &Span) -> Value {
+    json!({
+        "physicalLocation": {
+            "artifactLocation": { "uri": uri },
+            "region": {
+                "startLine": s.start_line,
+                "startColumn": s.start_column,
+                "endLine": s.end_line,
+                "endColumn": s.end_column,
+            },
+        },
+    })
+}
+
+pub fn sarif(reports: &[FileReport]) -> String {
+    let rules: Vec<Value> = DiagnosticKind::ALL.iter().map(|kind| json!({
+        "id": kind.code(),
+        "shortDescription": { "text": kind.description() },
+        "defaultConfiguration": { "level": sarif_level(kind.default_severity()) },
+        "properties": { "category": kind.category().name() },
+    })).collect();
+
+    let mut results = Vec::new();
+    for report in reports {
+        let starts = line_starts(&report.text);
+        let uri = sarif_uri(&display_path(report));
+        for (d, severity) in &report.diagnostics {
+            let mut result = json!({
+                "ruleId": d.kind.code(),
+                "ruleIndex": DiagnosticKind::ALL.iter().position(|k| *k == d.kind),
+                "level": sarif_level(*severity),
+                "message": { "text": d.message },
+                "locations": [sarif_location(&uri, &span(&report.text, &starts, d.start, d.end))],
+            });
+            if let Some(r) = &d.related {
+                let mut location = sarif_location(&uri, &span(&report.text, &starts, r.start, r.end));
+                location["id"] = json!(0);
+                location["message"] = json!({ "text": r.message });
+                result["relatedLocations"] = json!([location]);
+            }
+            results.push(result);
+        }
+    }
+
+    let log = json!({
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": "wow_ls",
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "rules": rules,
+                },
+            },
+            "columnKind": "unicodeCodePoints",
+            "results": results,
+        }],
+    });
+    format!("{:#}\n", log)
+}
+
+fn xml_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            // Control characters aren't allowed in XML 1.0 at all
+            c if c.is_control() && !matches!(c, '\t' | '\n' | '\r') => escaped.push('\u{fffd}'),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+pub fn checkstyle(reports: &[FileReport]) -> String {
+    let mut out = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<checkstyle version=\"4.3\">\n");
+    for report in reports {
+        let starts = line_starts(&report.text);
+        let _ = writeln!(out, "  <file name=\"{}\">", xml_escape(&display_path(report)));
+        for (d, severity) in &report.diagnostics {
+            let (line, column) = line_column(&report.text, &starts, d.start);
+            let severity = match severity {
+                Severity::Error => "error",
+                Severity::Warning => "warning",
+                Severity::Information | Severity::Hint => "info",
+            };
+            let _ = writeln!(out, "    <error line=\"{}\" column=\"{}\" severity=\"{}\" message=\"{}\" source=\"wow_ls.{}\"/>",
+                line, column, severity, xml_escape(&d.message), d.kind.code());
+        }
+        let _ = writeln!(out, "  </file>");
+    }
+    out.push_str("</checkstyle>\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use crate::diagnostics::{Diagnostic, Related};
+
+    /// A call on line 2 with its related span after an `é` on line 1, so
+    /// character and byte columns differ
+    fn reports() -> Vec<FileReport> {
+        let text = String::from("local é, n = 1, 2\nn()\n");
+        let start = text.find("n()").unwrap();
+        let mut d = Diagnostic::new(DiagnosticKind::CallingNonFunction, start, start + 3, String::from("Can't call 'n' <a \"number\"> & more"));
+        let name = text.find("n =").unwrap();
+        d.related = Some(Related { start: name, end: name + 1, message: String::from("set here") });
+        vec![FileReport { path: PathBuf::from("addon\\my file.lua"), text, diagnostics: vec![(d, Severity::Warning)] }]
+    }
+
+    #[test]
+    fn writes_json_lines() {
+        let out = json_lines(&reports());
+        assert_eq!(out.lines().count(), 1);
+        let value: Value = serde_json::from_str(&out).unwrap();
+        assert_eq!(value, json!({
+            "file": "addon/my file.lua",
+            "line": 2,
+            "column": 1,
+            "end_line": 2,
+            "end_column": 4,
+            "severity": "warning",
+            "code": "call-non-function",
+            "category": "type",
+            "message": "Can't call 'n' <a \"number\"> & more",
+            "related": { "message": "set here", "line": 1, "column": 10, "end_line": 1, "end_column": 11 },
+        }));
+    }
+
+    #[test]
+    fn writes_sarif() {
+        let log: Value = serde_json::from_str(&sarif(&reports())).unwrap();
+        assert_eq!(log["version"], "2.1.0");
+        let run = &log["runs"][0];
+        assert_eq!(run["columnKind"], "unicodeCodePoints");
+        let result = &run["results"][0];
+        assert_eq!(result["ruleId"], "call-non-function");
+        assert_eq!(run["tool"]["driver"]["rules"][result["ruleIndex"].as_u64().unwrap() as usize]["id"], "call-non-function");
+        assert_eq!(result["level"], "warning");
+        assert_eq!(result["message"]["text"], "Can't call 'n' <a \"number\"> & more");
+        let location = &result["locations"][0]["physicalLocation"];
+        assert_eq!(location["artifactLocation"]["uri"], "addon/my%20file.lua");
+        assert_eq!(location["region"], json!({ "startLine": 2, "startColumn": 1, "endLine": 2, "endColumn": 4 }));
+        let related = &result["relatedLocations"][0];
+        assert_eq!(related["message"]["text"], "set here");
+        assert_eq!(related["physicalLocation"]["region"]["startColumn"], 10);
+    }
+
+    #[test]
+    fn writes_checkstyle() {
+        assert_eq!(checkstyle(&reports()), "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<checkstyle version=\"4.3\">\n  \
+            <file name=\"addon/my file.lua\">\n    \
+            <error line=\"2\" column=\"1\" severity=\"warning\" \
+            message=\"Can&apos;t call &apos;n&apos; &lt;a &quot;number&quot;&gt; &amp; more\" source=\"wow_ls.call-non-function\"/>\n  \
+            </file>\n</checkstyle>\n");
+    }
+
+    #[test]
+    fn replaces_characters_xml_cant_hold() {
+        assert_eq!(xml_escape("a\u{1}b\tc"), "a\u{fffd}b\tc");
+    }
+}
@@ -1,4 +1,11 @@
 mod main_loop;
+mod cache;
 mod diagnostics;
+mod documents;
+mod hover;
+mod inspect;
+mod line_index;
+mod workers;
+mod workspace;
 
 pub use main_loop::start_ls;
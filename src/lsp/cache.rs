@@ -0,0 +1,122 @@
+//Copyright (C) 2025-  plusmouse and other contributors
+//
+//This program is free software: you can redistribute it and/or modify
+//it under the terms of the GNU General Public License as published by
+//the Free Software Foundation, either version 3 of the License, or
+//(at your option) any later version.
+//
+//This program is distributed in the hope that it will be useful,
+//but WITHOUT ANY WARRANTY; without even the implied warranty of
+//MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//GNU General Public License for more details.
+//
+//You should have received a copy of the GNU General Public License
+//along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Summaries of a workspace's files from the last run, so startup only parses
+//! the files that changed since
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use serde_json::{json, Value};
+
+use crate::symbols::{GlobalDefinition, Summary};
+
+/// Bumped when summaries change shape or meaning, so old caches get ignored
+const FORMAT: u64 = 1;
+
+/// FNV-1a, which unlike std's hasher is the same from one build to the next
+pub fn hash(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in bytes {
+        hash ^= u64::from(*byte);
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+pub type Entries = HashMap<String, (u64, Summary)>;
+
+pub struct Cache {
+    path: PathBuf,
+}
+
+impl Cache {
+    /// One cache per set of workspace roots, `None` without a cache directory
+    pub fn for_roots(roots: &[PathBuf]) -> Option<Cache> {
+        if roots.is_empty() {
+            return None
+        }
+        let mut names: Vec<String> = roots.iter().map(|r| r.to_string_lossy().into_owned()).collect();
+        names.sort();
+        let path = cache_dir()?.join(format!("{:016x}.json", hash(names.join("\n").as_bytes())));
+        Some(Cache { path })
+    }
+
+    /// Anything unreadable or from another version means starting afresh
+    pub fn load(&self) -> Entries {
+        let Ok(text) = std::fs::read_to_string(&self.path) else {
+            return Entries::new()
+        };
+        let Ok(value) = serde_json::from_str::<Value>(&text) else {
+            return Entries::new()
+        };
+        if value["format"] != FORMAT || value["version"] != env!("CARGO_PKG_VERSION") {
+            return Entries::new()
+        }
+        let Some(files) = value["files"].as_object() else {
+            return Entries::new()
+        };
+        files.iter().filter_map(|(uri, entry)| {
+            let hash = u64::from_str_radix(entry["hash"].as_str()?, 16).ok()?;
+            Some((uri.clone(), (hash, summary_from_json(entry)?)))
+        }).collect()
+    }
+
+    pub fn save<'a>(&self, entries: impl Iterator<Item = (&'a str, u64, &'a Summary)>) -> std::io::Result<()> {
+        let files: serde_json::Map<String, Value> = entries.map(|(uri, hash, summary)| {
+            let definitions: Vec<Value> = summary.definitions.iter()
+                .map(|d| json!([d.name, d.start, d.end, d.value_type]))
+                .collect();
+            (String::from(uri), json!({
+                "hash": format!("{hash:016x}"),
+                "definitions": definitions,
+                "references": summary.references,
+            }))
+        }).collect();
+        let value = json!({
+            "format": FORMAT,
+            "version": env!("CARGO_PKG_VERSION"),
+            "files": files,
+        });
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        // Written aside then moved, so a crash can't leave half a cache
+        let temporary = self.path.with_extension("tmp");
+        std::fs::write(&temporary, value.to_string())?;
+        std::fs::rename(&temporary, &self.path)
+    }
+}
+
+fn summary_from_json(entry: &Value) -> Option<Summary> {
+    let definitions = entry["definitions"].as_array()?.iter().map(|d| Some(GlobalDefinition {
+        name: String::from(d[0].as_str()?),
+        start: usize::try_from(d[1].as_u64()?).ok()?,
+        end: usize::try_from(d[2].as_u64()?).ok()?,
+        value_type: String::from(d[3].as_str()?),
+    })).collect::<Option<Vec<_>>>()?;
+    let references = entry["references"].as_array()?.iter()
+        .map(|r| r.as_str().map(String::from))
+        .collect::<Option<Vec<_>>>()?;
+    Some(Summary { definitions, references })
+}
+
+/// `$XDG_CACHE_HOME/wow_ls`, `~/.cache/wow_ls` or `%LOCALAPPDATA%\wow_ls`
+fn cache_dir() -> Option<PathBuf> {
+    let base = std::env::var_os("XDG_CACHE_HOME").filter(|d| !d.is_empty()).map(PathBuf::from)
+        .or_else(|| std::env::var_os("LOCALAPPDATA").map(PathBuf::from))
+        .or_else(|| std::env::var_os("HOME").map(|h| Path::new(&h).join(".cache")))?;
+    Some(base.join("wow_ls"))
+}
@@ -14,38 +14,81 @@
 //along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
 use lsp_server::{Connection, ExtractError, Message, Notification, Request, RequestId, Response};
-use lsp_types::{request, Diagnostic, DiagnosticSeverity, Position, PublishDiagnosticsParams, Range, Uri};
+use lsp_types::{request, Diagnostic, DiagnosticRelatedInformation, DiagnosticSeverity, Location, NumberOrString, PublishDiagnosticsParams, Uri};
 
-pub fn get(connection: &Connection, uri: Uri, text: &str) {
-    let mut parser = crate::syntax::syntax::Generator::new(text);
-    let numbers = line_numbers::LinePositions::from(text);
-    let _green_tree = parser.process_all();
-    let errors = parser.errors();
+use crate::diagnostics::{Config, Severity};
+use crate::lsp::line_index::{Encoding, LineIndex};
+use crate::lsp::cache;
+use crate::settings::Settings;
+use crate::state::Globals;
+use crate::symbols::{self, GlobalTypes};
 
-    let mut diagnostics: Vec<Diagnostic> = Vec::with_capacity(errors.len());
+/// The file's diagnostics as LSP has them, after the config's severities
+pub fn compute(uri: &Uri, text: &str, config: &Config, globals: &GlobalTypes, encoding: Encoding) -> Vec<Diagnostic> {
+    let index = LineIndex::new(text, encoding);
+    let to_range = |start: usize, end: usize| index.range(start, end);
 
-    for e in errors {
-        let start = numbers.from_offset(e.start);
-        let end = numbers.from_offset(e.end);
+    let mut diagnostics: Vec<Diagnostic> = Vec::new();
+
+    for d in crate::diagnostics::check_with_globals(text, uri.as_str(), globals) {
+        let Some(severity) = config.severity(d.kind) else {
+            continue
+        };
+        let related_information = d.related.map(|r| vec![DiagnosticRelatedInformation {
+            location: Location { uri: uri.clone(), range: to_range(r.start, r.end) },
+            message: r.message,
+        }]);
         diagnostics.push(Diagnostic {
-            range: Range {
-                start: Position { line: start.0.0, character: start.1 as u32},
-                end: Position { line: end.0.0, character: end.1 as u32},
-            },
-            severity: Some(DiagnosticSeverity::ERROR),
-            code: None,
+            range: to_range(d.start, d.end),
+            severity: Some(to_lsp_severity(severity)),
+            code: Some(NumberOrString::String(String::from(d.kind.code()))),
             code_description: None,
             source: Some(String::from("wow_ls")),
-            message: format!("{:?}", e.kind),
+            message: d.message,
             tags: None,
-            related_information: None,
+            related_information,
             data: None,
         });
     }
 
+    diagnostics
+}
+
+/// Diagnostics asked for by the client, which can be told nothing changed
+/// since a result it already has
+pub enum Pulled {
+    Unchanged(String),
+    Full(String, Vec<Diagnostic>),
+}
+
+pub fn pull(uri: &Uri, text: &str, previous: Option<&str>, globals: &Globals, settings: &Settings, encoding: Encoding) -> Pulled {
+    let summary = symbols::summarize(text);
+    let types = globals.types_for(uri.as_str(), &summary.references);
+    let id = result_id(text, &types, settings.fingerprint);
+    if previous == Some(id.as_str()) {
+        return Pulled::Unchanged(id)
+    }
+    let diagnostics = compute(uri, text, &settings.diagnostics, &types, encoding);
+    Pulled::Full(id, diagnostics)
+}
+
+/// Diagnostics only depend on the text, the types of the globals from other
+/// files it uses and the settings, so those make up the id of a result
+fn result_id(text: &str, globals: &GlobalTypes, settings: u64) -> String {
+    let mut types: Vec<(&String, &String)> = globals.iter().collect();
+    types.sort_unstable();
+    let mut id = cache::hash(text.as_bytes()) ^ settings;
+    for (name, value_type) in types {
+        id = id.rotate_left(7) ^ cache::hash(format!("{name}={value_type}").as_bytes());
+    }
+    format!("{id:016x}")
+}
+
+/// Sends the diagnostics for `version` of the file, an empty list clearing them
+pub fn publish(connection: &Connection, uri: Uri, version: Option<i32>, diagnostics: Vec<Diagnostic>) {
     let params = PublishDiagnosticsParams {
         uri,
-        version: None,
+        version,
         diagnostics,
     };
     let Ok(encoded) = serde_json::to_value(params) else {
@@ -55,5 +98,71 @@ pub fn get(connection: &Connection, uri: Uri, text: &str) {
         method: String::from("textDocument/publishDiagnostics"),
         params: encoded,
     };
-    connection.sender.send(Message::Notification(not));
+    let _ = connection.sender.send(Message::Notification(not));
+}
+
+fn to_lsp_severity(severity: Severity) -> DiagnosticSeverity {
+    match severity {
+        Severity::Hint => DiagnosticSeverity::HINT,
+        Severity::Information => DiagnosticSeverity::INFORMATION,
+        Severity::Warning => DiagnosticSeverity::WARNING,
+        Severity::Error => DiagnosticSeverity::ERROR,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+    use serde_json::json;
+    use crate::state::State;
+
+    fn uri() -> Uri {
+        Uri::from_str("file:///addon/Core.lua").unwrap()
+    }
+
+    fn full(pulled: Pulled) -> (String, Vec<Diagnostic>) {
+        match pulled {
+            Pulled::Full(id, diagnostics) => (id, diagnostics),
+            Pulled::Unchanged(id) => panic!("unchanged {id}"),
+        }
+    }
+
+    #[test]
+    fn unchanged_for_the_current_result() {
+        let globals = State::default().globals();
+        let settings = Settings::default();
+        let text = "local n = 1\nn()\n";
+        let (id, diagnostics) = full(pull(&uri(), text, None, &globals, &settings, Encoding::Utf16));
+        assert_eq!(diagnostics.len(), 1);
+        match pull(&uri(), text, Some(&id), &globals, &settings, Encoding::Utf16) {
+            Pulled::Unchanged(same) => assert_eq!(same, id),
+            Pulled::Full(..) => panic!("reported again without a change"),
+        }
+    }
+
+    #[test]
+    fn full_after_an_edit() {
+        let globals = State::default().globals();
+        let settings = Settings::default();
+        let (id, _) = full(pull(&uri(), "local n = 1\nn()\n", None, &globals, &settings, Encoding::Utf16));
+        let (edited, diagnostics) = full(pull(&uri(), "local n = print\nn()\n", Some(&id), &globals, &settings, Encoding::Utf16));
+        assert_ne!(edited, id);
+        assert!(diagnostics.is_empty());
+    }
+
+    #[test]
+    fn full_after_another_file_or_the_settings_change() {
+        let mut state = State::default();
+        let text = "Count()\n";
+        let settings = Settings::default();
+        let (id, diagnostics) = full(pull(&uri(), text, None, &state.globals(), &settings, Encoding::Utf16));
+        assert!(diagnostics.is_empty());
+        state.set_file(String::from("file:///addon/Data.lua"), symbols::summarize("Count = 5\n"), None);
+        let (changed, diagnostics) = full(pull(&uri(), text, Some(&id), &state.globals(), &settings, Encoding::Utf16));
+        assert_eq!(diagnostics.len(), 1);
+        let (strict, _) = Settings::from_json(&json!({ "diagnostics": { "severity": { "call-non-function": "error" } } }), None);
+        let (_, diagnostics) = full(pull(&uri(), text, Some(&changed), &state.globals(), &strict, Encoding::Utf16));
+        assert_eq!(diagnostics[0].severity, Some(DiagnosticSeverity::ERROR));
+    }
 }
@@ -0,0 +1,82 @@
+//Copyright (C) 2025-  plusmouse and other contributors
+//
+//This program is free software: you can redistribute it and/or modify
+//it under the terms of the GNU General Public License as published by
+//the Free Software Foundation, either version 3 of the License, or
+//(at your option) any later version.
+//
+//This program is distributed in the hope that it will be useful,
+//but WITHOUT ANY WARRANTY; without even the implied warranty of
+//MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//GNU General Public License for more details.
+//
+//You should have received a copy of the GNU General Public License
+//along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! The buffers the editor has open, which win over what's on disk. Each
+//! version is immutable and shared, so work on other threads reads a snapshot
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+pub struct Document {
+    pub text: String,
+    pub language: String,
+    /// The editor's version, which goes up with every edit
+    pub version: i32,
+}
+
+impl Document {
+    pub fn is_lua(&self) -> bool {
+        self.language == "lua"
+    }
+}
+
+#[derive(Default)]
+pub struct Documents {
+    open: HashMap<String, Arc<Document>>,
+}
+
+impl Documents {
+    pub fn open(&mut self, uri: String, language: String, version: i32, text: String) {
+        self.open.insert(uri, Arc::new(Document { text, language, version }));
+    }
+
+    /// Returns false for edits to unknown documents or older than what's held,
+    /// which can't be applied
+    pub fn change(&mut self, uri: &str, version: i32, text: String) -> bool {
+        match self.open.get_mut(uri) {
+            Some(document) if version >= document.version => {
+                *document = Arc::new(Document { text, language: document.language.clone(), version });
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Saving doesn't bump the version, so the saved text replaces the buffer
+    /// as it is. Returns whether it differed
+    pub fn save(&mut self, uri: &str, text: String) -> bool {
+        match self.open.get_mut(uri) {
+            Some(document) if document.text != text => {
+                *document = Arc::new(Document { text, language: document.language.clone(), version: document.version });
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub fn close(&mut self, uri: &str) -> Option<Arc<Document>> {
+        self.open.remove(uri)
+    }
+
+    pub fn get(&self, uri: &str) -> Option<Arc<Document>> {
+        self.open.get(uri).cloned()
+    }
+
+    /// Whether results worked out from `snapshot` still describe the buffer.
+    /// A save can change the text without a new version, so versions alone won't do
+    pub fn is_current(&self, uri: &str, snapshot: &Arc<Document>) -> bool {
+        self.open.get(uri).is_some_and(|d| Arc::ptr_eq(d, snapshot))
+    }
+}
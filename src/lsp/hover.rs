@@ -0,0 +1,91 @@
+//Copyright (C) 2025-  plusmouse and other contributors
+//
+//This program is free software: you can redistribute it and/or modify
+//it under the terms of the GNU General Public License as published by
+//the Free Software Foundation, either version 3 of the License, or
+//(at your option) any later version.
+//
+//This program is distributed in the hope that it will be useful,
+//but WITHOUT ANY WARRANTY; without even the implied warranty of
+//MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//GNU General Public License for more details.
+//
+//You should have received a copy of the GNU General Public License
+//along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use lsp_types::{Hover, HoverContents, MarkupContent, MarkupKind, Position};
+
+use crate::ast::{AstNode, Expression};
+use crate::lsp::line_index::{Encoding, LineIndex};
+use crate::syntax::{SyntaxKind, SyntaxNode};
+use crate::syntax::literals;
+
+/// Shows what a constant expression like `60 * 60 * 24` comes to
+pub fn get(text: &str, position: Position, encoding: Encoding) -> Option<Hover> {
+    let index = LineIndex::new(text, encoding);
+    let offset = index.offset(position);
+    let mut parser = crate::syntax::syntax::Generator::new(text);
+    let root = SyntaxNode::new_root(parser.process_all());
+    let token = root.token_at_offset(offset.try_into().ok()?).right_biased()?;
+
+    // The widest expression around the cursor that's still all constants
+    let mut best = None;
+    for node in token.parent_ancestors() {
+        match node.kind() {
+            SyntaxKind::BinaryExpression | SyntaxKind::UnaryExpression | SyntaxKind::GroupedExpression
+            | SyntaxKind::Expression | SyntaxKind::Literal => (),
+            _ => break,
+        }
+        let Some(expression) = Expression::cast(node.clone()) else {
+            continue
+        };
+        match expression.constant() {
+            Some(value) => best = Some((node, expression, value)),
+            None if matches!(expression, Expression::Literal(_)) => (),
+            None => break,
+        }
+    }
+    let (node, expression, value) = best?;
+    let formatted = literals::format_number(value);
+    // Nothing to add for a plain `5`
+    if matches!(expression, Expression::Literal(_)) && node.text() == formatted.as_str() {
+        return None
+    }
+
+    // Expression nodes carry the whitespace after them, which shouldn't be highlighted
+    let mut tokens = node.descendants_with_tokens().filter_map(|n| n.into_token())
+        .filter(|t| !matches!(t.kind(), SyntaxKind::Whitespace | SyntaxKind::Newline | SyntaxKind::Comment));
+    let first = tokens.next()?;
+    let last = tokens.last().unwrap_or_else(|| first.clone());
+    Some(Hover {
+        contents: HoverContents::Markup(MarkupContent {
+            kind: MarkupKind::Markdown,
+            value: format!("```lua\n{}\n```", formatted),
+        }),
+        range: Some(index.range(first.text_range().start().into(), last.text_range().end().into())),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hover(text: &str, character: u32) -> Option<String> {
+        match get(text, Position::new(0, character), Encoding::Utf16)?.contents {
+            HoverContents::Markup(m) => Some(m.value),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn shows_the_whole_expression() {
+        assert_eq!(hover("local x = 2+3*4", 10).as_deref(), Some("```lua\n14\n```"));
+        assert_eq!(hover("local x = -2^2", 11).as_deref(), Some("```lua\n-4\n```"));
+        assert_eq!(hover("local x = 2^3^2", 10).as_deref(), Some("```lua\n512\n```"));
+    }
+
+    #[test]
+    fn nothing_for_a_plain_number() {
+        assert_eq!(hover("local x = 5", 10), None);
+    }
+}
@@ -0,0 +1,141 @@
+//Copyright (C) 2025-  plusmouse and other contributors
+//
+//This program is free software: you can redistribute it and/or modify
+//it under the terms of the GNU General Public License as published by
+//the Free Software Foundation, either version 3 of the License, or
+//(at your option) any later version.
+//
+//This program is distributed in the hope that it will be useful,
+//but WITHOUT ANY WARRANTY; without even the implied warranty of
+//MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//GNU General Public License for more details.
+//
+//You should have received a copy of the GNU General Public License
+//along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! `wowls/syntaxTree` and `wowls/inferredTypes`, for seeing what the server
+//! made of a buffer without leaving the editor
+
+use lsp_types::Range;
+use serde_json::{json, Value};
+
+use crate::lsp::line_index::{Encoding, LineIndex};
+use crate::syntax::{self, SyntaxNode};
+
+pub const SYNTAX_TREE: &str = "wowls/syntaxTree";
+pub const INFERRED_TYPES: &str = "wowls/inferredTypes";
+
+/// The uri of the `textDocument` every request here takes
+pub fn document_uri(params: &Value) -> Option<String> {
+    params.pointer("/textDocument/uri").and_then(Value::as_str).map(String::from)
+}
+
+/// The tree as `wow_ls dump` prints it, only the smallest node covering
+/// `range` when there is one
+pub fn syntax_tree(text: &str, params: &Value, encoding: Encoding) -> Option<String> {
+    let mut parser = syntax::syntax::Generator::new(text);
+    let root = SyntaxNode::new_root(parser.process_all());
+    let range = match params.get("range").filter(|r| !r.is_null()) {
+        Some(range) => Some(serde_json::from_value::<Range>(range.clone()).ok()?),
+        None => None,
+    };
+    let node = match range {
+        Some(range) => {
+            let index = LineIndex::new(text, encoding);
+            let start = index.offset(range.start);
+            let end = index.offset(range.end).max(start);
+            let range = rowan::TextRange::new(start.try_into().ok()?, end.try_into().ok()?);
+            match root.covering_element(range) {
+                rowan::NodeOrToken::Node(n) => n,
+                rowan::NodeOrToken::Token(t) => t.parent()?,
+            }
+        }
+        None => root,
+    };
+    Some(syntax::debug::tree_string(&node))
+}
+
+/// Every declaration the type scan records, with its type and where it is
+pub fn inferred_types(text: &str, uri: &str, encoding: Encoding) -> Value {
+    let mut parser = syntax::syntax::Generator::new(text);
+    let green = parser.process_all();
+    let index = LineIndex::new(text, encoding);
+    let types: Vec<Value> = crate::variables::get_type_table(green, uri).into_iter().map(|t| {
+        let signature = t.signature.map(|(parameters, varargs, returns)| json!({
+            "parameters": parameters,
+            "varargs": varargs,
+            "returns": returns,
+        }));
+        json!({
+            "name": t.name,
+            "range": index.range(t.start, t.end),
+            "type": t.value_type,
+            "scope": t.scope,
+            "signature": signature,
+        })
+    }).collect();
+    Value::Array(types)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEXT: &str = "local s = \"é\"\nlocal function f(a, ...) return a, 1 end\n";
+
+    #[test]
+    fn reads_the_document_uri() {
+        let params = json!({ "textDocument": { "uri": "file:///a.lua" } });
+        assert_eq!(document_uri(&params).as_deref(), Some("file:///a.lua"));
+        assert_eq!(document_uri(&json!({})), None);
+    }
+
+    #[test]
+    fn dumps_the_whole_tree() {
+        let tree = syntax_tree("x = 1\n", &json!({ "textDocument": { "uri": "file:///a.lua" } }), Encoding::Utf16).unwrap();
+        assert!(tree.starts_with("Block@0..6\n  AssignStatement@0..6\n"), "{tree}");
+        assert_eq!(tree.lines().count(), 12);
+    }
+
+    #[test]
+    fn dumps_the_node_covering_a_range() {
+        // `a, ...` on the second line, after a line with a character that's
+        // one UTF-16 unit but two bytes
+        let params = json!({ "range": { "start": { "line": 1, "character": 17 }, "end": { "line": 1, "character": 23 } } });
+        assert_eq!(syntax_tree(TEXT, &params, Encoding::Utf16).unwrap(), "\
+ParameterList@31..39
+  LeftBracket@31..32 \"(\"
+  Parameter@32..33 \"a\"
+  Comma@33..34 \",\"
+  Whitespace@34..35 \" \"
+  ParameterVarArgs@35..38 \"...\"
+  RightBracket@38..39 \")\"
+");
+    }
+
+    #[test]
+    fn refuses_a_malformed_range() {
+        assert_eq!(syntax_tree(TEXT, &json!({ "range": { "start": 1 } }), Encoding::Utf16), None);
+    }
+
+    #[test]
+    fn lists_inferred_types() {
+        let types = inferred_types(TEXT, "file:///a.lua", Encoding::Utf16);
+        assert_eq!(types, json!([
+            {
+                "name": "s",
+                "range": { "start": { "line": 0, "character": 6 }, "end": { "line": 0, "character": 7 } },
+                "type": "string",
+                "scope": [0],
+                "signature": null,
+            },
+            {
+                "name": "f",
+                "range": { "start": { "line": 1, "character": 15 }, "end": { "line": 1, "character": 16 } },
+                "type": "function",
+                "scope": [1],
+                "signature": { "parameters": 1, "varargs": true, "returns": ["unknown", "number"] },
+            },
+        ]));
+    }
+}
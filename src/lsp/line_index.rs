@@ -0,0 +1,186 @@
+//Copyright (C) 2025-  plusmouse and other contributors
+//
+//This program is free software: you can redistribute it and/or modify
+//it under the terms of the GNU General Public License as published by
+//the Free Software Foundation, either version 3 of the License, or
+//(at your option) any later version.
+//
+//This program is distributed in the hope that it will be useful,
+//but WITHOUT ANY WARRANTY; without even the implied warranty of
+//MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//GNU General Public License for more details.
+//
+//You should have received a copy of the GNU General Public License
+//along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Converting between the byte offsets everything else uses and LSP positions,
+//! whose `character` counts in whatever encoding was agreed at initialize
+
+use lsp_types::{Position, PositionEncodingKind, Range};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    Utf8,
+    Utf16,
+    Utf32,
+}
+
+impl Encoding {
+    /// Picks from what the client offers, UTF-8 first as that's what we hold
+    /// text in. UTF-16 is what clients that don't say anything expect
+    pub fn negotiate(offered: Option<&[PositionEncodingKind]>) -> Encoding {
+        let offered = offered.unwrap_or_default();
+        for (kind, encoding) in [(PositionEncodingKind::UTF8, Encoding::Utf8), (PositionEncodingKind::UTF32, Encoding::Utf32)] {
+            if offered.contains(&kind) {
+                return encoding
+            }
+        }
+        Encoding::Utf16
+    }
+
+    pub fn kind(&self) -> PositionEncodingKind {
+        match self {
+            Encoding::Utf8 => PositionEncodingKind::UTF8,
+            Encoding::Utf16 => PositionEncodingKind::UTF16,
+            Encoding::Utf32 => PositionEncodingKind::UTF32,
+        }
+    }
+
+    fn units(&self, c: char) -> usize {
+        match self {
+            Encoding::Utf8 => c.len_utf8(),
+            Encoding::Utf16 => c.len_utf16(),
+            Encoding::Utf32 => 1,
+        }
+    }
+}
+
+pub struct LineIndex<'a> {
+    text: &'a str,
+    encoding: Encoding,
+    /// Byte offset of each line's start. LSP ends lines at `\n`, `\r\n` or a lone `\r`
+    starts: Vec<usize>,
+}
+
+impl<'a> LineIndex<'a> {
+    pub fn new(text: &'a str, encoding: Encoding) -> LineIndex<'a> {
+        let bytes = text.as_bytes();
+        let mut starts = vec![0];
+        for (i, b) in bytes.iter().enumerate() {
+            match b {
+                b'\n' => starts.push(i + 1),
+                b'\r' if bytes.get(i + 1) != Some(&b'\n') => starts.push(i + 1),
+                _ => (),
+            }
+        }
+        LineIndex { text, encoding, starts }
+    }
+
+    /// The line's text without its line ending
+    fn line(&self, line: usize) -> &'a str {
+        let end = self.starts.get(line + 1).copied().unwrap_or(self.text.len());
+        self.text[self.starts[line]..end].trim_end_matches(['\n', '\r'])
+    }
+
+    pub fn position(&self, offset: usize) -> Position {
+        let mut offset = offset.min(self.text.len());
+        while !self.text.is_char_boundary(offset) {
+            offset -= 1;
+        }
+        let line = self.starts.partition_point(|s| *s <= offset) - 1;
+        let start = self.starts[line];
+        let content = self.line(line);
+        let before = &content[..(offset - start).min(content.len())];
+        let character: usize = before.chars().map(|c| self.encoding.units(c)).sum();
+        Position { line: line as u32, character: character as u32 }
+    }
+
+    pub fn range(&self, start: usize, end: usize) -> Range {
+        Range { start: self.position(start), end: self.position(end) }
+    }
+
+    /// Positions past the end of a line clamp to it, and past the last line to
+    /// the end of the text, as the spec asks
+    pub fn offset(&self, position: Position) -> usize {
+        let line = position.line as usize;
+        if line >= self.starts.len() {
+            return self.text.len()
+        }
+        let start = self.starts[line];
+        let mut units = 0;
+        for (i, c) in self.line(line).char_indices() {
+            // Landing inside a character counts as before it
+            units += self.encoding.units(c);
+            if units > position.character as usize {
+                return start + i
+            }
+        }
+        start + self.line(line).len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // "é" is 2 bytes and 1 UTF-16 unit, "😀" is 4 bytes and 2 UTF-16 units
+    const TEXT: &str = "a = \"é😀x\"\nb\r\nc\rd";
+
+    #[test]
+    fn positions_in_each_encoding() {
+        let x = TEXT.find('x').unwrap();
+        assert_eq!(LineIndex::new(TEXT, Encoding::Utf8).position(x), Position::new(0, 11));
+        assert_eq!(LineIndex::new(TEXT, Encoding::Utf16).position(x), Position::new(0, 8));
+        assert_eq!(LineIndex::new(TEXT, Encoding::Utf32).position(x), Position::new(0, 7));
+    }
+
+    #[test]
+    fn offsets_in_each_encoding() {
+        let x = TEXT.find('x').unwrap();
+        assert_eq!(LineIndex::new(TEXT, Encoding::Utf8).offset(Position::new(0, 11)), x);
+        assert_eq!(LineIndex::new(TEXT, Encoding::Utf16).offset(Position::new(0, 8)), x);
+        assert_eq!(LineIndex::new(TEXT, Encoding::Utf32).offset(Position::new(0, 7)), x);
+    }
+
+    #[test]
+    fn round_trips_every_character() {
+        for encoding in [Encoding::Utf8, Encoding::Utf16, Encoding::Utf32] {
+            let index = LineIndex::new(TEXT, encoding);
+            // The middle of a `\r\n` isn't a position of its own
+            for (offset, _) in TEXT.char_indices().filter(|(_, c)| *c != '\n') {
+                assert_eq!(index.offset(index.position(offset)), offset, "{encoding:?} at {offset}");
+            }
+        }
+    }
+
+    #[test]
+    fn splits_lines_on_any_ending() {
+        let index = LineIndex::new(TEXT, Encoding::Utf16);
+        assert_eq!(index.position(TEXT.find('b').unwrap()), Position::new(1, 0));
+        assert_eq!(index.position(TEXT.find('c').unwrap()), Position::new(2, 0));
+        assert_eq!(index.position(TEXT.find('d').unwrap()), Position::new(3, 0));
+    }
+
+    #[test]
+    fn inside_a_character_counts_as_before_it() {
+        let index = LineIndex::new(TEXT, Encoding::Utf16);
+        let smiley = TEXT.find('😀').unwrap();
+        assert_eq!(index.offset(Position::new(0, 7)), smiley);
+        assert_eq!(index.position(smiley + 1), Position::new(0, 6));
+    }
+
+    #[test]
+    fn clamps_past_the_end() {
+        let index = LineIndex::new(TEXT, Encoding::Utf16);
+        assert_eq!(index.offset(Position::new(1, 40)), TEXT.find('b').unwrap() + 1);
+        assert_eq!(index.offset(Position::new(9, 0)), TEXT.len());
+    }
+
+    #[test]
+    fn negotiates_what_the_client_offers() {
+        assert_eq!(Encoding::negotiate(None), Encoding::Utf16);
+        assert_eq!(Encoding::negotiate(Some(&[PositionEncodingKind::UTF16, PositionEncodingKind::UTF8])), Encoding::Utf8);
+        assert_eq!(Encoding::negotiate(Some(&[PositionEncodingKind::UTF32])), Encoding::Utf32);
+        assert_eq!(Encoding::negotiate(Some(&[PositionEncodingKind::UTF16])), Encoding::Utf16);
+    }
+}
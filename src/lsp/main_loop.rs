@@ -13,17 +13,35 @@
 //You should have received a copy of the GNU General Public License
 //along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::error::Error;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crossbeam_channel::Sender;
 use lsp_types::{
     notification, request, ClientCapabilities, GotoDefinitionResponse, InitializeParams,
     ServerCapabilities,
 };
-use lsp_types::{TextDocumentSyncCapability, TextDocumentSyncKind};
+use lsp_types::{DiagnosticOptions, DiagnosticServerCapabilities, DocumentDiagnosticReport, DocumentDiagnosticReportResult, FullDocumentDiagnosticReport, RelatedFullDocumentDiagnosticReport, RelatedUnchangedDocumentDiagnosticReport, UnchangedDocumentDiagnosticReport};
+use lsp_types::{WorkspaceDiagnosticReport, WorkspaceDiagnosticReportResult, WorkspaceDocumentDiagnosticReport, WorkspaceFullDocumentDiagnosticReport, WorkspaceUnchangedDocumentDiagnosticReport};
+use lsp_types::{ConfigurationItem, ConfigurationParams, MessageType, ShowMessageParams};
+use lsp_types::{DidChangeWatchedFilesRegistrationOptions, FileChangeType, FileEvent, FileSystemWatcher, GlobPattern, Registration, RegistrationParams};
+use lsp_types::{HoverProviderCapability, NumberOrString, SaveOptions, TextDocumentSyncCapability, TextDocumentSyncKind, TextDocumentSyncOptions, TextDocumentSyncSaveOptions, Uri};
 
-use lsp_server::{Connection, ExtractError, Message, Notification, Request, RequestId, Response};
+use lsp_server::{Connection, ErrorCode, ExtractError, Message, Notification, Request, RequestId, Response};
 
-use crate::lsp::diagnostics;
+use crate::lsp::{diagnostics, hover, inspect, workspace};
+use crate::lsp::documents::{Document, Documents};
+use crate::lsp::line_index::Encoding;
+use crate::lsp::workers::{Cancellation, Pool};
+use crate::lsp::cache::{self, Cache};
+use crate::lsp::workspace::Progress;
+use crate::settings::{self, Settings, PROJECT_FILE};
+use crate::state::State;
+use crate::symbols::{self, Summary};
 
 pub fn start_ls()  -> Result<(), Box<dyn Error + Sync + Send>> {
     // Note that  we must have our logging only write out to stderr.
@@ -36,9 +54,23 @@ pub fn start_ls()  -> Result<(), Box<dyn Error + Sync + Send>> {
     let (id, params) = connection.initialize_start()?;
 
     let init_params: InitializeParams = serde_json::from_value(params).unwrap();
-    let _client_capabilities: ClientCapabilities = init_params.capabilities;
+    let client_capabilities: ClientCapabilities = init_params.capabilities;
+    let encoding = Encoding::negotiate(client_capabilities.general.as_ref().and_then(|g| g.position_encodings.as_deref()));
     let server_capabilities = ServerCapabilities {
-        text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)),
+        position_encoding: Some(encoding.kind()),
+        text_document_sync: Some(TextDocumentSyncCapability::Options(TextDocumentSyncOptions {
+            open_close: Some(true),
+            change: Some(TextDocumentSyncKind::FULL),
+            save: Some(TextDocumentSyncSaveOptions::SaveOptions(SaveOptions { include_text: Some(true) })),
+            ..TextDocumentSyncOptions::default()
+        })),
+        hover_provider: Some(HoverProviderCapability::Simple(true)),
+        diagnostic_provider: Some(DiagnosticServerCapabilities::Options(DiagnosticOptions {
+            identifier: Some(String::from("wow_ls")),
+            inter_file_dependencies: true,
+            workspace_diagnostics: true,
+            ..DiagnosticOptions::default()
+        })),
         ..ServerCapabilities::default()
     };
 
@@ -52,70 +84,708 @@ pub fn start_ls()  -> Result<(), Box<dyn Error + Sync + Send>> {
 
     connection.initialize_finish(id, initialize_data)?;
 
-    main_loop(connection)
+    // Older clients only send the one root
+    #[allow(deprecated)]
+    let roots: Vec<PathBuf> = match init_params.workspace_folders {
+        Some(folders) => folders.iter().filter_map(|f| workspace::uri_to_path(&f.uri)).collect(),
+        None => init_params.root_uri.as_ref().and_then(workspace::uri_to_path).into_iter().collect(),
+    };
+
+    let initialization_options = init_params.initialization_options.unwrap_or_default();
+    main_loop(connection, encoding, roots, client_capabilities, initialization_options)
+}
+
+/// How long to wait after an edit before checking, so typing doesn't queue a
+/// check per key press
+const DIAGNOSTICS_DELAY: Duration = Duration::from_millis(200);
+
+/// Work done on a worker that the main loop has to act on
+enum Finished {
+    Diagnostics { uri: Uri, snapshot: Arc<Document>, diagnostics: Vec<lsp_types::Diagnostic>, summary: Summary },
+    /// A file read from disk with the hash of its text, `None` if it couldn't
+    /// be. `scan` is set for the files of the startup scan, which progress counts
+    Indexed { uri: String, summary: Option<(u64, Summary)>, scan: bool },
+}
+
+type RequestResult = Result<serde_json::Value, (ErrorCode, String)>;
+
+struct Server {
+    connection: Connection,
+    settings: Arc<Settings>,
+    /// Settings sent with `initialize` and by `workspace/configuration`, under
+    /// what the project files say
+    initialization_options: serde_json::Value,
+    client_settings: serde_json::Value,
+    /// The client can be asked for its settings, and the request doing so
+    configuration_support: bool,
+    configuration_request: Option<RequestId>,
+    roots: Vec<PathBuf>,
+    encoding: Encoding,
+    documents: Documents,
+    pool: Pool,
+    /// Requests being worked on, for `$/cancelRequest`
+    pending: Arc<Mutex<HashMap<RequestId, Cancellation>>>,
+    /// Documents edited since they were last checked, and when to check them
+    dirty: HashMap<String, (Uri, Instant)>,
+    finished: Sender<Finished>,
+    /// The globals of every file in the workspace, open or not
+    state: State,
+    /// Reads files from disk, kept apart so a big workspace doesn't hold up requests
+    indexer: Pool,
+    /// Files of the startup scan still to be read
+    scanning: usize,
+    progress: Option<Progress>,
+    work_done_progress: bool,
+    cache: Option<Cache>,
+    /// What the cache had at startup, only needed until the scan's done
+    cached: Arc<cache::Entries>,
+    /// The client pulls diagnostics, so they aren't published as well
+    pull: bool,
+    /// The client can be told to pull again, and when to next tell it
+    refresh_support: bool,
+    refresh_at: Option<Instant>,
+    /// For ids of requests sent to the client
+    requests_sent: u64,
+}
+
+fn main_loop(connection: Connection, encoding: Encoding, roots: Vec<PathBuf>, capabilities: ClientCapabilities, initialization_options: serde_json::Value) -> Result<(), Box<dyn Error + Sync + Send>> {
+    let messages = connection.receiver.clone();
+    let (finished, finished_receiver) = crossbeam_channel::unbounded();
+    let mut server = Server {
+        connection,
+        settings: Arc::default(),
+        initialization_options,
+        client_settings: serde_json::Value::Null,
+        configuration_support: capabilities.workspace.as_ref().and_then(|w| w.configuration).unwrap_or(false),
+        configuration_request: None,
+        encoding,
+        documents: Documents::default(),
+        pool: Pool::with_default_size(),
+        pending: Arc::default(),
+        dirty: HashMap::new(),
+        finished,
+        state: State::default(),
+        indexer: Pool::with_default_size(),
+        scanning: 0,
+        progress: None,
+        work_done_progress: capabilities.window.as_ref().and_then(|w| w.work_done_progress).unwrap_or(false),
+        cache: Cache::for_roots(&roots),
+        cached: Arc::default(),
+        pull: capabilities.text_document.as_ref().is_some_and(|t| t.diagnostic.is_some()),
+        refresh_support: capabilities.workspace.as_ref().and_then(|w| w.diagnostic.as_ref())
+            .and_then(|d| d.refresh_support).unwrap_or(false),
+        refresh_at: None,
+        requests_sent: 0,
+        roots,
+    };
+    let watch_files = capabilities.workspace.as_ref().and_then(|w| w.did_change_watched_files)
+        .and_then(|w| w.dynamic_registration).unwrap_or(false);
+    let watch_configuration = capabilities.workspace.as_ref().and_then(|w| w.did_change_configuration)
+        .and_then(|w| w.dynamic_registration).unwrap_or(false);
+    server.register(watch_files, watch_configuration)?;
+    server.apply_settings();
+    server.request_configuration();
+    server.index_workspace();
+    loop {
+        let now = Instant::now();
+        let timeout = server.dirty.values().map(|(_, at)| *at).chain(server.refresh_at).min()
+            .map_or(Duration::from_secs(60), |at| at.saturating_duration_since(now));
+        crossbeam_channel::select! {
+            recv(messages) -> msg => {
+                let Ok(msg) = msg else {
+                    return Ok(())
+                };
+                if server.handle(msg)? {
+                    return Ok(())
+                }
+            }
+            recv(finished_receiver) -> done => {
+                if let Ok(done) = done {
+                    server.finish(done);
+                }
+            }
+            default(timeout) => (),
+        }
+        server.check_due();
+    }
 }
 
-fn main_loop(connection: Connection) -> Result<(), Box<dyn Error + Sync + Send>> {
-    let mut language: HashMap<String, String> = HashMap::new();
-    for msg in &connection.receiver {
+impl Server {
+    /// Returns true once the client has asked to shut down
+    fn handle(&mut self, msg: Message) -> Result<bool, Box<dyn Error + Sync + Send>> {
         eprintln!("got msg: {msg:?}");
         match msg {
             Message::Request(req) => {
-                if connection.handle_shutdown(&req)? {
-                    return Ok(());
+                if self.connection.handle_shutdown(&req)? {
+                    self.save_cache();
+                    return Ok(true);
                 }
                 eprint!("got req {}", &*req.method);
-                match &*req.method {
-                    "textDocument/definition" => {
-                        if let Ok((id, params)) = cast_req::<request::GotoDefinition>(req) {
-                            eprintln!("got gotoDefinition request #{id}: {params:?}");
-                            let result = Some(GotoDefinitionResponse::Array(Vec::new()));
-                            let result = serde_json::to_value(&result).unwrap();
-                            let resp = Response {
-                                id,
-                                result: Some(result),
-                                error: None,
-                            };
-                            connection.sender.send(Message::Response(resp))?;
-                            continue;
-                        }
-                    }
-                    _ => {
-                    }
-                };
-                // ...
+                self.handle_request(req)?;
             }
             Message::Response(resp) => {
                 eprintln!("got response: {resp:?}");
+                if self.configuration_request.as_ref() == Some(&resp.id) {
+                    self.configuration_request = None;
+                    // One item asked for, so one answer
+                    if let Some(serde_json::Value::Array(mut items)) = resp.result && !items.is_empty() {
+                        self.client_settings = items.swap_remove(0);
+                        self.settings_changed();
+                    }
+                }
             }
             Message::Notification(not) => {
                 eprint!("got not {}", &*not.method);
-                match &*not.method {
-                    "textDocument/didChange" => {
-                        if let Ok(params) = cast_not::<notification::DidChangeTextDocument>(not) {
-                            if let Some(l) = language.get(&params.text_document.uri.to_string()) {
-                                if l == "lua" {
-                                    diagnostics::get(&connection, params.text_document.uri, &params.content_changes[0].text);
-                                }
-                            }
-                        }
+                self.handle_notification(not);
+            }
+        }
+        Ok(false)
+    }
+
+    fn handle_request(&mut self, req: Request) -> Result<(), Box<dyn Error + Sync + Send>> {
+        let encoding = self.encoding;
+        match &*req.method {
+            "textDocument/definition" => {
+                if let Ok((id, params)) = cast_req::<request::GotoDefinition>(req) {
+                    eprintln!("got gotoDefinition request #{id}: {params:?}");
+                    let result = Some(GotoDefinitionResponse::Array(Vec::new()));
+                    let result = serde_json::to_value(&result).unwrap();
+                    let resp = Response {
+                        id,
+                        result: Some(result),
+                        error: None,
+                    };
+                    self.connection.sender.send(Message::Response(resp))?;
+                }
+            }
+            "textDocument/hover" => {
+                if let Ok((id, params)) = cast_req::<request::HoverRequest>(req) {
+                    let position = params.text_document_position_params;
+                    let document = self.documents.get(position.text_document.uri.as_str());
+                    self.run_request(id, move |_| {
+                        let result = document.and_then(|d| hover::get(&d.text, position.position, encoding));
+                        Ok(serde_json::to_value(&result).unwrap())
+                    });
+                }
+            }
+            inspect::SYNTAX_TREE | inspect::INFERRED_TYPES => {
+                let document = inspect::document_uri(&req.params).and_then(|uri| self.documents.get(&uri).map(|d| (uri, d)));
+                let Request { id, method, params } = req;
+                self.run_request(id, move |_| {
+                    let Some((uri, document)) = document else {
+                        return Err((ErrorCode::InvalidParams, String::from("document isn't open")))
+                    };
+                    if method == inspect::SYNTAX_TREE {
+                        inspect::syntax_tree(&document.text, &params, encoding).map(serde_json::Value::String)
+                            .ok_or((ErrorCode::InvalidParams, String::from("bad range")))
+                    } else {
+                        Ok(inspect::inferred_types(&document.text, &uri, encoding))
                     }
-                    "textDocument/didOpen" => {
-                        if let Ok(params) = cast_not::<notification::DidOpenTextDocument>(not) {
-                            language.insert(params.text_document.uri.to_string(), params.text_document.language_id.clone());
-                            if params.text_document.language_id == "lua" {
-                                diagnostics::get(&connection, params.text_document.uri, &params.text_document.text);
+                });
+            }
+            "textDocument/diagnostic" => {
+                if let Ok((id, params)) = cast_req::<request::DocumentDiagnosticRequest>(req) {
+                    let uri = params.text_document.uri;
+                    let document = self.documents.get(uri.as_str());
+                    let lua = document.as_ref().map_or_else(|| is_lua_file(uri.as_str()), |d| d.is_lua());
+                    let globals = self.state.globals();
+                    let settings = Arc::clone(&self.settings);
+                    self.run_request(id, move |_| {
+                        let text = match document {
+                            Some(document) => Some(document.text.clone()),
+                            None => workspace::read(&uri),
+                        };
+                        let pulled = match text {
+                            Some(text) if lua => diagnostics::pull(&uri, &text, params.previous_result_id.as_deref(), &globals, &settings, encoding),
+                            // Nothing to say about other files, or ones that aren't there
+                            _ => diagnostics::Pulled::Full(String::new(), Vec::new()),
+                        };
+                        let report = match pulled {
+                            diagnostics::Pulled::Unchanged(result_id) => DocumentDiagnosticReport::Unchanged(RelatedUnchangedDocumentDiagnosticReport {
+                                related_documents: None,
+                                unchanged_document_diagnostic_report: UnchangedDocumentDiagnosticReport { result_id },
+                            }),
+                            diagnostics::Pulled::Full(result_id, items) => DocumentDiagnosticReport::Full(RelatedFullDocumentDiagnosticReport {
+                                related_documents: None,
+                                full_document_diagnostic_report: FullDocumentDiagnosticReport {
+                                    result_id: Some(result_id).filter(|id| !id.is_empty()),
+                                    items,
+                                },
+                            }),
+                        };
+                        Ok(serde_json::to_value(DocumentDiagnosticReportResult::Report(report)).unwrap())
+                    });
+                }
+            }
+            "workspace/diagnostic" => {
+                if let Ok((id, params)) = cast_req::<request::WorkspaceDiagnosticRequest>(req) {
+                    let previous: HashMap<String, String> = params.previous_result_ids.into_iter()
+                        .map(|p| (p.uri.to_string(), p.value))
+                        .collect();
+                    let mut files: Vec<(String, Option<Arc<Document>>)> = self.state.uris()
+                        .map(|uri| (String::from(uri), self.documents.get(uri)))
+                        .filter(|(uri, document)| document.as_ref().map_or_else(|| is_lua_file(uri), |d| d.is_lua()))
+                        // Libraries are only there for their globals
+                        .filter(|(uri, _)| !Uri::from_str(uri).ok().as_ref().and_then(workspace::uri_to_path)
+                            .is_some_and(|path| self.settings.is_library(&path)))
+                        .collect();
+                    files.sort_by(|a, b| a.0.cmp(&b.0));
+                    let globals = self.state.globals();
+                    let settings = Arc::clone(&self.settings);
+                    self.run_request(id, move |token| {
+                        let mut items = Vec::new();
+                        for (uri, document) in files {
+                            if token.is_cancelled() {
+                                break
                             }
+                            let Ok(parsed) = Uri::from_str(&uri) else {
+                                continue
+                            };
+                            let Some(text) = document.as_ref().map(|d| d.text.clone()).or_else(|| workspace::read(&parsed)) else {
+                                continue
+                            };
+                            let version = document.map(|d| i64::from(d.version));
+                            let pulled = diagnostics::pull(&parsed, &text, previous.get(&uri).map(String::as_str), &globals, &settings, encoding);
+                            items.push(match pulled {
+                                diagnostics::Pulled::Unchanged(result_id) => WorkspaceDocumentDiagnosticReport::Unchanged(WorkspaceUnchangedDocumentDiagnosticReport {
+                                    uri: parsed,
+                                    version,
+                                    unchanged_document_diagnostic_report: UnchangedDocumentDiagnosticReport { result_id },
+                                }),
+                                diagnostics::Pulled::Full(result_id, items) => WorkspaceDocumentDiagnosticReport::Full(WorkspaceFullDocumentDiagnosticReport {
+                                    uri: parsed,
+                                    version,
+                                    full_document_diagnostic_report: FullDocumentDiagnosticReport { result_id: Some(result_id), items },
+                                }),
+                            });
                         }
+                        let report = WorkspaceDiagnosticReportResult::Report(WorkspaceDiagnosticReport { items });
+                        Ok(serde_json::to_value(report).unwrap())
+                    });
+                }
+            }
+            _ => {
+                let resp = Response::new_err(req.id, ErrorCode::MethodNotFound as i32, format!("unhandled method {}", req.method));
+                self.connection.sender.send(Message::Response(resp))?;
+            }
+        };
+        Ok(())
+    }
+
+    /// Answers the request on a worker, unless it's cancelled first
+    fn run_request(&self, id: RequestId, job: impl FnOnce(&Cancellation) -> RequestResult + Send + 'static) {
+        let token = Cancellation::default();
+        self.pending.lock().unwrap().insert(id.clone(), token.clone());
+        let pending = Arc::clone(&self.pending);
+        let sender = self.connection.sender.clone();
+        self.pool.spawn(move || {
+            let result = (!token.is_cancelled()).then(|| job(&token));
+            pending.lock().unwrap().remove(&id);
+            // Cancelled while working still gets the cancelled answer, the client
+            // has stopped caring about the result
+            let resp = match result {
+                Some(Ok(value)) if !token.is_cancelled() => Response::new_ok(id, value),
+                Some(Err((code, message))) if !token.is_cancelled() => Response::new_err(id, code as i32, message),
+                _ => Response::new_err(id, ErrorCode::RequestCanceled as i32, String::from("cancelled")),
+            };
+            let _ = sender.send(Message::Response(resp));
+        });
+    }
+
+    fn handle_notification(&mut self, not: Notification) {
+        match &*not.method {
+            "$/cancelRequest" => {
+                if let Ok(params) = cast_not::<notification::Cancel>(not) {
+                    let id = match params.id {
+                        NumberOrString::Number(n) => RequestId::from(n),
+                        NumberOrString::String(s) => RequestId::from(s),
+                    };
+                    if let Some(token) = self.pending.lock().unwrap().get(&id) {
+                        token.cancel();
                     }
-                    _ => {
-                        eprintln!("fallback")
+                }
+            }
+            "textDocument/didOpen" => {
+                if let Ok(params) = cast_not::<notification::DidOpenTextDocument>(not) {
+                    let document = params.text_document;
+                    self.documents.open(document.uri.to_string(), document.language_id, document.version, document.text);
+                    self.check(document.uri);
+                }
+            }
+            "textDocument/didChange" => {
+                if let Ok(params) = cast_not::<notification::DidChangeTextDocument>(not) {
+                    // Full sync, so the last change holds the whole text
+                    let Some(change) = params.content_changes.into_iter().last() else {
+                        return
+                    };
+                    let uri = params.text_document.uri;
+                    if self.documents.change(uri.as_str(), params.text_document.version, change.text) {
+                        self.dirty.insert(uri.to_string(), (uri, Instant::now() + DIAGNOSTICS_DELAY));
                     }
                 }
             }
+            "textDocument/didSave" => {
+                if let Ok(params) = cast_not::<notification::DidSaveTextDocument>(not) {
+                    let uri = params.text_document.uri;
+                    if let Some(text) = params.text && self.documents.save(uri.as_str(), text) {
+                        self.dirty.remove(uri.as_str());
+                        self.check(uri);
+                    }
+                }
+            }
+            "textDocument/didClose" => {
+                if let Ok(params) = cast_not::<notification::DidCloseTextDocument>(not) {
+                    let uri = params.text_document.uri;
+                    self.dirty.remove(uri.as_str());
+                    // Nothing looks at files that aren't open yet, so their problems go with them
+                    if self.documents.close(uri.as_str()).is_some_and(|d| d.is_lua()) {
+                        diagnostics::publish(&self.connection, uri.clone(), None, Vec::new());
+                    }
+                    // The buffer may not have been saved, what's on disk is what counts now.
+                    // Files from elsewhere were only known while open
+                    if workspace::uri_to_path(&uri).is_some_and(|path| self.in_workspace(&path)) {
+                        self.index(uri.to_string(), false);
+                    } else {
+                        self.forget(uri.as_str());
+                    }
+                }
+            }
+            "workspace/didChangeConfiguration" => {
+                if let Ok(params) = cast_not::<notification::DidChangeConfiguration>(not) {
+                    // Clients that can be asked only send what changed, if anything
+                    if self.configuration_support {
+                        self.request_configuration();
+                    } else {
+                        let settings = params.settings;
+                        self.client_settings = settings.get("wowls").cloned().unwrap_or(settings);
+                        self.settings_changed();
+                    }
+                }
+            }
+            "workspace/didChangeWatchedFiles" => {
+                if let Ok(params) = cast_not::<notification::DidChangeWatchedFiles>(not) {
+                    self.files_changed(params.changes);
+                }
+            }
+            _ => {
+                eprintln!("fallback")
+            }
         }
     }
-    Ok(())
+
+    /// Checks the buffer as it is now on a worker. Clients that pull their
+    /// diagnostics still need the buffer's globals kept up to date
+    fn check(&mut self, uri: Uri) {
+        let Some(snapshot) = self.documents.get(uri.as_str()).filter(|d| d.is_lua()) else {
+            return
+        };
+        let settings = Arc::clone(&self.settings);
+        let globals = self.state.globals();
+        let push = !self.pull;
+        let encoding = self.encoding;
+        let finished = self.finished.clone();
+        self.pool.spawn(move || {
+            let summary = symbols::summarize(&snapshot.text);
+            let diagnostics = match push {
+                true => diagnostics::compute(&uri, &snapshot.text, &settings.diagnostics, &globals.types_for(uri.as_str(), &summary.references), encoding),
+                false => Vec::new(),
+            };
+            let _ = finished.send(Finished::Diagnostics { uri, snapshot, diagnostics, summary });
+        });
+    }
+
+    fn check_due(&mut self) {
+        let now = Instant::now();
+        let due: Vec<String> = self.dirty.iter().filter(|(_, (_, at))| *at <= now).map(|(k, _)| k.clone()).collect();
+        for key in due {
+            if let Some((uri, _)) = self.dirty.remove(&key) {
+                self.check(uri);
+            }
+        }
+        if self.refresh_at.is_some_and(|at| at <= now) {
+            self.refresh_at = None;
+            self.send_request("workspace/diagnostic/refresh", serde_json::Value::Null);
+        }
+    }
+
+    fn finish(&mut self, done: Finished) {
+        match done {
+            Finished::Diagnostics { uri, snapshot, diagnostics, summary } => {
+                // The buffer moved on while checking, a newer check will report for it
+                if self.documents.is_current(uri.as_str(), &snapshot) {
+                    let changed = self.state.set_file(uri.to_string(), summary, None);
+                    self.recheck_dependents(&changed, uri.as_str());
+                    if !self.pull {
+                        diagnostics::publish(&self.connection, uri, Some(snapshot.version), diagnostics);
+                    }
+                }
+            }
+            Finished::Indexed { uri, summary, scan } => {
+                // Open buffers win over the disk, they're indexed when checked
+                if self.documents.get(&uri).is_none() {
+                    let changed = match summary {
+                        Some((hash, summary)) => self.state.set_file(uri.clone(), summary, Some(hash)),
+                        None => self.state.remove_file(&uri)
+                            .map(|s| s.definitions.into_iter().map(|d| d.name).collect())
+                            .unwrap_or_default(),
+                    };
+                    self.recheck_dependents(&changed, &uri);
+                }
+                if scan {
+                    self.scanned();
+                } else {
+                    // Changed on disk, so its own diagnostics may have too
+                    self.request_refresh();
+                }
+            }
+        }
+    }
+
+    /// Open buffers using any of the globals get checked again, after the usual
+    /// delay so a run of changes only checks them once
+    fn recheck_dependents(&mut self, changed: &HashSet<String>, uri: &str) {
+        if changed.is_empty() {
+            return
+        }
+        // Any file can depend on them, not only open ones
+        if self.pull {
+            self.request_refresh();
+            return
+        }
+        let at = Instant::now() + DIAGNOSTICS_DELAY;
+        for dependent in self.state.dependents(changed, uri) {
+            if self.documents.get(&dependent).is_some() && let Ok(parsed) = Uri::from_str(&dependent) {
+                self.dirty.entry(dependent).or_insert((parsed, at));
+            }
+        }
+    }
+
+    /// Tells clients that pull diagnostics to pull again soon, once the scan's
+    /// done so it isn't asked for every file read
+    fn request_refresh(&mut self) {
+        if self.pull && self.refresh_support && self.scanning == 0 {
+            self.refresh_at.get_or_insert(Instant::now() + DIAGNOSTICS_DELAY);
+        }
+    }
+
+    fn send_request(&mut self, method: &str, params: impl serde::Serialize) -> RequestId {
+        self.requests_sent += 1;
+        let id = RequestId::from(format!("wowls/{}", self.requests_sent));
+        let req = Request::new(id.clone(), String::from(method), params);
+        let _ = self.connection.sender.send(Message::Request(req));
+        id
+    }
+
+    /// Asks to hear about files changed outside the editor, and settings
+    /// changed in it
+    fn register(&mut self, watch_files: bool, watch_configuration: bool) -> Result<(), Box<dyn Error + Sync + Send>> {
+        let mut registrations = Vec::new();
+        if watch_files {
+            let watcher = |glob: &str| FileSystemWatcher { glob_pattern: GlobPattern::String(String::from(glob)), kind: None };
+            let options = DidChangeWatchedFilesRegistrationOptions {
+                watchers: vec![watcher("**/*.{lua,xml,toc}"), watcher(&format!("**/{PROJECT_FILE}"))],
+            };
+            registrations.push(Registration {
+                id: String::from("wowls/watchedFiles"),
+                method: String::from("workspace/didChangeWatchedFiles"),
+                register_options: Some(serde_json::to_value(options)?),
+            });
+        }
+        if watch_configuration {
+            registrations.push(Registration {
+                id: String::from("wowls/configuration"),
+                method: String::from("workspace/didChangeConfiguration"),
+                register_options: Some(serde_json::json!({ "section": "wowls" })),
+            });
+        }
+        if !registrations.is_empty() {
+            self.send_request("client/registerCapability", RegistrationParams { registrations });
+        }
+        Ok(())
+    }
+
+    /// Asks the client for its `wowls` settings, the answer being applied when it comes
+    fn request_configuration(&mut self) {
+        if !self.configuration_support {
+            return
+        }
+        let params = ConfigurationParams {
+            items: vec![ConfigurationItem { scope_uri: None, section: Some(String::from("wowls")) }],
+        };
+        self.configuration_request = Some(self.send_request("workspace/configuration", params));
+    }
+
+    /// Reads the settings again from each place they're kept. Returns whether
+    /// different files need indexing
+    fn apply_settings(&mut self) -> bool {
+        let (merged, errors) = settings::combine(&self.initialization_options, &self.client_settings, &self.roots);
+        for e in errors {
+            eprintln!("{e}");
+            let params = ShowMessageParams { typ: MessageType::WARNING, message: e };
+            let not = Notification::new(String::from("window/showMessage"), params);
+            let _ = self.connection.sender.send(Message::Notification(not));
+        }
+        // Only what the editor sent can still be relative, project files are
+        // resolved against their own roots
+        let (new, problems) = Settings::from_json(&merged, self.roots.first().map(PathBuf::as_path));
+        for problem in problems {
+            eprintln!("{problem}");
+        }
+        let reindex = new.flavor != self.settings.flavor || !new.library_roots().eq(self.settings.library_roots());
+        self.state.set_known(new.globals.clone());
+        self.settings = Arc::new(new);
+        reindex
+    }
+
+    /// Everything may be reported differently after a change to the settings
+    fn settings_changed(&mut self) {
+        if self.apply_settings() {
+            self.index_workspace();
+        }
+        if self.pull {
+            self.request_refresh();
+        } else {
+            let at = Instant::now() + DIAGNOSTICS_DELAY;
+            let open: Vec<String> = self.state.uris().filter(|uri| self.documents.get(uri).is_some()).map(String::from).collect();
+            for uri in open {
+                if let Ok(parsed) = Uri::from_str(&uri) {
+                    self.dirty.entry(uri).or_insert((parsed, at));
+                }
+            }
+        }
+    }
+
+    /// Files changed on disk, by a checkout or a packager. Open buffers win over
+    /// the disk so they're left alone, they're read again when closed
+    fn files_changed(&mut self, changes: Vec<FileEvent>) {
+        for change in changes {
+            let uri = change.uri.to_string();
+            if uri.ends_with(&format!("/{PROJECT_FILE}")) {
+                self.settings_changed();
+                continue
+            }
+            if self.documents.get(&uri).is_some() {
+                continue
+            }
+            // Deleted files can't be read, so indexing them drops them
+            self.index(uri, false);
+            // A new `.toc` can load files from outside the workspace
+            if change.typ != FileChangeType::DELETED
+                && let Some(path) = workspace::uri_to_path(&change.uri)
+                && path.extension().is_some_and(|e| e.eq_ignore_ascii_case("toc")) {
+                for file in crate::files::toc_files(&path, Some(self.settings.flavor)).unwrap_or_default() {
+                    if let Some(file) = workspace::path_to_uri(&file) && !self.state.contains(file.as_str()) {
+                        self.index(file.to_string(), false);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Starts reading every file under the workspace roots and libraries,
+    /// forgetting files that aren't any more
+    fn index_workspace(&mut self) {
+        let mut uris = Vec::new();
+        for root in self.roots.iter().chain(self.settings.library_roots()) {
+            match crate::files::workspace_files(root, Some(self.settings.flavor)) {
+                Ok(files) => uris.extend(files.iter().filter_map(|f| workspace::path_to_uri(f)).map(|u| u.to_string())),
+                Err(e) => eprintln!("can't read workspace {}: {e}", root.display()),
+            }
+        }
+        uris.sort();
+        uris.dedup();
+        let keep: HashSet<&str> = uris.iter().map(String::as_str).collect();
+        let gone: Vec<String> = self.state.uris()
+            .filter(|uri| !keep.contains(uri) && self.documents.get(uri).is_none())
+            .map(String::from)
+            .collect();
+        for uri in gone {
+            self.forget(&uri);
+        }
+        if uris.is_empty() {
+            return
+        }
+        self.scanning += uris.len();
+        if let Some(cache) = &self.cache {
+            self.cached = Arc::new(cache.load());
+            eprintln!("{} files in the index cache", self.cached.len());
+        }
+        match &mut self.progress {
+            Some(progress) => progress.extend(uris.len()),
+            None => {
+                self.requests_sent += 1;
+                let token = format!("wowls/indexing/{}", self.requests_sent);
+                self.progress = Progress::begin(&self.connection.sender, self.work_done_progress, &token, "Indexing workspace", uris.len());
+            }
+        }
+        for uri in uris {
+            self.index(uri, true);
+        }
+    }
+
+    /// Whether the file is under a workspace root or library, so is indexed
+    /// whether open or not
+    fn in_workspace(&self, path: &std::path::Path) -> bool {
+        self.roots.iter().any(|root| path.starts_with(root)) || self.settings.is_library(path)
+    }
+
+    /// Drops what the file defined, rechecking whatever used it
+    fn forget(&mut self, uri: &str) {
+        let changed = self.state.remove_file(uri)
+            .map(|s| s.definitions.into_iter().map(|d| d.name).collect())
+            .unwrap_or_default();
+        self.recheck_dependents(&changed, uri);
+    }
+
+    /// Reads the file from disk and summarizes it on the indexer, unless the
+    /// cache has it as it is
+    fn index(&self, uri: String, scan: bool) {
+        let finished = self.finished.clone();
+        let cached = Arc::clone(&self.cached);
+        self.indexer.spawn(move || {
+            let summary = Uri::from_str(&uri).ok().as_ref().and_then(workspace::uri_to_path)
+                .and_then(|path| std::fs::read(&path).ok().map(|bytes| (path, bytes)))
+                .map(|(path, bytes)| {
+                    let hash = cache::hash(&bytes);
+                    match cached.get(&uri) {
+                        Some((cached_hash, summary)) if *cached_hash == hash => (hash, summary.clone()),
+                        _ => (hash, symbols::summarize_file(&path, &String::from_utf8_lossy(&bytes))),
+                    }
+                });
+            let _ = finished.send(Finished::Indexed { uri, summary, scan });
+        });
+    }
+
+    fn scanned(&mut self) {
+        self.scanning = self.scanning.saturating_sub(1);
+        if let Some(progress) = &mut self.progress {
+            progress.step();
+        }
+        if self.scanning == 0 {
+            eprintln!("indexed {} files", self.state.file_count());
+            if let Some(progress) = self.progress.take() {
+                progress.end();
+            }
+            self.cached = Arc::default();
+            self.save_cache();
+            self.request_refresh();
+        }
+    }
+
+    fn save_cache(&self) {
+        // Saving before the scan's done would lose what it hasn't got to
+        if self.scanning > 0 {
+            return
+        }
+        if let Some(cache) = &self.cache && let Err(e) = cache.save(self.state.disk_summaries()) {
+            eprintln!("can't save the index cache: {e}");
+        }
+    }
+
+}
+
+fn is_lua_file(uri: &str) -> bool {
+    uri.rsplit('.').next().is_some_and(|e| e.eq_ignore_ascii_case("lua"))
 }
 
 fn cast_req<R>(req: Request) -> Result<(RequestId, R::Params), ExtractError<Request>>
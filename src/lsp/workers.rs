@@ -0,0 +1,128 @@
+//Copyright (C) 2025-  plusmouse and other contributors
+//
+//This program is free software: you can redistribute it and/or modify
+//it under the terms of the GNU General Public License as published by
+//the Free Software Foundation, either version 3 of the License, or
+//(at your option) any later version.
+//
+//This program is distributed in the hope that it will be useful,
+//but WITHOUT ANY WARRANTY; without even the implied warranty of
+//MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//GNU General Public License for more details.
+//
+//You should have received a copy of the GNU General Public License
+//along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Threads for the slow parts, so one big file doesn't hold up every request
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::JoinHandle;
+
+use crossbeam_channel::{Receiver, Sender};
+
+type Job = Box<dyn FnOnce() + Send>;
+
+pub struct Pool {
+    sender: Option<Sender<Job>>,
+    threads: Vec<JoinHandle<()>>,
+    /// Set when shutting down, so queued jobs are dropped rather than run
+    stopped: Cancellation,
+}
+
+impl Pool {
+    pub fn new(threads: usize) -> Pool {
+        let (sender, receiver): (Sender<Job>, Receiver<Job>) = crossbeam_channel::unbounded();
+        let stopped = Cancellation::default();
+        let threads = (0..threads.max(1)).map(|i| {
+            let receiver = receiver.clone();
+            let stopped = stopped.clone();
+            std::thread::Builder::new()
+                .name(format!("wow_ls worker {}", i))
+                .spawn(move || {
+                    for job in receiver {
+                        if stopped.is_cancelled() {
+                            break
+                        }
+                        job();
+                    }
+                })
+                .expect("can start worker threads")
+        }).collect();
+        Pool { sender: Some(sender), threads, stopped }
+    }
+
+    /// A thread per core, leaving one for the main loop. Always at least two,
+    /// so a quick request isn't stuck behind checking one huge file
+    pub fn with_default_size() -> Pool {
+        let cores = std::thread::available_parallelism().map_or(2, |n| n.get());
+        Pool::new(cores.saturating_sub(1).max(2))
+    }
+
+    pub fn spawn(&self, job: impl FnOnce() + Send + 'static) {
+        if let Some(sender) = &self.sender {
+            let _ = sender.send(Box::new(job));
+        }
+    }
+}
+
+impl Drop for Pool {
+    /// Waits for running jobs, but throws away queued ones so shutting down
+    /// isn't held up by indexing
+    fn drop(&mut self) {
+        self.stopped.cancel();
+        self.sender = None;
+        for thread in self.threads.drain(..) {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Set by `$/cancelRequest`, looked at by the job answering the request
+#[derive(Clone, Default)]
+pub struct Cancellation(Arc<AtomicBool>);
+
+impl Cancellation {
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+    use std::time::Duration;
+
+    #[test]
+    fn drop_skips_queued_jobs() {
+        let pool = Pool::new(1);
+        let ran = Arc::new(AtomicUsize::new(0));
+        pool.spawn(|| std::thread::sleep(Duration::from_millis(50)));
+        for _ in 0..100 {
+            let ran = ran.clone();
+            pool.spawn(move || {
+                ran.fetch_add(1, Ordering::Relaxed);
+            });
+        }
+        drop(pool);
+        assert_eq!(ran.load(Ordering::Relaxed), 0);
+    }
+
+    #[test]
+    fn runs_jobs() {
+        let pool = Pool::new(2);
+        let (sender, receiver) = crossbeam_channel::unbounded();
+        for i in 0..10 {
+            let sender = sender.clone();
+            pool.spawn(move || sender.send(i).unwrap());
+        }
+        let mut results: Vec<i32> = (0..10).map(|_| receiver.recv().unwrap()).collect();
+        results.sort();
+        assert_eq!(results, (0..10).collect::<Vec<_>>());
+    }
+}
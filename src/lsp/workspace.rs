@@ -0,0 +1,150 @@
+//Copyright (C) 2025-  plusmouse and other contributors
+//
+//This program is free software: you can redistribute it and/or modify
+//it under the terms of the GNU General Public License as published by
+//the Free Software Foundation, either version 3 of the License, or
+//(at your option) any later version.
+//
+//This program is distributed in the hope that it will be useful,
+//but WITHOUT ANY WARRANTY; without even the implied warranty of
+//MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//GNU General Public License for more details.
+//
+//You should have received a copy of the GNU General Public License
+//along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Files on disk as the client names them, and telling the client how
+//! reading them all is going
+
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use crossbeam_channel::Sender;
+use lsp_server::{Message, Notification, Request, RequestId};
+use lsp_types::{
+    NumberOrString, ProgressParams, ProgressParamsValue, Uri, WorkDoneProgress, WorkDoneProgressBegin,
+    WorkDoneProgressCreateParams, WorkDoneProgressEnd, WorkDoneProgressReport,
+};
+
+/// Only `file:` uris have a path, anything else is buffer-only
+pub fn uri_to_path(uri: &Uri) -> Option<PathBuf> {
+    let path = uri.as_str().strip_prefix("file://")?;
+    // The authority is empty for local files
+    let path = &path[path.find('/')?..];
+    let bytes = path.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && let Some(byte) = path.get(i + 1..i + 3).and_then(|h| u8::from_str_radix(h, 16).ok()) {
+            decoded.push(byte);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    let path = String::from_utf8(decoded).ok()?;
+    // `/c:/AddOns` on Windows
+    if cfg!(windows) && path.as_bytes().get(2) == Some(&b':') {
+        return Some(PathBuf::from(&path[1..]))
+    }
+    Some(PathBuf::from(path))
+}
+
+pub fn path_to_uri(path: &Path) -> Option<Uri> {
+    let path = path.to_str()?.replace('\\', "/");
+    let mut uri = String::from("file://");
+    if !path.starts_with('/') {
+        uri.push('/');
+    }
+    for byte in path.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => uri.push(byte as char),
+            // Windows drive letters, written by VS Code as `%3A` but accepted either way
+            b':' => uri.push(':'),
+            _ => uri.push_str(&format!("%{byte:02X}")),
+        }
+    }
+    Uri::from_str(&uri).ok()
+}
+
+/// What's on disk for a file that isn't open
+pub fn read(uri: &Uri) -> Option<String> {
+    let bytes = std::fs::read(uri_to_path(uri)?).ok()?;
+    Some(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+/// How often progress gets reported, the client only needs to see it moving
+const REPORT_INTERVAL: Duration = Duration::from_millis(100);
+
+/// A `window/workDoneProgress` the server started itself, for a number of steps
+pub struct Progress {
+    sender: Sender<Message>,
+    token: NumberOrString,
+    total: usize,
+    done: usize,
+    last_report: Instant,
+}
+
+impl Progress {
+    /// Asks the client to show progress and starts it. Clients that can't show
+    /// it get `None`, and nothing is sent
+    pub fn begin(sender: &Sender<Message>, supported: bool, token: &str, title: &str, total: usize) -> Option<Progress> {
+        if !supported {
+            return None
+        }
+        let create = Request::new(
+            RequestId::from(format!("{token}/create")),
+            String::from("window/workDoneProgress/create"),
+            WorkDoneProgressCreateParams { token: NumberOrString::String(String::from(token)) },
+        );
+        let _ = sender.send(Message::Request(create));
+        let token = NumberOrString::String(String::from(token));
+        let progress = Progress { sender: sender.clone(), token, total, done: 0, last_report: Instant::now() };
+        progress.send(WorkDoneProgress::Begin(WorkDoneProgressBegin {
+            title: String::from(title),
+            cancellable: Some(false),
+            message: Some(progress.message()),
+            percentage: Some(0),
+        }));
+        Some(progress)
+    }
+
+    /// More steps turned up while working
+    pub fn extend(&mut self, steps: usize) {
+        self.total += steps;
+    }
+
+    /// Marks a step as done, reporting every so often
+    pub fn step(&mut self) {
+        self.done += 1;
+        if self.last_report.elapsed() < REPORT_INTERVAL {
+            return
+        }
+        self.last_report = Instant::now();
+        self.send(WorkDoneProgress::Report(WorkDoneProgressReport {
+            cancellable: Some(false),
+            message: Some(self.message()),
+            percentage: Some(self.percentage()),
+        }));
+    }
+
+    pub fn end(self) {
+        self.send(WorkDoneProgress::End(WorkDoneProgressEnd { message: Some(self.message()) }));
+    }
+
+    fn message(&self) -> String {
+        format!("Indexing {}/{} files", self.done, self.total)
+    }
+
+    fn percentage(&self) -> u32 {
+        (self.done * 100).checked_div(self.total).unwrap_or(100) as u32
+    }
+
+    fn send(&self, progress: WorkDoneProgress) {
+        let params = ProgressParams { token: self.token.clone(), value: ProgressParamsValue::WorkDone(progress) };
+        let not = Notification::new(String::from("$/progress"), params);
+        let _ = self.sender.send(Message::Notification(not));
+    }
+}
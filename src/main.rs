@@ -21,9 +21,18 @@ use std::env;
 mod syntax;
 mod lsp;
 mod state;
+mod symbols;
+mod settings;
 mod diagnostics;
 mod variables;
 mod ast;
+mod annotations;
+mod validate;
+mod verify;
+mod files;
+mod check;
+mod formats;
+mod dump;
 
 fn main() -> Result<(), Box<dyn Error + Sync + Send>> {
     let args: Vec<String> = env::args().collect();
@@ -40,10 +49,35 @@ fn main() -> Result<(), Box<dyn Error + Sync + Send>> {
         syntax::debug::print_tree(&res);
         println!("{:#?}", res);
         println!("{:#?}", a.errors());
+        for d in diagnostics::check(&s, filename) {
+            let line = numbers.from_offset(d.start).0.0 + 1;
+            println!("{}: {}[{}] {}", line, d.kind.default_severity().name(), d.kind.code(), d.message);
+        }
         //println!("{:?}", numbers.from_offset(a.errors()[0].start));
         println!("syntax: {:?}", dur);
         variables::get_types(res, filename);
         Ok(())
+    } else if args.len() > 1 && args[1] == "check" {
+        match check::run(&args[2..]) {
+            Ok(false) => Ok(()),
+            Ok(true) => std::process::exit(1),
+            Err(message) => {
+                eprintln!("{}", message);
+                std::process::exit(2);
+            }
+        }
+    } else if args.len() > 1 && args[1] == "dump" {
+        if let Err(message) = dump::run(&args[2..]) {
+            eprintln!("{}", message);
+            std::process::exit(2);
+        }
+        Ok(())
+    } else if args.len() > 1 && args[1] == "verify" {
+        let dir = args.get(2).map_or(".", String::as_str);
+        if verify::run(std::path::Path::new(dir))? > 0 {
+            std::process::exit(1);
+        }
+        Ok(())
     } else {
         lsp::start_ls()
     }
@@ -0,0 +1,348 @@
+//Copyright (C) 2025-  plusmouse and other contributors
+//
+//This program is free software: you can redistribute it and/or modify
+//it under the terms of the GNU General Public License as published by
+//the Free Software Foundation, either version 3 of the License, or
+//(at your option) any later version.
+//
+//This program is distributed in the hope that it will be useful,
+//but WITHOUT ANY WARRANTY; without even the implied warranty of
+//MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//GNU General Public License for more details.
+//
+//You should have received a copy of the GNU General Public License
+//along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! What can be set, from the editor and from a `.wowls.toml` kept with the
+//! addon. Both are read as JSON shaped like
+//!
+//! ```json
+//! {
+//!     "flavor": "mainline",
+//!     "globals": ["LibStub", "MyAddonDB"],
+//!     "diagnostics": { "disable": ["need-check-nil"], "severity": { "type": "error" } },
+//!     "library": ["../Libs"],
+//!     "frameXML": "../wow-ui-source/Interface"
+//! }
+//! ```
+//!
+//! `globals` can also map names to their types, `{ "CreateFrame": "function" }`
+
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::path::{Path, PathBuf};
+
+use serde_json::Value;
+
+use crate::diagnostics::Config;
+
+/// Lives in the workspace root, and wins over what the editor sends so the
+/// whole team gets the same results
+pub const PROJECT_FILE: &str = ".wowls.toml";
+
+/// Which version of the game the addon is for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Flavor {
+    #[default]
+    Mainline,
+    Mists,
+    Cata,
+    Wrath,
+    Tbc,
+    Vanilla,
+}
+
+impl Flavor {
+    pub fn from_name(name: &str) -> Option<Flavor> {
+        match &*name.to_ascii_lowercase() {
+            "mainline" | "retail" => Some(Flavor::Mainline),
+            "mists" => Some(Flavor::Mists),
+            "cata" => Some(Flavor::Cata),
+            "wrath" => Some(Flavor::Wrath),
+            "tbc" => Some(Flavor::Tbc),
+            "vanilla" | "classic_era" => Some(Flavor::Vanilla),
+            _ => None,
+        }
+    }
+
+    /// What `[Family]` becomes in a `.toc`
+    pub fn family(self) -> &'static str {
+        match self {
+            Flavor::Mainline => "Mainline",
+            _ => "Classic",
+        }
+    }
+
+    /// What `[Game]` becomes in a `.toc`
+    pub fn game(self) -> &'static str {
+        match self {
+            Flavor::Mainline => "Standard",
+            Flavor::Mists => "Mists",
+            Flavor::Cata => "Cata",
+            Flavor::Wrath => "Wrath",
+            Flavor::Tbc => "TBC",
+            Flavor::Vanilla => "Vanilla",
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct Settings {
+    pub flavor: Flavor,
+    /// Globals set outside the workspace, by the game or other addons, with
+    /// their types. `unknown` when only the name was given
+    pub globals: Vec<(String, String)>,
+    pub diagnostics: Config,
+    /// Code that's used but not worked on, indexed for its globals but not
+    /// reported on
+    pub library: Vec<PathBuf>,
+    /// A checkout of the game's own UI code, treated like a library
+    pub frame_xml: Option<PathBuf>,
+    /// Differs whenever the settings do, to tell results from before and after apart
+    pub fingerprint: u64,
+}
+
+impl Settings {
+    /// Reads the settings, relative paths being from `base`. Returns anything
+    /// that wasn't understood alongside
+    pub fn from_json(value: &Value, base: Option<&Path>) -> (Settings, Vec<String>) {
+        let mut settings = Settings::default();
+        let mut problems = Vec::new();
+        let Some(object) = value.as_object() else {
+            return (settings, problems)
+        };
+        let path = |p: &str| match base {
+            Some(base) if Path::new(p).is_relative() => base.join(p),
+            _ => PathBuf::from(p),
+        };
+        for (key, value) in object {
+            match &**key {
+                "flavor" => match value.as_str().and_then(Flavor::from_name) {
+                    Some(flavor) => settings.flavor = flavor,
+                    None => problems.push(format!("unknown flavor: {value}")),
+                },
+                "globals" => match value {
+                    Value::Array(names) => settings.globals.extend(names.iter()
+                        .filter_map(|n| n.as_str())
+                        .map(|n| (String::from(n), String::from("unknown")))),
+                    Value::Object(types) => settings.globals.extend(types.iter()
+                        .map(|(n, t)| (n.clone(), String::from(t.as_str().unwrap_or("unknown"))))),
+                    _ => problems.push(String::from("globals should be a list of names or a table of types")),
+                },
+                "diagnostics" => problems.extend(settings.read_diagnostics(value)),
+                "library" => settings.library = strings(value).iter().map(|p| path(p)).collect(),
+                "frameXML" => settings.frame_xml = value.as_str().filter(|p| !p.is_empty()).map(path),
+                _ => problems.push(format!("unknown setting: {key}")),
+            }
+        }
+        let mut hasher = DefaultHasher::new();
+        value.to_string().hash(&mut hasher);
+        base.hash(&mut hasher);
+        settings.fingerprint = hasher.finish();
+        (settings, problems)
+    }
+
+    /// Turning a rule off is the same as setting its severity to `off`, and wins
+    fn read_diagnostics(&mut self, value: &Value) -> Vec<String> {
+        let mut severities = value.get("severity").and_then(Value::as_object).cloned().unwrap_or_default();
+        for code in strings(value.get("disable").unwrap_or(&Value::Null)) {
+            severities.insert(code, Value::from("off"));
+        }
+        let (config, unknown) = Config::from_json(&Value::Object(severities));
+        self.diagnostics = config;
+        unknown.into_iter().map(|key| format!("unknown diagnostic setting: {key}")).collect()
+    }
+
+    /// Directories indexed besides the workspace
+    pub fn library_roots(&self) -> impl Iterator<Item = &PathBuf> {
+        self.library.iter().chain(&self.frame_xml)
+    }
+
+    pub fn is_library(&self, path: &Path) -> bool {
+        self.library_roots().any(|root| path.starts_with(root))
+    }
+}
+
+fn strings(value: &Value) -> Vec<String> {
+    value.as_array().map(|a| a.iter().filter_map(|s| s.as_str().map(String::from)).collect()).unwrap_or_default()
+}
+
+/// Layers `overlay` on top of `base`, tables merging key by key
+pub fn merge(base: &mut Value, overlay: &Value) {
+    match (base, overlay) {
+        (Value::Object(base), Value::Object(overlay)) => {
+            for (key, value) in overlay {
+                merge(base.entry(key.clone()).or_insert(Value::Null), value);
+            }
+        }
+        (_, Value::Null) => (),
+        (base, overlay) => *base = overlay.clone(),
+    }
+}
+
+/// Layers everything the settings come from, later winning: what the editor
+/// sent at startup, what it's sent since, then the project file in each root.
+/// Relative paths in a project file are from its own root. Project files that
+/// couldn't be read come back alongside
+pub fn combine(initialization_options: &Value, client: &Value, roots: &[PathBuf]) -> (Value, Vec<String>) {
+    let mut merged = initialization_options.clone();
+    merge(&mut merged, client);
+    let mut errors = Vec::new();
+    for root in roots {
+        match read_project_file(root) {
+            Ok(Some(mut project)) => {
+                resolve_paths(&mut project, root);
+                merge(&mut merged, &project);
+            }
+            Ok(None) => (),
+            Err(e) => errors.push(e),
+        }
+    }
+    (merged, errors)
+}
+
+/// Makes the relative paths in settings absolute, as [`Settings::from_json`]
+/// only knows the one base
+fn resolve_paths(value: &mut Value, root: &Path) {
+    let resolve = |path: &mut Value| {
+        if let Some(p) = path.as_str() && !p.is_empty() && Path::new(p).is_relative() {
+            *path = Value::from(root.join(p).to_string_lossy().into_owned());
+        }
+    };
+    if let Some(library) = value.get_mut("library").and_then(Value::as_array_mut) {
+        library.iter_mut().for_each(resolve);
+    }
+    if let Some(frame_xml) = value.get_mut("frameXML") {
+        resolve(frame_xml);
+    }
+}
+
+/// The project file in `root`, if there is one, as JSON
+pub fn read_project_file(root: &Path) -> Result<Option<Value>, String> {
+    let path = root.join(PROJECT_FILE);
+    let text = match std::fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(format!("{}: {e}", path.display())),
+    };
+    let value: toml::Value = toml::from_str(&text).map_err(|e| format!("{}: {e}", path.display()))?;
+    serde_json::to_value(value).map(Some).map_err(|e| format!("{}: {e}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use crate::diagnostics::{DiagnosticKind, Severity};
+
+    /// A fresh directory to put a project file in
+    fn project(name: &str, toml: &str) -> PathBuf {
+        let root = std::env::temp_dir().join(format!("wowls-settings-{}-{name}", std::process::id()));
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join(PROJECT_FILE), toml).unwrap();
+        root
+    }
+
+    #[test]
+    fn reads_every_setting() {
+        let value = json!({
+            "flavor": "classic_era",
+            "globals": { "CreateFrame": "function", "MyDB": "table" },
+            "diagnostics": { "disable": ["need-check-nil"], "severity": { "concat-table": "error" } },
+            "library": ["../Libs", "/abs/Libs"],
+            "frameXML": "/ui/Interface",
+        });
+        let (settings, problems) = Settings::from_json(&value, Some(Path::new("/ws/addon")));
+        assert_eq!(problems, Vec::<String>::new());
+        assert_eq!(settings.flavor, Flavor::Vanilla);
+        assert!(settings.globals.contains(&(String::from("CreateFrame"), String::from("function"))));
+        assert_eq!(settings.diagnostics.severity(DiagnosticKind::PossiblyNil), None);
+        assert_eq!(settings.diagnostics.severity(DiagnosticKind::ConcatenatingTable), Some(Severity::Error));
+        assert_eq!(settings.library, [PathBuf::from("/ws/addon/../Libs"), PathBuf::from("/abs/Libs")]);
+        assert!(settings.is_library(Path::new("/ui/Interface/FrameXML/UIParent.lua")));
+        assert!(!settings.is_library(Path::new("/ws/addon/Core.lua")));
+    }
+
+    #[test]
+    fn takes_globals_as_a_list() {
+        let (settings, _) = Settings::from_json(&json!({ "globals": ["LibStub"] }), None);
+        assert_eq!(settings.globals, [(String::from("LibStub"), String::from("unknown"))]);
+    }
+
+    #[test]
+    fn reports_what_it_doesnt_understand() {
+        let (settings, problems) = Settings::from_json(&json!({
+            "flavor": "cataclysm-classic",
+            "colour": "blue",
+            "diagnostics": { "disable": ["no-such-rule"] },
+        }), None);
+        assert_eq!(settings.flavor, Flavor::Mainline);
+        assert_eq!(problems.len(), 3, "{problems:?}");
+    }
+
+    #[test]
+    fn fingerprints_differ_with_settings() {
+        let (a, _) = Settings::from_json(&json!({ "flavor": "wrath" }), None);
+        let (b, _) = Settings::from_json(&json!({ "flavor": "tbc" }), None);
+        let (c, _) = Settings::from_json(&json!({ "flavor": "wrath" }), None);
+        assert_ne!(a.fingerprint, b.fingerprint);
+        assert_eq!(a.fingerprint, c.fingerprint);
+    }
+
+    #[test]
+    fn merges_tables_key_by_key() {
+        let mut base = json!({ "flavor": "mainline", "diagnostics": { "disable": ["a"], "severity": { "b": "error" } } });
+        merge(&mut base, &json!({ "diagnostics": { "disable": ["c"] }, "globals": ["X"], "flavor": null }));
+        assert_eq!(base, json!({
+            "flavor": "mainline",
+            "diagnostics": { "disable": ["c"], "severity": { "b": "error" } },
+            "globals": ["X"],
+        }));
+    }
+
+    #[test]
+    fn project_file_wins() {
+        let root = project("wins", "flavor = \"wrath\"\n[diagnostics]\ndisable = [\"concat-table\"]\n");
+        let options = json!({ "flavor": "vanilla", "globals": ["FromOptions"] });
+        let client = json!({ "flavor": "tbc", "diagnostics": { "disable": ["need-check-nil"] } });
+        let (merged, errors) = combine(&options, &client, std::slice::from_ref(&root));
+        std::fs::remove_dir_all(&root).unwrap();
+        assert_eq!(errors, Vec::<String>::new());
+        let (settings, _) = Settings::from_json(&merged, Some(&root));
+        assert_eq!(settings.flavor, Flavor::Wrath);
+        assert_eq!(settings.globals, [(String::from("FromOptions"), String::from("unknown"))]);
+        assert_eq!(settings.diagnostics.severity(DiagnosticKind::ConcatenatingTable), None);
+        assert!(settings.diagnostics.severity(DiagnosticKind::PossiblyNil).is_some());
+    }
+
+    #[test]
+    fn project_paths_are_from_their_own_root() {
+        let first = project("first", "library = [\"Libs\"]\n");
+        let second = project("second", "frameXML = \"UI\"\n");
+        let roots = [first.clone(), second.clone()];
+        let (merged, errors) = combine(&Value::Null, &json!({ "library": ["Editor"] }), &roots);
+        std::fs::remove_dir_all(&first).unwrap();
+        std::fs::remove_dir_all(&second).unwrap();
+        assert_eq!(errors, Vec::<String>::new());
+        let (settings, _) = Settings::from_json(&merged, Some(&first));
+        assert_eq!(settings.library, [first.join("Libs")]);
+        assert_eq!(settings.frame_xml, Some(second.join("UI")));
+        // What the editor sent is still from the first root
+        let (merged, _) = combine(&Value::Null, &json!({ "library": ["Editor"] }), &[]);
+        assert_eq!(Settings::from_json(&merged, Some(&first)).0.library, [first.join("Editor")]);
+    }
+
+    #[test]
+    fn client_settings_win_over_initialization_options() {
+        let (merged, _) = combine(&json!({ "flavor": "vanilla" }), &json!({ "flavor": "tbc" }), &[]);
+        assert_eq!(Settings::from_json(&merged, None).0.flavor, Flavor::Tbc);
+    }
+
+    #[test]
+    fn reports_broken_project_files() {
+        let root = project("broken", "flavor = \n");
+        let (merged, errors) = combine(&json!({ "flavor": "tbc" }), &Value::Null, std::slice::from_ref(&root));
+        std::fs::remove_dir_all(&root).unwrap();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(Settings::from_json(&merged, None).0.flavor, Flavor::Tbc);
+    }
+}
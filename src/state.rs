@@ -1,14 +1,13 @@
-use std::{collections::{HashMap, HashSet}, sync::{Mutex}};
+use std::{collections::{HashMap, HashSet}, sync::{Arc, Mutex}};
 
-use lsp_types::Uri;
-use rowan::GreenNode;
-use crate::diagnostics;
+use crate::symbols::{GlobalDefinition, GlobalTypes, Summary};
 use crate::syntax::SyntaxNodePtr;
 
+/// What's known about a file in the workspace, keyed by its uri in [`State`]
 pub struct File {
-    root: GreenNode,
-    uri: Uri,
-    diagnostics: Vec<diagnostics::Diagnostic>,
+    pub summary: Summary,
+    /// Hash of the text on disk the summary is of, `None` for open buffers
+    pub hash: Option<u64>,
 }
 
 static COUNTER: Mutex<u128> = Mutex::new(0);
@@ -24,13 +23,153 @@ impl Identifier {
     }
 }
 
+/// Every file of the workspace and the globals they define between them
+#[derive(Default)]
 pub struct State {
-    files: Vec<File>,
+    files: HashMap<String, File>,
+    /// Which files define each global, so lookups don't go through every file
+    globals: HashMap<String, HashSet<String>>,
+    snapshot: Option<Arc<Globals>>,
+    /// Globals from the settings, set somewhere outside the workspace
+    known: Vec<(String, String)>,
     identifiers: HashMap<Identifier, (SyntaxNodePtr, HashSet<Identifier>)>,
 }
 
 impl State {
-    pub fn add_file(&mut self, f: File) {
-        self.files.push(f);
+    /// Replaces what's known about the file. Returns the globals whose
+    /// definitions changed, which other files may need checking again for
+    pub fn set_file(&mut self, uri: String, summary: Summary, hash: Option<u64>) -> HashSet<String> {
+        let snapshot = self.snapshot.take();
+        let old = self.remove_file(&uri);
+        let mut changed: HashSet<String> = summary.definitions.iter().map(|d| d.name.clone()).collect();
+        // Moving a definition around doesn't change what other files see
+        if old.as_ref().is_some_and(|old| typed_names(old) == typed_names(&summary)) {
+            changed.clear();
+        } else if let Some(old) = &old {
+            changed.extend(old.definitions.iter().map(|d| d.name.clone()));
+        }
+        // Still good if nothing changed
+        if changed.is_empty() {
+            self.snapshot = snapshot;
+        }
+        for definition in &summary.definitions {
+            self.globals.entry(definition.name.clone()).or_default().insert(uri.clone());
+        }
+        self.files.insert(uri, File { summary, hash });
+        changed
+    }
+
+    /// Replaces the globals known from the settings. Returns the ones that changed
+    pub fn set_known(&mut self, mut known: Vec<(String, String)>) -> HashSet<String> {
+        known.sort();
+        if known == self.known {
+            return HashSet::new()
+        }
+        self.snapshot = None;
+        let changed = self.known.drain(..).chain(known.iter().cloned()).map(|(name, _)| name).collect();
+        self.known = known;
+        changed
+    }
+
+    /// Forgets the file, giving back what was known about it
+    pub fn remove_file(&mut self, uri: &str) -> Option<Summary> {
+        let file = self.files.remove(uri)?;
+        if !file.summary.definitions.is_empty() {
+            self.snapshot = None;
+        }
+        for definition in &file.summary.definitions {
+            if let Some(uris) = self.globals.get_mut(&definition.name) {
+                uris.remove(uri);
+                if uris.is_empty() {
+                    self.globals.remove(&definition.name);
+                }
+            }
+        }
+        Some(file.summary)
+    }
+
+    pub fn contains(&self, uri: &str) -> bool {
+        self.files.contains_key(uri)
+    }
+
+    pub fn uris(&self) -> impl Iterator<Item = &str> {
+        self.files.keys().map(String::as_str)
+    }
+
+    pub fn file_count(&self) -> usize {
+        self.files.len()
+    }
+
+    /// The summaries of files as they are on disk, which can be cached
+    pub fn disk_summaries(&self) -> impl Iterator<Item = (&str, u64, &Summary)> {
+        self.files.iter().filter_map(|(uri, file)| Some((uri.as_str(), file.hash?, &file.summary)))
+    }
+
+    /// Every definition of every global, with the uri of the file it's in
+    pub fn definitions(&self) -> impl Iterator<Item = (&str, &GlobalDefinition)> {
+        self.files.iter().flat_map(|(uri, file)| file.summary.definitions.iter().map(move |d| (uri.as_str(), d)))
+    }
+
+    /// The globals as they are now, shared until a definition changes
+    pub fn globals(&mut self) -> Arc<Globals> {
+        if let Some(globals) = &self.snapshot {
+            return Arc::clone(globals)
+        }
+        let mut definitions: HashMap<String, Vec<(String, String)>> = HashMap::new();
+        for (uri, definition) in self.definitions() {
+            definitions.entry(definition.name.clone()).or_default().push((String::from(uri), definition.value_type.clone()));
+        }
+        // Outside every file, so never left out as a file's own definition
+        for (name, value_type) in &self.known {
+            definitions.entry(name.clone()).or_default().push((String::new(), value_type.clone()));
+        }
+        let globals = Arc::new(Globals { definitions });
+        self.snapshot = Some(Arc::clone(&globals));
+        globals
+    }
+
+    /// Files other than `uri` using any of `names`, which a change to them can
+    /// affect
+    pub fn dependents(&self, names: &HashSet<String>, uri: &str) -> Vec<String> {
+        let roots: HashSet<&str> = names.iter().map(|n| n.split('.').next().unwrap_or(n)).collect();
+        self.files.iter()
+            .filter(|(u, file)| *u != uri && file.summary.references.iter().any(|r| roots.contains(r.as_str())))
+            .map(|(u, _)| u.clone())
+            .collect()
+    }
+}
+
+fn typed_names(summary: &Summary) -> Vec<(&str, &str)> {
+    let mut names: Vec<(&str, &str)> = summary.definitions.iter().map(|d| (d.name.as_str(), d.value_type.as_str())).collect();
+    names.sort_unstable();
+    names
+}
+
+/// Every global's definitions, with the file and type of each, for work on
+/// other threads
+pub struct Globals {
+    definitions: HashMap<String, Vec<(String, String)>>,
+}
+
+impl Globals {
+    /// Types of the globals `references` covers that are set outside `uri`.
+    /// Ones set to different types, or only to `nil` as a placeholder, are
+    /// left out rather than guessed at
+    pub fn types_for(&self, uri: &str, references: &[String]) -> GlobalTypes {
+        let mut types = GlobalTypes::new();
+        for (name, definitions) in &self.definitions {
+            let root = name.split('.').next().unwrap_or(name);
+            if references.binary_search_by(|r| r.as_str().cmp(root)).is_err() {
+                continue
+            }
+            let mut found = definitions.iter().filter(|(u, _)| u != uri).map(|(_, t)| t.as_str());
+            let Some(first) = found.next() else {
+                continue
+            };
+            if first != "unknown" && first != "nil" && found.all(|t| t == first) {
+                types.insert(name.clone(), String::from(first));
+            }
+        }
+        types
     }
 }
@@ -0,0 +1,278 @@
+//Copyright (C) 2025-  plusmouse and other contributors
+//
+//This program is free software: you can redistribute it and/or modify
+//it under the terms of the GNU General Public License as published by
+//the Free Software Foundation, either version 3 of the License, or
+//(at your option) any later version.
+//
+//This program is distributed in the hope that it will be useful,
+//but WITHOUT ANY WARRANTY; without even the implied warranty of
+//MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//GNU General Public License for more details.
+//
+//You should have received a copy of the GNU General Public License
+//along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! What a file shares with the rest of the addon: the globals it defines and
+//! the ones it uses. Small enough to keep for every file in a workspace
+
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+use crate::ast::{AstNode, Expression, ForCountLoop, ForInLoop, FunctionDefinition, Identifier, LocalAssign};
+use crate::syntax::{self, SyntaxKind, SyntaxNode};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GlobalDefinition {
+    /// `Name`, or `Name.field` for fields of a global table
+    pub name: String,
+    pub start: usize,
+    pub end: usize,
+    /// What's assigned, as a type name, `unknown` when it isn't obvious
+    pub value_type: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Summary {
+    pub definitions: Vec<GlobalDefinition>,
+    /// Globals read or written, by their first name, sorted
+    pub references: Vec<String>,
+}
+
+/// The type of each global the other files agree on, by name
+pub type GlobalTypes = HashMap<String, String>;
+
+struct Collector {
+    scopes: Vec<HashSet<String>>,
+    definitions: Vec<GlobalDefinition>,
+    references: HashSet<String>,
+}
+
+impl Collector {
+    fn is_local(&self, name: &str) -> bool {
+        self.scopes.iter().any(|s| s.contains(name))
+    }
+
+    fn declare(&mut self, name: String) {
+        self.scopes.last_mut().expect("always a scope").insert(name);
+    }
+
+    fn define(&mut self, chain: &[String], node: &SyntaxNode, value_type: &str) {
+        let Some(first) = chain.first() else {
+            return
+        };
+        if self.is_local(first) {
+            return
+        }
+        // Without the whitespace the parser leaves attached to the end
+        let mut tokens = node.descendants_with_tokens().filter_map(|n| n.into_token())
+            .filter(|t| !matches!(t.kind(), SyntaxKind::Whitespace | SyntaxKind::Newline | SyntaxKind::Comment));
+        let Some(first_token) = tokens.next() else {
+            return
+        };
+        let last_token = tokens.last().unwrap_or_else(|| first_token.clone());
+        self.definitions.push(GlobalDefinition {
+            name: chain.join("."),
+            start: usize::from(first_token.text_range().start()),
+            end: usize::from(last_token.text_range().end()),
+            value_type: String::from(value_type),
+        });
+    }
+
+    fn walk_children(&mut self, node: &SyntaxNode) {
+        for child in node.children() {
+            self.walk(&child);
+        }
+    }
+
+    fn walk(&mut self, node: &SyntaxNode) {
+        match node.kind() {
+            SyntaxKind::Block => {
+                self.scopes.push(HashSet::new());
+                self.walk_children(node);
+                self.scopes.pop();
+            }
+            SyntaxKind::LocalAssignStatement => {
+                let Some(local) = LocalAssign::cast(node.clone()) else {
+                    return
+                };
+                // The values can't see the names they're being assigned to
+                if let Some(list) = local.expression_list() {
+                    self.walk(list.syntax());
+                }
+                for name in local.name_list().map(|l| l.names()).unwrap_or_default() {
+                    self.declare(name);
+                }
+            }
+            SyntaxKind::AssignStatement => {
+                let values: Vec<Expression> = node.children()
+                    .find(|c| c.kind() == SyntaxKind::ExpressionList)
+                    .map(|l| l.children().filter_map(Expression::cast).collect())
+                    .unwrap_or_default();
+                let targets = node.children().find(|c| c.kind() == SyntaxKind::VariableList)
+                    .map(|l| l.children().filter_map(Identifier::cast).collect::<Vec<_>>())
+                    .unwrap_or_default();
+                for (i, target) in targets.iter().enumerate() {
+                    if let Some(chain) = target.name_chain() {
+                        let value_type = values.get(i).map_or("unknown", value_type);
+                        self.define(&chain, target.syntax(), value_type);
+                    }
+                }
+                self.walk_children(node);
+            }
+            SyntaxKind::FunctionDefinition => {
+                let Some(function) = FunctionDefinition::cast(node.clone()) else {
+                    return
+                };
+                if function.is_local() {
+                    if let Some(name) = function.local_name() {
+                        self.declare(name);
+                    }
+                } else if let Some(identifier) = function.identifier() {
+                    // Methods are just fields, `a:b` defines `a.b`
+                    self.define(&identifier.names(), identifier.syntax(), "function");
+                    self.walk(identifier.syntax());
+                }
+                self.scopes.push(HashSet::new());
+                if function.identifier().is_some_and(|i| i.method().is_some()) {
+                    self.declare(String::from("self"));
+                }
+                for parameter in function.params().map(|p| p.names()).unwrap_or_default() {
+                    self.declare(parameter);
+                }
+                if let Some(block) = function.block() {
+                    self.walk(block.syntax());
+                }
+                self.scopes.pop();
+            }
+            SyntaxKind::ForCountLoop => {
+                let Some(for_loop) = ForCountLoop::cast(node.clone()) else {
+                    return
+                };
+                if let Some(list) = for_loop.expression_list() {
+                    self.walk(list.syntax());
+                }
+                self.scopes.push(HashSet::new());
+                if let Some(name) = for_loop.name() {
+                    self.declare(name);
+                }
+                if let Some(block) = for_loop.block() {
+                    self.walk(block.syntax());
+                }
+                self.scopes.pop();
+            }
+            SyntaxKind::ForInLoop => {
+                let Some(for_loop) = ForInLoop::cast(node.clone()) else {
+                    return
+                };
+                if let Some(list) = for_loop.expression_list() {
+                    self.walk(list.syntax());
+                }
+                self.scopes.push(HashSet::new());
+                for name in for_loop.name_list().map(|l| l.names()).unwrap_or_default() {
+                    self.declare(name);
+                }
+                if let Some(block) = for_loop.block() {
+                    self.walk(block.syntax());
+                }
+                self.scopes.pop();
+            }
+            SyntaxKind::Identifier => {
+                let first = node.children_with_tokens().find(|n|
+                    !matches!(n.kind(), SyntaxKind::Whitespace | SyntaxKind::Newline | SyntaxKind::Comment)
+                );
+                match first {
+                    Some(rowan::NodeOrToken::Token(t)) if t.kind() == SyntaxKind::Name && !self.is_local(t.text()) => {
+                        self.references.insert(t.text().to_string());
+                    }
+                    Some(rowan::NodeOrToken::Node(prefix)) => self.walk(&prefix),
+                    _ => (),
+                }
+                // Later identifiers are the field names after a prefix, only keys are expressions
+                for child in node.children().filter(|c| c.kind() == SyntaxKind::Expression) {
+                    self.walk(&child);
+                }
+            }
+            SyntaxKind::Field => {
+                // The `name` of `name = value` is a key, not a global
+                let skip_name = node.children_with_tokens().any(|n| n.kind() == SyntaxKind::Assign)
+                    && !node.children_with_tokens().any(|n| n.kind() == SyntaxKind::LeftSquareBracket);
+                for (i, child) in node.children().enumerate() {
+                    if !(skip_name && i == 0 && child.kind() == SyntaxKind::Identifier) {
+                        self.walk(&child);
+                    }
+                }
+            }
+            _ => self.walk_children(node),
+        }
+    }
+}
+
+fn value_type(expression: &Expression) -> &'static str {
+    match expression {
+        Expression::Function(_) => "function",
+        Expression::TableConstructor(_) => "table",
+        Expression::Literal(l) if l.get_string().is_some() => "string",
+        Expression::Literal(l) if l.get_number().is_some() => "number",
+        Expression::Literal(l) if l.get_bool().is_some() => "boolean",
+        Expression::Literal(l) if l.is_nil() => "nil",
+        _ => "unknown",
+    }
+}
+
+pub fn summarize(text: &str) -> Summary {
+    let mut parser = syntax::syntax::Generator::new(text);
+    let root = SyntaxNode::new_root(parser.process_all());
+    let mut collector = Collector { scopes: Vec::new(), definitions: Vec::new(), references: HashSet::new() };
+    collector.walk(&root);
+    let mut references: Vec<String> = collector.references.into_iter().collect();
+    references.sort();
+    Summary { definitions: collector.definitions, references }
+}
+
+/// Named frames in XML become globals, except the `$parent` ones whose names
+/// depend on where they end up
+pub fn summarize_xml(text: &str) -> Summary {
+    let mut definitions = Vec::new();
+    let mut from = 0;
+    while let Some(found) = text[from..].find("name=") {
+        let start = from + found;
+        from = start + 5;
+        // Only attributes, `<Frame name=` and not `<Frame parentKey="name=`
+        let before = text[..start].chars().next_back();
+        if !before.is_some_and(char::is_whitespace) {
+            continue
+        }
+        let tag_open = text[..start].rfind('<');
+        if tag_open.is_none_or(|open| text[open..start].contains('>') || text[open..].starts_with("<!--")) {
+            continue
+        }
+        let value = &text[from..];
+        let Some(quote) = value.chars().next().filter(|c| *c == '"' || *c == '\'') else {
+            continue
+        };
+        let Some(length) = value[1..].find(quote) else {
+            continue
+        };
+        let name = &value[1..length + 1];
+        if name.is_empty() || name.contains('$') {
+            continue
+        }
+        definitions.push(GlobalDefinition {
+            name: String::from(name),
+            start: from + 1,
+            end: from + 1 + length,
+            value_type: String::from("table"),
+        });
+    }
+    Summary { definitions, references: Vec::new() }
+}
+
+/// What a file defines, going by its extension
+pub fn summarize_file(path: &Path, text: &str) -> Summary {
+    match path.extension().map(|e| e.to_ascii_lowercase()) {
+        Some(e) if e == "lua" => summarize(text),
+        Some(e) if e == "xml" => summarize_xml(text),
+        _ => Summary::default(),
+    }
+}
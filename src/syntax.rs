@@ -1,5 +1,6 @@
 pub mod lexer;
 pub mod syntax;
+pub mod literals;
 
 pub mod debug;
 
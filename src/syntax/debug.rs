@@ -13,37 +13,172 @@
 //You should have received a copy of the GNU General Public License
 //along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
+use std::fmt::Write;
+
 use crate::syntax;
+use crate::syntax::SyntaxKind;
+use crate::syntax::syntax::str_to_keyword;
+
+/// Every node and token under `node` in source order, with how deep it is
+fn elements(node: &syntax::SyntaxNode) -> impl Iterator<Item = (usize, syntax::syntax::SyntaxElement)> + '_ {
+    node.preorder_with_tokens().filter_map(move |event| match event {
+        rowan::WalkEvent::Enter(child) => {
+            // A token's ancestors start at its parent, a node's at itself
+            let depth = child.ancestors().take_while(|n| n != node).count() + usize::from(child.as_token().is_some());
+            Some((depth, child))
+        },
+        rowan::WalkEvent::Leave(_) => None,
+    })
+}
 
-fn dump_nodes(node: &syntax::SyntaxNode, indent: i32) {
-    let mut counter = indent * 2;
-    while counter > 0 {
-        print!(" ");
-        counter = counter - 1;
+/// `Kind@start..end`, with the text quoted and escaped for tokens
+fn describe_element(element: &syntax::syntax::SyntaxElement) -> String {
+    match element {
+        rowan::NodeOrToken::Node(n) => format!("{:?}@{:?}", n.kind(), n.text_range()),
+        rowan::NodeOrToken::Token(t) => format!("{:?}@{:?} {:?}", t.kind(), t.text_range(), t.text()),
     }
-    print!("Node: {:?}, {:?}\n", node.kind(), node.text_range());
-    for child in node.children_with_tokens() {
-        match child {
-            rowan::NodeOrToken::Node(n) => {
-                dump_nodes(&n, indent + 1);
-            },
-            rowan::NodeOrToken::Token(t) => {
-                let mut counter = (indent + 1) * 2;
-                while counter > 0 {
-                    print!(" ");
-                    counter = counter - 1;
-                }
-                let mut text = "";
-                if t.text() != "\n" {
-                    text = t.text()
-                }
-                print!("{:?}, {:?}, \"{}\"\n", t.kind(), t.text_range(), text);
-            }
-        }
+}
+
+/// The concrete tree, one node or token a line, indented by depth
+pub fn tree_string(node: &syntax::SyntaxNode) -> String {
+    let mut out = String::new();
+    for (depth, element) in elements(node) {
+        let _ = writeln!(out, "{}{}", "  ".repeat(depth), describe_element(&element));
     }
+    out
 }
+
+/// The concrete tree as JSON: nodes have `children`, tokens `text`
+pub fn tree_json(node: &syntax::SyntaxNode) -> serde_json::Value {
+    let children: Vec<serde_json::Value> = node.children_with_tokens().map(|child| match child {
+        rowan::NodeOrToken::Node(n) => tree_json(&n),
+        rowan::NodeOrToken::Token(t) => serde_json::json!({
+            "kind": format!("{:?}", t.kind()),
+            "start": u32::from(t.text_range().start()),
+            "end": u32::from(t.text_range().end()),
+            "text": t.text(),
+        }),
+    }).collect();
+    serde_json::json!({
+        "kind": format!("{:?}", node.kind()),
+        "start": u32::from(node.text_range().start()),
+        "end": u32::from(node.text_range().end()),
+        "children": children,
+    })
+}
+
 #[allow(dead_code)]
 pub fn print_tree(green: &rowan::GreenNode) {
     let root = syntax::SyntaxNode::new_root(green.clone());
-    dump_nodes(&root, 0);
+    print!("{}", tree_string(&root));
+}
+
+/// Whether a token's text is something the lexer could have given that kind
+pub fn token_matches(kind: SyntaxKind, text: &str) -> bool {
+    let fixed = match kind {
+        SyntaxKind::Dot => ".",
+        SyntaxKind::DoubleDot => "..",
+        SyntaxKind::TripleDot | SyntaxKind::ParameterVarArgs => "...",
+        SyntaxKind::LeftBracket => "(",
+        SyntaxKind::RightBracket => ")",
+        SyntaxKind::LeftCurlyBracket => "{",
+        SyntaxKind::RightCurlyBracket => "}",
+        SyntaxKind::LeftSquareBracket => "[",
+        SyntaxKind::RightSquareBracket => "]",
+        SyntaxKind::Minus => "-",
+        SyntaxKind::Plus => "+",
+        SyntaxKind::Asterisk => "*",
+        SyntaxKind::Slash => "/",
+        SyntaxKind::Modulo => "%",
+        SyntaxKind::Semicolon => ";",
+        SyntaxKind::Colon => ":",
+        SyntaxKind::EqualsBoolean => "==",
+        SyntaxKind::NotEqualsBoolean => "~=",
+        SyntaxKind::LessThanOrEquals => "<=",
+        SyntaxKind::GreaterThanOrEquals => ">=",
+        SyntaxKind::LessThan => "<",
+        SyntaxKind::GreaterThan => ">",
+        SyntaxKind::Assign => "=",
+        SyntaxKind::Comma => ",",
+        SyntaxKind::Hash => "#",
+        SyntaxKind::Hat => "^",
+        _ => "",
+    };
+    if !fixed.is_empty() {
+        return text == fixed
+    }
+    match kind {
+        SyntaxKind::Invalid => true,
+        SyntaxKind::Whitespace => !text.is_empty() && text.chars().all(char::is_whitespace),
+        SyntaxKind::Newline => matches!(text, "\n" | "\r\n"),
+        SyntaxKind::Comment => text.starts_with("--"),
+        SyntaxKind::String => text.starts_with(['"', '\'', '[']),
+        SyntaxKind::Number => text.starts_with(|c: char| c.is_ascii_digit() || c == '.'),
+        SyntaxKind::Name | SyntaxKind::Parameter => {
+            text.starts_with(|c: char| c.is_alphabetic() || c == '_')
+                && text.chars().all(|c| c.is_alphanumeric() || c == '_')
+                && str_to_keyword(text) == SyntaxKind::Name
+        }
+        // Keywords come after `Missing` in the kind list
+        _ if kind > SyntaxKind::Missing => str_to_keyword(text) == kind,
+        _ => false,
+    }
+}
+
+/// The first place a parsed tree doesn't faithfully hold its source
+#[derive(Debug, Clone)]
+pub struct Mismatch {
+    pub message: String,
+    /// The tree around the problem, the problem line marked with `>`
+    pub excerpt: String,
+}
+
+/// An indented dump of the node around `offset`, marking the line for it
+fn excerpt(root: &syntax::SyntaxNode, offset: usize) -> String {
+    let at = rowan::TextSize::try_from(offset).unwrap_or_default().min(root.text_range().end());
+    let node = root.token_at_offset(at).left_biased()
+        .and_then(|token| token.parent_ancestors().nth(1).or(token.parent()))
+        .unwrap_or_else(|| root.clone());
+    let mut out = String::new();
+    let mut hit = None;
+    for (i, (depth, element)) in elements(&node).enumerate() {
+        let range = element.text_range();
+        if hit.is_none() && element.as_token().is_some() && range.start() <= at && (at < range.end() || range.is_empty()) {
+            hit = Some(i);
+        }
+        let _ = writeln!(out, "{}{}", "  ".repeat(depth), describe_element(&element));
+    }
+    let from = hit.unwrap_or(0).saturating_sub(12);
+    out.lines().enumerate().skip(from).take(25)
+        .map(|(i, l)| format!("{} {}\n", if Some(i) == hit { ">" } else { " " }, l))
+        .collect()
+}
+
+/// Checks the tree gives back exactly `text` and that every token's kind fits its text
+pub fn verify_tree(root: &syntax::SyntaxNode, text: &str) -> Result<(), Mismatch> {
+    let tree_text = root.text().to_string();
+    if tree_text != text {
+        let offset = tree_text.bytes().zip(text.bytes()).position(|(a, b)| a != b)
+            .unwrap_or(tree_text.len().min(text.len()));
+        let line = text[..offset.min(text.len())].matches('\n').count() + 1;
+        return Err(Mismatch {
+            message: format!("tree text differs from the source at line {} (tree has {} bytes, source {})", line, tree_text.len(), text.len()),
+            excerpt: excerpt(root, offset),
+        })
+    }
+    for token in root.descendants_with_tokens().filter_map(|n| n.into_token()) {
+        // Stand-ins for missing tokens are empty on purpose
+        if token.text().is_empty() && token.parent().is_some_and(|p| p.kind() == SyntaxKind::Missing) {
+            continue
+        }
+        if !token_matches(token.kind(), token.text()) {
+            let offset = usize::from(token.text_range().start());
+            let line = text[..offset].matches('\n').count() + 1;
+            return Err(Mismatch {
+                message: format!("{:?} token at line {} has the text {:?}", token.kind(), line, token.text()),
+                excerpt: excerpt(root, offset),
+            })
+        }
+    }
+    Ok(())
 }
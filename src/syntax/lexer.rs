@@ -245,20 +245,23 @@ impl<'a> Generator<'a> {
     fn scan_long_bracket_string(&mut self, start: usize) -> Option<Token> {
         let (_, ch, end) = self.next_char()?;
         let mut opening_counter = 0;
+        // Tokens only reach as far as what's been consumed, the rest gets scanned again
+        let mut consumed = end;
         if ch == '=' {
             opening_counter += 1;
             while let Some((_, ch, end)) = self.peek_char() {
                 match ch {
                     '=' => opening_counter += 1,
                     '[' => break,
-                    _ => return Some(Token{kind: TokenKind::Invalid, start, end})
+                    _ => return Some(Token{kind: TokenKind::Invalid, start, end: consumed})
                 }
                 self.next_char();
+                consumed = end;
             }
         } else if ch != '[' {
             return Some(Token{kind: TokenKind::Invalid, start, end})
         }
-        let mut end = start;
+        let mut end = consumed;
         while let Some((_, ch, end_2)) = self.next_char() {
             end = end_2;
             if ch == ']' {
@@ -307,10 +310,10 @@ impl<'a> Generator<'a> {
     }
 
     fn scan_minus(&mut self, start: usize, end: usize) -> Option<Token> {
-        if let Some((_, ch, _)) = self.peek_char() {
+        if let Some((_, ch, dash_end)) = self.peek_char() {
             if ch == '-' {
                 self.next_char();
-                if let Some((pos, ch, end)) = self.peek_char() {
+                if let Some((pos, ch, mut end)) = self.peek_char() {
                     if ch == '[' {
                         self.next_char();
                         let multiline = self.scan_long_bracket_string(pos);
@@ -322,7 +325,8 @@ impl<'a> Generator<'a> {
                                         start, end: t.end,
                                     })
                                 }
-                                TokenKind::Invalid => (),
+                                // Whatever it consumed carries on as a plain comment
+                                TokenKind::Invalid => end = t.end,
                                 _ => return Some(Token{
                                         kind: TokenKind::Comment { validity: token_validity::Comment::Valid, modifier: token_modifier::Comment::Multiline },
                                         start, end: t.end,
@@ -331,7 +335,8 @@ impl<'a> Generator<'a> {
 
                         }
                     }
-                    let mut end = end;
+                    // Only a consumed `[` belongs to the comment, not a newline we peeked at
+                    let mut end = if ch == '[' { end } else { pos };
                     while let Some((_, ch, end_2)) = self.peek_char() {
                         if ch == '\r' || ch == '\n' {
                             break;
@@ -345,6 +350,11 @@ impl<'a> Generator<'a> {
                         start, end,
                     })
                 }
+                // `--` right at the end of the file
+                return Some(Token{
+                    kind: TokenKind::Comment { validity: token_validity::Comment::Valid, modifier: token_modifier::Comment::Oneline },
+                    start, end: dash_end,
+                })
             }
         }
         Some(Token{ kind: TokenKind::Minus, start, end })
@@ -0,0 +1,310 @@
+//Copyright (C) 2025-  plusmouse and other contributors
+//
+//This program is free software: you can redistribute it and/or modify
+//it under the terms of the GNU General Public License as published by
+//the Free Software Foundation, either version 3 of the License, or
+//(at your option) any later version.
+//
+//This program is distributed in the hope that it will be useful,
+//but WITHOUT ANY WARRANTY; without even the implied warranty of
+//MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//GNU General Public License for more details.
+//
+//You should have received a copy of the GNU General Public License
+//along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Turning literal tokens into the values they stand for, following Lua 5.1
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EscapeErrorKind {
+    /// `\q` and the like, which 5.1 quietly reads as the character itself
+    Unknown,
+    /// `\ddd` over 255
+    TooLarge,
+}
+
+/// A bad escape, with offsets into the literal's text
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EscapeError {
+    pub start: usize,
+    pub end: usize,
+    pub kind: EscapeErrorKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecodedString {
+    pub bytes: Vec<u8>,
+    pub errors: Vec<EscapeError>,
+}
+
+/// Decodes a string literal as written in source, quotes or long brackets
+/// included. An unterminated literal decodes up to where it stops
+pub fn decode_string(text: &str) -> DecodedString {
+    let bytes = text.as_bytes();
+    match bytes.first() {
+        Some(b'"') | Some(b'\'') => decode_quoted(bytes),
+        Some(b'[') => DecodedString { bytes: decode_long(bytes), errors: Vec::new() },
+        _ => DecodedString { bytes: bytes.to_vec(), errors: Vec::new() },
+    }
+}
+
+/// Length of the newline at `i`, treating `\r\n` and `\n\r` as one like Lua does
+fn newline_length(bytes: &[u8], i: usize) -> usize {
+    match (bytes.get(i), bytes.get(i + 1)) {
+        (Some(b'\n'), Some(b'\r')) | (Some(b'\r'), Some(b'\n')) => 2,
+        (Some(b'\n'), _) | (Some(b'\r'), _) => 1,
+        _ => 0,
+    }
+}
+
+fn decode_quoted(bytes: &[u8]) -> DecodedString {
+    let quote = bytes[0];
+    let mut end = bytes.len();
+    if end > 1 && bytes[end - 1] == quote {
+        // A closing quote can't be escaped by a `\` that's itself escaped
+        let backslashes = bytes[1..end - 1].iter().rev().take_while(|b| **b == b'\\').count();
+        if backslashes % 2 == 0 {
+            end -= 1;
+        }
+    }
+
+    let mut decoded = Vec::with_capacity(end);
+    let mut errors = Vec::new();
+    let mut i = 1;
+    while i < end {
+        let b = bytes[i];
+        if b != b'\\' {
+            decoded.push(b);
+            i += 1;
+            continue
+        }
+        let start = i;
+        i += 1;
+        let Some(&escaped) = bytes.get(i).filter(|_| i < end) else {
+            break
+        };
+        let simple = match escaped {
+            b'a' => Some(0x07),
+            b'b' => Some(0x08),
+            b'f' => Some(0x0c),
+            b'n' => Some(b'\n'),
+            b'r' => Some(b'\r'),
+            b't' => Some(b'\t'),
+            b'v' => Some(0x0b),
+            b'\\' | b'"' | b'\'' => Some(escaped),
+            _ => None,
+        };
+        if let Some(value) = simple {
+            decoded.push(value);
+            i += 1;
+        } else if escaped == b'\n' || escaped == b'\r' {
+            decoded.push(b'\n');
+            i += newline_length(bytes, i);
+        } else if escaped.is_ascii_digit() {
+            let digits = bytes[i..end].iter().take(3).take_while(|b| b.is_ascii_digit()).count();
+            let value = bytes[i..i + digits].iter().fold(0u32, |v, d| v * 10 + u32::from(d - b'0'));
+            i += digits;
+            if value > 255 {
+                errors.push(EscapeError { start, end: i, kind: EscapeErrorKind::TooLarge });
+            } else {
+                decoded.push(value as u8);
+            }
+        } else {
+            let length = std::str::from_utf8(&bytes[i..end]).ok()
+                .and_then(|s| s.chars().next())
+                .map_or(1, |c| c.len_utf8());
+            errors.push(EscapeError { start, end: i + length, kind: EscapeErrorKind::Unknown });
+            decoded.extend_from_slice(&bytes[i..i + length]);
+            i += length;
+        }
+    }
+    DecodedString { bytes: decoded, errors }
+}
+
+fn decode_long(bytes: &[u8]) -> Vec<u8> {
+    let level = bytes[1..].iter().take_while(|b| **b == b'=').count();
+    let mut start = (level + 2).min(bytes.len());
+    let closing_length = level + 2;
+    let mut end = bytes.len();
+    if end >= start + closing_length
+        && bytes[end - 1] == b']'
+        && bytes[end - closing_length] == b']'
+        && bytes[end - closing_length + 1..end - 1].iter().all(|b| *b == b'=') {
+        end -= closing_length;
+    }
+    // A newline straight after the opening bracket isn't part of the string
+    start += newline_length(&bytes[..end], start);
+
+    let mut decoded = Vec::with_capacity(end.saturating_sub(start));
+    let mut i = start;
+    while i < end {
+        let length = newline_length(&bytes[..end], i);
+        if length > 0 {
+            decoded.push(b'\n');
+            i += length;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    decoded
+}
+
+/// Reads a number as Lua 5.1 does, for literals and strings coerced in
+/// arithmetic: decimal with optional fraction and exponent, or `0x` hex.
+/// Surrounding whitespace is allowed, as `tonumber` allows it
+pub fn parse_number(text: &str) -> Option<f64> {
+    let text = text.trim_matches(|c: char| c.is_ascii_whitespace());
+    let (negative, unsigned) = match text.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, text.strip_prefix('+').unwrap_or(text)),
+    };
+    let value = if let Some(hex) = unsigned.strip_prefix("0x").or_else(|| unsigned.strip_prefix("0X")) {
+        if hex.is_empty() || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return None
+        }
+        hex.bytes().fold(0.0, |v, d| v * 16.0 + f64::from((d as char).to_digit(16).expect("checked above")))
+    } else {
+        // Rust also takes "inf" and "nan", which Lua doesn't
+        let (mantissa, exponent) = match unsigned.find(['e', 'E']) {
+            Some(i) => (&unsigned[..i], Some(&unsigned[i + 1..])),
+            None => (unsigned, None),
+        };
+        let digits = mantissa.bytes().filter(|b| b.is_ascii_digit()).count();
+        let dots = mantissa.bytes().filter(|b| *b == b'.').count();
+        if digits == 0 || digits + dots != mantissa.len() || dots > 1 {
+            return None
+        }
+        if let Some(exponent) = exponent {
+            let exponent = exponent.strip_prefix(['+', '-']).unwrap_or(exponent);
+            if exponent.is_empty() || !exponent.bytes().all(|b| b.is_ascii_digit()) {
+                return None
+            }
+        }
+        unsigned.parse::<f64>().ok()?
+    };
+    Some(if negative { -value } else { value })
+}
+
+/// Writes a number the way Lua 5.1's `tostring` does, which is C's `%.14g`
+pub fn format_number(value: f64) -> String {
+    if value.is_nan() {
+        return String::from(if value.is_sign_negative() { "-nan" } else { "nan" })
+    }
+    if value.is_infinite() {
+        return String::from(if value < 0.0 { "-inf" } else { "inf" })
+    }
+    if value == 0.0 {
+        return String::from(if value.is_sign_negative() { "-0" } else { "0" })
+    }
+    const PRECISION: i32 = 14;
+    // Round to the significant digits first, as that can carry into the next power of ten
+    let scientific = format!("{:.*e}", (PRECISION - 1) as usize, value);
+    let (mantissa, exponent) = scientific.split_once('e').expect("formatted with an exponent");
+    let exponent: i32 = exponent.parse().expect("formatted exponent is a number");
+    if !(-4..PRECISION).contains(&exponent) {
+        let mantissa = trim_fraction(mantissa);
+        let sign = if exponent < 0 { '-' } else { '+' };
+        format!("{}e{}{:02}", mantissa, sign, exponent.abs())
+    } else {
+        let decimals = (PRECISION - 1 - exponent) as usize;
+        trim_fraction(&format!("{:.*}", decimals, value)).to_string()
+    }
+}
+
+fn trim_fraction(number: &str) -> &str {
+    if number.contains('.') {
+        number.trim_end_matches('0').trim_end_matches('.')
+    } else {
+        number
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decoded(text: &str) -> Vec<u8> {
+        let decoded = decode_string(text);
+        assert_eq!(decoded.errors, [], "{text}");
+        decoded.bytes
+    }
+
+    #[test]
+    fn decodes_simple_escapes() {
+        assert_eq!(decoded(r#""a\nb\tc""#), b"a\nb\tc");
+        assert_eq!(decoded(r#"'\\ \" \' \a\b\f\r\v'"#), b"\\ \" ' \x07\x08\x0c\r\x0b");
+    }
+
+    #[test]
+    fn decodes_decimal_escapes() {
+        assert_eq!(decoded(r#""\65\066\0673""#), b"AB\x433");
+        assert_eq!(decoded(r#""\0\255""#), b"\0\xff");
+    }
+
+    #[test]
+    fn decodes_escaped_newlines() {
+        assert_eq!(decoded("\"a\\\nb\""), b"a\nb");
+        assert_eq!(decoded("\"a\\\r\nb\""), b"a\nb");
+    }
+
+    #[test]
+    fn decodes_long_brackets() {
+        assert_eq!(decoded("[[a\\nb]]"), b"a\\nb");
+        assert_eq!(decoded("[==[a]]b]==]"), b"a]]b");
+        // A newline straight after the opening bracket is skipped
+        assert_eq!(decoded("[[\nline]]"), b"line");
+        assert_eq!(decoded("[[\r\nline]]"), b"line");
+    }
+
+    #[test]
+    fn reports_bad_escapes() {
+        let decoded = decode_string(r#""a\qb""#);
+        assert_eq!(decoded.bytes, b"aqb");
+        assert_eq!(decoded.errors, [EscapeError { start: 2, end: 4, kind: EscapeErrorKind::Unknown }]);
+
+        let decoded = decode_string(r#""\256""#);
+        assert_eq!(decoded.errors.iter().map(|e| (e.start, e.end, e.kind)).collect::<Vec<_>>(), [(1, 5, EscapeErrorKind::TooLarge)]);
+    }
+
+    #[test]
+    fn decodes_unterminated_strings() {
+        assert_eq!(decode_string("\"abc").bytes, b"abc");
+        assert_eq!(decode_string("[[abc").bytes, b"abc");
+    }
+
+    #[test]
+    fn parses_numbers() {
+        assert_eq!(parse_number("42"), Some(42.0));
+        assert_eq!(parse_number("3.5"), Some(3.5));
+        assert_eq!(parse_number(".5"), Some(0.5));
+        assert_eq!(parse_number("5."), Some(5.0));
+        assert_eq!(parse_number("1e3"), Some(1000.0));
+        assert_eq!(parse_number("2.5E-2"), Some(0.025));
+        assert_eq!(parse_number("0xff"), Some(255.0));
+        assert_eq!(parse_number("0XA"), Some(10.0));
+        assert_eq!(parse_number(" -7 "), Some(-7.0));
+    }
+
+    #[test]
+    fn rejects_what_lua_rejects() {
+        for text in ["", ".", "0x", "1e", "1e+", "1.2.3", "inf", "nan", "0xg", "1_000", "e5"] {
+            assert_eq!(parse_number(text), None, "{text}");
+        }
+    }
+
+    #[test]
+    fn formats_like_tostring() {
+        assert_eq!(format_number(86400.0), "86400");
+        assert_eq!(format_number(0.1), "0.1");
+        assert_eq!(format_number(1.0 / 3.0), "0.33333333333333");
+        assert_eq!(format_number(1e15), "1e+15");
+        assert_eq!(format_number(123456789012345.0), "1.2345678901234e+14");
+        assert_eq!(format_number(0.0001), "0.0001");
+        assert_eq!(format_number(0.00001), "1e-05");
+        assert_eq!(format_number(-4.0), "-4");
+        assert_eq!(format_number(-0.0), "-0");
+        assert_eq!(format_number(f64::INFINITY), "inf");
+        assert_eq!(format_number(f64::NEG_INFINITY), "-inf");
+    }
+}
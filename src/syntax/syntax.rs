@@ -17,7 +17,6 @@ use crate::syntax::lexer::token_validity;
 use crate::syntax::lexer::Generator as TokenGenerator;
 use crate::syntax::lexer::Token;
 use crate::syntax::lexer::TokenKind;
-use std::cmp::min;
 use rowan::GreenNodeBuilder;
 
 #[repr(u16)]
@@ -71,6 +70,8 @@ pub enum SyntaxKind {
     AssignStatement,
     LocalAssignStatement,
     ReturnStatement,
+    BreakStatement,
+    EmptyStatement,
     VariableList,
     NameList,
     Expression,
@@ -92,6 +93,7 @@ pub enum SyntaxKind {
     Field,
     //IndexingVariable,
     Literal,
+    Missing,
 
     AndKeyword,
     BreakKeyword,
@@ -140,11 +142,113 @@ pub enum ErrorKind {
     InvalidName,
     InvalidFunction,
 }
+/// Errors past this many are dropped, a file this broken has bigger problems
+const MAX_ERRORS: usize = 100;
+
 #[derive(Debug, Clone, Copy)]
 pub struct Error {
     pub start: usize,
     pub end: usize,
     pub kind: ErrorKind,
+    /// What would have been accepted here instead
+    pub expected: &'static [SyntaxKind],
+    /// The token that started the construct left unfinished, like the
+    /// `function` missing its `end`
+    pub opener: Option<(usize, usize)>,
+}
+
+impl ErrorKind {
+    fn expected(&self) -> &'static [SyntaxKind] {
+        match self {
+            ErrorKind::ExpectingComma => &[SyntaxKind::Comma],
+            ErrorKind::ExpectingCommaOrBracket => &[SyntaxKind::Comma, SyntaxKind::RightBracket],
+            ErrorKind::ExpectingThen => &[SyntaxKind::ThenKeyword],
+            ErrorKind::ExpectingDo => &[SyntaxKind::DoKeyword],
+            ErrorKind::ExpectingName => &[SyntaxKind::Name],
+            ErrorKind::ExpectingClosingBracket => &[SyntaxKind::RightBracket],
+            ErrorKind::ExpectingFunctionCall => &[SyntaxKind::ArgumentList],
+            ErrorKind::ExpectingExpression => &[SyntaxKind::Expression],
+            ErrorKind::ExpectingOperator => &[SyntaxKind::Assign],
+            ErrorKind::NotClosedBlock => &[SyntaxKind::EndKeyword],
+            _ => &[],
+        }
+    }
+}
+
+/// The token list for a block ending in `kind`
+fn terminator_of(kind: SyntaxKind) -> &'static [SyntaxKind] {
+    match kind {
+        SyntaxKind::UntilKeyword => &[SyntaxKind::UntilKeyword],
+        _ => &[SyntaxKind::EndKeyword],
+    }
+}
+
+/// How a token shows up in an error message
+fn describe(kind: SyntaxKind) -> &'static str {
+    match kind {
+        SyntaxKind::Name => "name",
+        SyntaxKind::Expression => "expression",
+        SyntaxKind::ArgumentList => "arguments",
+        SyntaxKind::Comma => "','",
+        SyntaxKind::Assign => "'='",
+        SyntaxKind::RightBracket => "')'",
+        SyntaxKind::RightSquareBracket => "']'",
+        SyntaxKind::RightCurlyBracket => "'}'",
+        SyntaxKind::ThenKeyword => "'then'",
+        SyntaxKind::DoKeyword => "'do'",
+        SyntaxKind::InKeyword => "'in'",
+        SyntaxKind::EndKeyword => "'end'",
+        SyntaxKind::UntilKeyword => "'until'",
+        _ => "token",
+    }
+}
+
+impl Error {
+    /// A message for showing to people, `text` being what was parsed
+    pub fn message(&self, text: &str) -> String {
+        let found = text.get(self.start..self.end).unwrap_or("").trim();
+        let found = if found.is_empty() || found.contains('\n') || found.len() > 20 {
+            String::new()
+        } else {
+            format!(" near '{}'", found)
+        };
+        let expected = self.expected.iter().map(|k| describe(*k)).collect::<Vec<_>>().join(" or ");
+        let opened = match self.opener {
+            Some((start, end)) => {
+                let line = text[..start].matches('\n').count() + 1;
+                format!(" '{}' at line {}", &text[start..end], line)
+            }
+            None => String::new(),
+        };
+        match self.kind {
+            ErrorKind::NotClosedBlock => format!("{} expected to close{}", expected, opened),
+            ErrorKind::NotClosedComment => String::from("unfinished long comment"),
+            ErrorKind::NotTerminatedString => String::from("unfinished string"),
+            ErrorKind::InvalidNumberFormat => format!("malformed number{}", found),
+            ErrorKind::UnexpectedKeyword | ErrorKind::UnexpectedToken | ErrorKind::UnexpectedOperator => {
+                if found.is_empty() {
+                    String::from("unexpected symbol")
+                } else {
+                    format!("unexpected symbol{}", found)
+                }
+            }
+            ErrorKind::ExpectingThen if self.opener.is_some() => {
+                format!("{} expected after the condition of{}", expected, opened)
+            }
+            ErrorKind::ExpectingDo if self.opener.is_some() => {
+                format!("{} expected to start the body of{}", expected, opened)
+            }
+            ErrorKind::ExpectingClosingBracket if self.opener.is_some() => {
+                format!("{} expected to close{}", expected, opened)
+            }
+            ErrorKind::ExpectingFunctionCall => format!("function arguments expected{}", found),
+            ErrorKind::ExpectingOperator => format!("'=' or function arguments expected{}", found),
+            ErrorKind::InvalidName => format!("invalid name{}", found),
+            ErrorKind::InvalidFunction => String::from("function name or parameters expected"),
+            _ if expected.is_empty() => format!("syntax error{}", found),
+            _ => format!("{} expected{}", expected, found),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
@@ -158,7 +262,37 @@ pub enum ExpressionKind {
     None,
 }
 
-fn str_to_keyword(text: &str) -> SyntaxKind {
+const UNARY_PRIORITY: usize = 7;
+
+/// The binary operator a token is, with its priority and whether it groups
+/// from the right. Lua's order, loosest first, is `or`, `and`, comparisons,
+/// `..`, `+ -`, `* / %`, unary operators, then `^`
+fn binary_operator(token: &Token, text: &str) -> Option<(SyntaxKind, usize, bool)> {
+    let operator = match token.kind {
+        TokenKind::Identifier => match str_to_keyword(text) {
+            SyntaxKind::OrKeyword => (SyntaxKind::OrKeyword, 1, false),
+            SyntaxKind::AndKeyword => (SyntaxKind::AndKeyword, 2, false),
+            _ => return None,
+        },
+        TokenKind::LessThan => (SyntaxKind::LessThan, 3, false),
+        TokenKind::LessThanOrEquals => (SyntaxKind::LessThanOrEquals, 3, false),
+        TokenKind::GreaterThan => (SyntaxKind::GreaterThan, 3, false),
+        TokenKind::GreaterThanOrEquals => (SyntaxKind::GreaterThanOrEquals, 3, false),
+        TokenKind::EqualsBoolean => (SyntaxKind::EqualsBoolean, 3, false),
+        TokenKind::NotEqualsBoolean => (SyntaxKind::NotEqualsBoolean, 3, false),
+        TokenKind::DoubleDot => (SyntaxKind::DoubleDot, 4, true),
+        TokenKind::Plus => (SyntaxKind::Plus, 5, false),
+        TokenKind::Minus => (SyntaxKind::Minus, 5, false),
+        TokenKind::Asterisk => (SyntaxKind::Asterisk, 6, false),
+        TokenKind::Slash => (SyntaxKind::Slash, 6, false),
+        TokenKind::Modulo => (SyntaxKind::Modulo, 6, false),
+        TokenKind::Hat => (SyntaxKind::Hat, 8, true),
+        _ => return None,
+    };
+    Some(operator)
+}
+
+pub fn str_to_keyword(text: &str) -> SyntaxKind {
     match text {
         "and" => SyntaxKind::AndKeyword,
         "break" => SyntaxKind::BreakKeyword,
@@ -277,6 +411,69 @@ impl<'a> Generator<'a> {
         return end
     }
 
+    fn error(&mut self, start: usize, end: usize, kind: ErrorKind) {
+        self.push_error(Error { start, end, kind, expected: kind.expected(), opener: None });
+    }
+
+    /// Records an error for something `opener` started but never finished
+    fn unfinished(&mut self, start: usize, end: usize, kind: ErrorKind, expected: &'static [SyntaxKind], opener: &Token) {
+        let opener = Some((opener.start, opener.end));
+        self.push_error(Error { start, end, kind, expected, opener });
+    }
+
+    /// Records an error unless it falls inside the previous one, and joins it
+    /// onto the previous one if they're the same kind and touch, so a single
+    /// mistake doesn't get reported once per token
+    fn push_error(&mut self, error: Error) {
+        if let Some(last) = self.errors.last_mut() && last.opener == error.opener {
+            if error.start >= last.start && error.end <= last.end {
+                return
+            }
+            if std::mem::discriminant(&last.kind) == std::mem::discriminant(&error.kind) && error.start <= last.end {
+                last.end = last.end.max(error.end);
+                return
+            }
+        }
+        if self.errors.len() < MAX_ERRORS {
+            self.errors.push(error);
+        }
+    }
+
+    /// Adds a zero width node standing in for a token that should have been here
+    fn missing(&mut self, kind: SyntaxKind) {
+        self.builder.start_node(to_raw(SyntaxKind::Missing));
+        self.builder.token(to_raw(kind), "");
+        self.builder.finish_node();
+    }
+
+    fn peek_keyword(&mut self) -> Option<SyntaxKind> {
+        let t = self.peek_raw_token()?;
+        if t.kind != TokenKind::Identifier {
+            return None
+        }
+        Some(str_to_keyword(&self.text[t.start..t.end]))
+    }
+
+    /// Skips everything up to where a statement could start again, reporting
+    /// it all as one error
+    fn skip_invalid(&mut self, first: &Token) {
+        self.builder.token(to_raw(SyntaxKind::Invalid), &self.text[first.start..first.end]);
+        let mut end = first.end;
+        loop {
+            self.eat_whitespace();
+            let Some(t) = self.peek_raw_token() else {
+                break
+            };
+            if matches!(t.kind, TokenKind::Identifier | TokenKind::LeftBracket | TokenKind::Semicolon) {
+                break
+            }
+            self.next_raw_token();
+            self.builder.token(to_raw(SyntaxKind::Invalid), &self.text[t.start..t.end]);
+            end = t.end;
+        }
+        self.error(first.start, end, ErrorKind::UnexpectedToken);
+    }
+
     pub fn process_all(&mut self) -> rowan::GreenNode {
         if let Some(first) = self.peek_raw_token() {
             self.scan_block(None, &first);
@@ -297,7 +494,7 @@ impl<'a> Generator<'a> {
                 TokenKind::Comment { validity: v, modifier: _ } => {
                     let text = &self.text[token.start .. token.end];
                     if v == crate::syntax::lexer::token_validity::Comment::NotTerminated {
-                        self.errors.push(Error{ start: token.start, end: self.text.len(), kind: ErrorKind::NotClosedComment });
+                        self.error(token.start, self.text.len(), ErrorKind::NotClosedComment);
                     }
                     self.builder.token(to_raw(SyntaxKind::Comment), text)
                 },
@@ -323,7 +520,7 @@ impl<'a> Generator<'a> {
                     }
                     let keyword_kind = str_to_keyword(text);
                     if keyword_kind != SyntaxKind::Name {
-                        self.errors.push(Error { start: token.start, end: t.end, kind: ErrorKind::InvalidName });
+                        self.error(token.start, t.end, ErrorKind::InvalidName);
                         self.builder.token(to_raw(keyword_kind), text);
                     } else {
                         self.builder.token(to_raw(SyntaxKind::Name), text);
@@ -338,7 +535,7 @@ impl<'a> Generator<'a> {
                 }
                 TokenKind::Dot => {
                     if id_expected {
-                        self.errors.push(Error { start: token.start, end: t.end, kind: ErrorKind::InvalidName });
+                        self.error(token.start, t.end, ErrorKind::InvalidName);
                         break
                     }
                     self.builder.token(to_raw(SyntaxKind::Dot), text);
@@ -350,7 +547,7 @@ impl<'a> Generator<'a> {
                 }
                 TokenKind::Colon => {
                     if id_expected {
-                        self.errors.push(Error { start: token.start, end: t.end, kind: ErrorKind::InvalidName });
+                        self.error(token.start, t.end, ErrorKind::InvalidName);
                         break
                     }
                     terminate_next = true;
@@ -391,7 +588,7 @@ impl<'a> Generator<'a> {
             match t.kind {
                 TokenKind::Number{validity, modifier: _} => {
                     if validity == token_validity::Number::Invalid {
-                        self.errors.push(Error{ start: t.start, end: t.end, kind: ErrorKind::InvalidNumberFormat });
+                        self.error(t.start, t.end, ErrorKind::InvalidNumberFormat);
                     }
                     self.next_raw_token();
                     self.builder.start_node(to_raw(SyntaxKind::Literal));
@@ -401,7 +598,7 @@ impl<'a> Generator<'a> {
                 },
                 TokenKind::String{validity, modifier: _} => {
                     if validity == token_validity::String::NotTerminated {
-                        self.errors.push(Error{ start: t.start, end: t.end, kind: ErrorKind::NotTerminatedString });
+                        self.error(t.start, t.end, ErrorKind::NotTerminatedString);
                     }
                     self.next_raw_token();
                     self.builder.start_node(to_raw(SyntaxKind::Literal));
@@ -480,14 +677,21 @@ impl<'a> Generator<'a> {
                                             if let Some(t) = self.peek_raw_token() {
                                                 let text = &self.text[t.start..t.end];
                                                 if t.kind != TokenKind::Identifier || str_to_keyword(text) != SyntaxKind::Name {
-                                                    self.errors.push(Error{ start: t.start, end: t.end, kind: ErrorKind::ExpectingName });
+                                                    self.error(t.start, t.end, ErrorKind::ExpectingName);
                                                     break;
                                                 } else {
                                                     self.builder.token(to_raw(SyntaxKind::Name), text);
                                                     self.next_raw_token();
                                                     self.eat_whitespace();
-                                                    if !self.scan_arguments() {
-                                                        self.errors.push(Error { start, end: t.end, kind: ErrorKind::ExpectingFunctionCall });
+                                                    if started_group {
+                                                        self.builder.finish_node();
+                                                        started_group = false;
+                                                    }
+                                                    self.builder.start_node_at(checkpoint, to_raw(SyntaxKind::FunctionCall));
+                                                    let scanned = self.scan_arguments();
+                                                    self.builder.finish_node();
+                                                    if !scanned {
+                                                        self.error(start, t.end, ErrorKind::ExpectingFunctionCall);
                                                         break
                                                     }
                                                 }
@@ -527,7 +731,9 @@ impl<'a> Generator<'a> {
                         },
                         SyntaxKind::NilKeyword | SyntaxKind::FalseKeyword | SyntaxKind::TrueKeyword => {
                             self.next_raw_token();
+                            self.builder.start_node(to_raw(SyntaxKind::Literal));
                             self.builder.token(to_raw(keyword_kind), text);
+                            self.builder.finish_node();
                             return ExpressionKind::Literal
                         }
                         SyntaxKind::FunctionKeyword => {
@@ -549,6 +755,7 @@ impl<'a> Generator<'a> {
                     }
                 },
                 TokenKind::LeftCurlyBracket => {
+                    let opener = t;
                     self.next_raw_token();
                     self.builder.start_node(to_raw(SyntaxKind::TableConstructor));
                     self.builder.token(to_raw(SyntaxKind::LeftCurlyBracket), &self.text[t.start..t.end]);
@@ -557,7 +764,8 @@ impl<'a> Generator<'a> {
                     self.eat_whitespace();
                     if let Some(t) = self.peek_raw_token() {
                         if t.kind != TokenKind::RightCurlyBracket {
-                            self.errors.push(Error{ start: t.start, end: t.end, kind: ErrorKind::ExpectingClosingBracket });
+                            self.unfinished(t.start, t.end, ErrorKind::ExpectingClosingBracket, &[SyntaxKind::RightCurlyBracket], &opener);
+                            self.missing(SyntaxKind::RightCurlyBracket);
                             self.builder.finish_node();
                             return ExpressionKind::None
                         } else {
@@ -581,263 +789,84 @@ impl<'a> Generator<'a> {
     }
 
     fn scan_expression(&mut self) -> ExpressionKind {
-        let mut group_kind = ExpressionKind::None;
-        let mut binary_possible = false;
-        let mut expecting_expression = true;
-        let mut checkpoints = [self.builder.checkpoint(); 10];
-        let mut is_open = [false; 8];
-        const OR_PRIORITY: usize = 1;
-        const AND_PRIORITY: usize = 2;
-        const COMPARISON_PRIORITY: usize = 3;
-        const CONCAT_PRIORITY: usize = 4;
-        const PLUS_PRIORITY: usize = 5;
-        const MULT_PRIORITY: usize = 6;
-        const UNARY_PRIORITY: usize = 7;
-        const HAT_PRIORITY: usize = 8;
-        const COLON_PRIORITY: usize = 9;
-        fn close_nodes(priority: usize, is_open: &mut [bool; 8], builder: &mut GreenNodeBuilder) {
-            for j in (priority)..8 {
-                if is_open[j] {
-                    builder.finish_node();
-                    is_open[j] = false;
-                }
-            }
-        }
-        fn update_checkpoints(priority: usize, checkpoints: &mut [rowan::Checkpoint; 10], point: rowan::Checkpoint) {
-            for j in (priority)..9 {
-                checkpoints[j] = point;
-            }
-        }
-        #[inline(always)]
-        fn apply_operator(token_kind: SyntaxKind, operator_kind: SyntaxKind, priority: usize, text: &str, builder: &mut GreenNodeBuilder, checkpoints: &mut [rowan::Checkpoint; 10], is_open: &mut [bool; 8]) {
-            close_nodes(priority - 1, is_open, builder);
-            checkpoints[priority] = builder.checkpoint();
-            builder.start_node_at(checkpoints[priority - 1], to_raw(operator_kind));
-            is_open[priority - 1] = true;
-            builder.token(to_raw(token_kind), text);
-            update_checkpoints(min(priority + 1, 8), checkpoints, builder.checkpoint());
+        self.scan_binary_expression(0)
+    }
+
+    /// An operand followed by any operators binding tighter than `priority`,
+    /// so `a + b * c` groups as `a + (b * c)` and `a ^ b ^ c` as `a ^ (b ^ c)`
+    fn scan_binary_expression(&mut self, priority: usize) -> ExpressionKind {
+        let checkpoint = self.builder.checkpoint();
+        let mut kind = self.scan_operand();
+        if kind == ExpressionKind::None {
+            return kind
         }
         while let Some(t) = self.peek_raw_token() {
             let text = &self.text[t.start..t.end];
-            if expecting_expression && !binary_possible {
-                let checkpoint = self.builder.checkpoint();
-                checkpoints[9] = checkpoint;
-                let part_kind = self.scan_expression_part();
-                self.eat_whitespace();
-                if group_kind == ExpressionKind::None {
-                    group_kind = part_kind;
-                }
-                if part_kind != ExpressionKind::None {
-                    if part_kind != ExpressionKind::Name && part_kind != ExpressionKind::Literal {
-                        self.builder.start_node_at(checkpoint, to_raw(SyntaxKind::Expression));
-                        self.builder.finish_node();
-                    }
-                    binary_possible = true;
-                    expecting_expression = false;
-                    continue;
-                }
+            let Some((operator, operator_priority, right_associative)) = binary_operator(&t, text) else {
+                break
+            };
+            if operator_priority <= priority {
+                break
             }
-            match t.kind {
-                TokenKind::Identifier => {
-                    let keyword_kind = str_to_keyword(text);
-                    match keyword_kind {
-                        SyntaxKind::NotKeyword => {
-                            self.next_raw_token();
-                            checkpoints[UNARY_PRIORITY] = self.builder.checkpoint();
-                            expecting_expression = true;
-                            apply_operator(SyntaxKind::NotKeyword, SyntaxKind::UnaryExpression, UNARY_PRIORITY, text, &mut self.builder, &mut checkpoints, &mut is_open);
-                        }
-                        SyntaxKind::AndKeyword => {
-                            group_kind = ExpressionKind::Combined;
-                            self.next_raw_token();
-                            if binary_possible {
-                                apply_operator(SyntaxKind::AndKeyword, SyntaxKind::BinaryExpression, AND_PRIORITY, text, &mut self.builder, &mut checkpoints, &mut is_open);
-                                expecting_expression = true;
-                                binary_possible = false;
-                            } else {
-                                self.errors.push(Error{ start: t.start, end: t.end, kind: ErrorKind::UnexpectedOperator });
-                            }
-                        }
-                        SyntaxKind::OrKeyword => {
-                            group_kind = ExpressionKind::Combined;
-                            self.next_raw_token();
-                            if binary_possible {
-                                apply_operator(SyntaxKind::OrKeyword, SyntaxKind::BinaryExpression, OR_PRIORITY, text, &mut self.builder, &mut checkpoints, &mut is_open);
-                                expecting_expression = true;
-                                binary_possible = false;
-                            } else {
-                                self.errors.push(Error{ start: t.start, end: t.end, kind: ErrorKind::UnexpectedOperator });
-                            }
-                        }
-                        _ => break,
-                    }
-                }
-                TokenKind::Minus => {
-                    group_kind = ExpressionKind::Combined;
-                    self.next_raw_token();
-                    if binary_possible {
-                        apply_operator(SyntaxKind::Minus, SyntaxKind::BinaryExpression, PLUS_PRIORITY, text, &mut self.builder, &mut checkpoints, &mut is_open);
-                        expecting_expression = true;
-                        binary_possible = false;
-                    } else {
-                        checkpoints[UNARY_PRIORITY] = self.builder.checkpoint();
-                        expecting_expression = true;
-                        apply_operator(SyntaxKind::Minus, SyntaxKind::UnaryExpression, UNARY_PRIORITY, text, &mut self.builder, &mut checkpoints, &mut is_open);
-                    }
-                }
-                TokenKind::Plus => {
-                    group_kind = ExpressionKind::Combined;
-                    self.next_raw_token();
-                    if binary_possible {
-                        apply_operator(SyntaxKind::Plus, SyntaxKind::BinaryExpression, PLUS_PRIORITY, text, &mut self.builder, &mut checkpoints, &mut is_open);
-                        expecting_expression = true;
-                        binary_possible = false;
-                    } else {
-                        self.errors.push(Error{ start: t.start, end: t.end, kind: ErrorKind::UnexpectedOperator });
-                    }
-                }
-                TokenKind::Asterisk => {
-                    group_kind = ExpressionKind::Combined;
-                    self.next_raw_token();
-                    if binary_possible {
-                        apply_operator(SyntaxKind::Asterisk, SyntaxKind::BinaryExpression, MULT_PRIORITY, text, &mut self.builder, &mut checkpoints, &mut is_open);
-                        expecting_expression = true;
-                        binary_possible = false;
-                    } else {
-                        self.errors.push(Error{ start: t.start, end: t.end, kind: ErrorKind::UnexpectedOperator });
-                    }
-                }
-                TokenKind::Slash => {
-                    group_kind = ExpressionKind::Combined;
-                    self.next_raw_token();
-                    if binary_possible {
-                        apply_operator(SyntaxKind::Slash, SyntaxKind::BinaryExpression, MULT_PRIORITY, text, &mut self.builder, &mut checkpoints, &mut is_open);
-                        expecting_expression = true;
-                        binary_possible = false;
-                    } else {
-                        self.errors.push(Error{ start: t.start, end: t.end, kind: ErrorKind::UnexpectedOperator });
-                    }
-                }
-                TokenKind::Modulo => {
-                    group_kind = ExpressionKind::Combined;
-                    self.next_raw_token();
-                    if binary_possible {
-                        apply_operator(SyntaxKind::Modulo, SyntaxKind::BinaryExpression, MULT_PRIORITY, text, &mut self.builder, &mut checkpoints, &mut is_open);
-                        expecting_expression = true;
-                        binary_possible = false;
-                    } else {
-                        self.errors.push(Error{ start: t.start, end: t.end, kind: ErrorKind::UnexpectedOperator });
-                    }
-                }
-                TokenKind::Hash => {
-                    if binary_possible {
-                        break;
-                    }
-                    self.next_raw_token();
-                    group_kind = ExpressionKind::Combined;
-                    checkpoints[UNARY_PRIORITY] = self.builder.checkpoint();
-                    expecting_expression = true;
-                    apply_operator(SyntaxKind::Hash, SyntaxKind::UnaryExpression, UNARY_PRIORITY, text, &mut self.builder, &mut checkpoints, &mut is_open);
-                }
-                TokenKind::Hat => {
-                    group_kind = ExpressionKind::Combined;
-                    self.next_raw_token();
-                    if binary_possible {
-                        apply_operator(SyntaxKind::Hat, SyntaxKind::BinaryExpression, HAT_PRIORITY, text, &mut self.builder, &mut checkpoints, &mut is_open);
-                        expecting_expression = true;
-                        binary_possible = false;
-                    } else {
-                        self.errors.push(Error{ start: t.start, end: t.end, kind: ErrorKind::UnexpectedOperator });
-                    }
-                }
-                TokenKind::DoubleDot => {
-                    group_kind = ExpressionKind::Combined;
-                    self.next_raw_token();
-                    if binary_possible {
-                        apply_operator(SyntaxKind::DoubleDot, SyntaxKind::BinaryExpression, CONCAT_PRIORITY, text, &mut self.builder, &mut checkpoints, &mut is_open);
-                        expecting_expression = true;
-                        binary_possible = false;
-                    } else {
-                        self.errors.push(Error{ start: t.start, end: t.end, kind: ErrorKind::UnexpectedOperator });
-                    }
-                }
-                TokenKind::LessThan => {
-                    group_kind = ExpressionKind::Combined;
-                    self.next_raw_token();
-                    if binary_possible {
-                        apply_operator(SyntaxKind::LessThan, SyntaxKind::BinaryExpression, COMPARISON_PRIORITY, text, &mut self.builder, &mut checkpoints, &mut is_open);
-                        expecting_expression = true;
-                        binary_possible = false;
-                    } else {
-                        self.errors.push(Error{ start: t.start, end: t.end, kind: ErrorKind::UnexpectedOperator });
-                    }
-                }
-                TokenKind::LessThanOrEquals => {
-                    group_kind = ExpressionKind::Combined;
-                    self.next_raw_token();
-                    if binary_possible {
-                        apply_operator(SyntaxKind::LessThanOrEquals, SyntaxKind::BinaryExpression, COMPARISON_PRIORITY, text, &mut self.builder, &mut checkpoints, &mut is_open);
-                        expecting_expression = true;
-                        binary_possible = false;
-                    } else {
-                        self.errors.push(Error{ start: t.start, end: t.end, kind: ErrorKind::UnexpectedOperator });
-                    }
-                }
-                TokenKind::GreaterThan => {
-                    group_kind = ExpressionKind::Combined;
-                    self.next_raw_token();
-                    if binary_possible {
-                        apply_operator(SyntaxKind::GreaterThan, SyntaxKind::BinaryExpression, COMPARISON_PRIORITY, text, &mut self.builder, &mut checkpoints, &mut is_open);
-                        expecting_expression = true;
-                        binary_possible = false;
-                    } else {
-                        self.errors.push(Error{ start: t.start, end: t.end, kind: ErrorKind::UnexpectedOperator });
-                    }
-                }
-                TokenKind::GreaterThanOrEquals => {
-                    group_kind = ExpressionKind::Combined;
-                    self.next_raw_token();
-                    if binary_possible {
-                        apply_operator(SyntaxKind::GreaterThanOrEquals, SyntaxKind::BinaryExpression, COMPARISON_PRIORITY, text, &mut self.builder, &mut checkpoints, &mut is_open);
-                        expecting_expression = true;
-                        binary_possible = false;
-                    } else {
-                        self.errors.push(Error{ start: t.start, end: t.end, kind: ErrorKind::UnexpectedOperator });
-                    }
-                }
-                TokenKind::EqualsBoolean => {
-                    group_kind = ExpressionKind::Combined;
-                    self.next_raw_token();
-                    if binary_possible {
-                        apply_operator(SyntaxKind::EqualsBoolean, SyntaxKind::BinaryExpression, COMPARISON_PRIORITY, text, &mut self.builder, &mut checkpoints, &mut is_open);
-                        expecting_expression = true;
-                        binary_possible = false;
-                    } else {
-                        self.errors.push(Error{ start: t.start, end: t.end, kind: ErrorKind::UnexpectedOperator });
-                    }
-                }
-                TokenKind::NotEqualsBoolean => {
-                    group_kind = ExpressionKind::Combined;
-                    self.next_raw_token();
-                    if binary_possible {
-                        apply_operator(SyntaxKind::LessThan, SyntaxKind::BinaryExpression, COMPARISON_PRIORITY, text, &mut self.builder, &mut checkpoints, &mut is_open);
-                        expecting_expression = true;
-                        binary_possible = false;
-                    } else {
-                        self.errors.push(Error{ start: t.start, end: t.end, kind: ErrorKind::UnexpectedOperator });
-                    }
-                }
-                _ => {
-                    break
-                }
+            self.next_raw_token();
+            self.builder.start_node_at(checkpoint, to_raw(SyntaxKind::BinaryExpression));
+            self.builder.token(to_raw(operator), text);
+            self.eat_whitespace();
+            let right_priority = if right_associative { operator_priority - 1 } else { operator_priority };
+            let right = self.scan_binary_expression(right_priority);
+            self.builder.finish_node();
+            if right == ExpressionKind::None {
+                return ExpressionKind::None
             }
+            kind = ExpressionKind::Combined;
+        }
+        kind
+    }
+
+    /// A single operand with any unary operators before it. Those bind less
+    /// tightly than `^`, so `-2^2` is `-(2^2)`
+    fn scan_operand(&mut self) -> ExpressionKind {
+        let Some(t) = self.peek_raw_token() else {
+            return ExpressionKind::None
+        };
+        let text = &self.text[t.start..t.end];
+        let unary = match t.kind {
+            TokenKind::Minus => Some(SyntaxKind::Minus),
+            TokenKind::Hash => Some(SyntaxKind::Hash),
+            TokenKind::Identifier if str_to_keyword(text) == SyntaxKind::NotKeyword => Some(SyntaxKind::NotKeyword),
+            _ => None,
+        };
+        if let Some(operator) = unary {
+            self.next_raw_token();
+            self.builder.start_node(to_raw(SyntaxKind::UnaryExpression));
+            self.builder.token(to_raw(operator), text);
             self.eat_whitespace();
+            let operand = self.scan_binary_expression(UNARY_PRIORITY);
+            self.builder.finish_node();
+            return match operand {
+                ExpressionKind::None => ExpressionKind::None,
+                _ => ExpressionKind::Combined,
+            }
+        }
+        let checkpoint = self.builder.checkpoint();
+        let kind = self.scan_expression_part();
+        if kind == ExpressionKind::None {
+            // An operator where an operand should be, report it and carry on after it
+            if let Some((operator, _, _)) = binary_operator(&t, text) {
+                self.next_raw_token();
+                self.error(t.start, t.end, ErrorKind::UnexpectedOperator);
+                self.builder.token(to_raw(operator), text);
+                self.eat_whitespace();
+                return self.scan_operand()
+            }
+            return kind
         }
-        if expecting_expression || !binary_possible {
-            group_kind = ExpressionKind::None;
+        self.eat_whitespace();
+        if kind != ExpressionKind::Name && kind != ExpressionKind::Literal {
+            self.builder.start_node_at(checkpoint, to_raw(SyntaxKind::Expression));
+            self.builder.finish_node();
         }
-        close_nodes(0, &mut is_open, &mut self.builder);
-        return group_kind
+        kind
     }
 
     fn scan_expression_list(&mut self) -> bool {
@@ -856,7 +885,7 @@ impl<'a> Generator<'a> {
                         self.next_raw_token();
                         self.builder.token(to_raw(SyntaxKind::Comma), &self.text[t.start..t.end]);
                         if !seen_expression {
-                            self.errors.push(Error{ start: t.start, end: t.end, kind: ErrorKind::UnexpectedOperator })
+                            self.error(t.start, t.end, ErrorKind::UnexpectedOperator)
                         }
                         seen_expression = false;
                     },
@@ -883,7 +912,8 @@ impl<'a> Generator<'a> {
                     self.next_raw_token();
                     self.builder.token(to_raw(SyntaxKind::RightBracket), &self.text[t.start..t.end]);
                 } else {
-                    self.errors.push(Error { start: t.start, end: t.end, kind: ErrorKind::ExpectingClosingBracket });
+                    self.unfinished(t.start, t.end, ErrorKind::ExpectingClosingBracket, &[SyntaxKind::RightBracket], token);
+                    self.missing(SyntaxKind::RightBracket);
                     res = false;
                 }
             }
@@ -903,7 +933,7 @@ impl<'a> Generator<'a> {
             self.builder.finish_node();
             if !scanned {
                 let end = self.get_current_position();
-                self.errors.push(Error{ start: token.start, end, kind: ErrorKind::ExpectingFunctionCall });
+                self.error(token.start, end, ErrorKind::ExpectingFunctionCall);
             }
             return ExpressionKind::FunctionCall
         } else if kind != ExpressionKind::None {
@@ -914,7 +944,7 @@ impl<'a> Generator<'a> {
                         let scanned = self.scan_arguments();
                         self.builder.finish_node();
                         if !scanned {
-                            self.errors.push(Error{ start: token.start, end: t.end, kind: ErrorKind::ExpectingFunctionCall });
+                            self.error(token.start, t.end, ErrorKind::ExpectingFunctionCall);
                         }
                         return ExpressionKind::FunctionCall
                     }
@@ -929,7 +959,7 @@ impl<'a> Generator<'a> {
             }
             return kind
         } else {
-            self.errors.push(Error{ start: token.start, end: token.end, kind: ErrorKind::ExpectingName });
+            self.error(token.start, token.end, ErrorKind::ExpectingName);
             return ExpressionKind::None
         }
     }
@@ -943,7 +973,9 @@ impl<'a> Generator<'a> {
                 self.builder.finish_node();
             },
             SyntaxKind::BreakKeyword => {
+                self.builder.start_node(to_raw(SyntaxKind::BreakStatement));
                 self.builder.token(to_raw(SyntaxKind::BreakKeyword), text);
+                self.builder.finish_node();
             },
             SyntaxKind::FunctionKeyword => {
                 let keyword_token = token;
@@ -966,7 +998,7 @@ impl<'a> Generator<'a> {
                             }
                         }
                         _ => {
-                            self.errors.push(Error { start: keyword_token.start, end: token.end, kind: ErrorKind::InvalidFunction});
+                            self.error(keyword_token.start, token.end, ErrorKind::InvalidFunction);
                         }
                     }
                 }
@@ -980,37 +1012,29 @@ impl<'a> Generator<'a> {
                 self.builder.token(to_raw(SyntaxKind::WhileKeyword), text);
                 self.eat_whitespace();
                 self.builder.start_node(to_raw(SyntaxKind::Condition));
-                let is_expression = self.scan_expression() != ExpressionKind::None;
-                self.builder.finish_node();
-                if is_expression {
-                    self.eat_whitespace();
-                    if let Some(t) = self.peek_raw_token() {
-                        if t.kind == TokenKind::Identifier {
-                            let text = &self.text[t.start..t.end];
-                            let keyword_kind = str_to_keyword(text);
-                            if keyword_kind == SyntaxKind::DoKeyword {
-                                self.next_raw_token();
-                                self.builder.token(to_raw(SyntaxKind::DoKeyword), &self.text[t.start..t.end]);
-                                self.scan_block(Some(SyntaxKind::EndKeyword), &token);
-                            }
-                        }
-                    }
-                } else {
-
+                if self.scan_expression() == ExpressionKind::None {
+                    let end = self.get_current_position();
+                    self.error(token.end, end, ErrorKind::ExpectingExpression);
                 }
                 self.builder.finish_node();
+                self.eat_whitespace();
+                self.scan_do(token);
+                self.scan_block(Some(SyntaxKind::EndKeyword), &token);
+                self.builder.finish_node();
             }
             SyntaxKind::RepeatKeyword => {
                 self.builder.start_node(to_raw(SyntaxKind::RepeatUntilLoop));
                 self.builder.token(to_raw(SyntaxKind::RepeatKeyword), text);
                 self.eat_whitespace();
-                self.scan_block(Some(SyntaxKind::UntilKeyword), &token);
+                let terminated = self.scan_block(Some(SyntaxKind::UntilKeyword), &token);
                 self.eat_whitespace();
-                if self.scan_expression() == ExpressionKind::None {
+                self.builder.start_node(to_raw(SyntaxKind::Condition));
+                if terminated && self.scan_expression() == ExpressionKind::None {
                     let end = self.get_current_position();
-                    self.errors.push(Error {start: token.start, end, kind: ErrorKind::ExpectingExpression });
+                    self.error(token.start, end, ErrorKind::ExpectingExpression);
                 }
                 self.builder.finish_node();
+                self.builder.finish_node();
             }
             SyntaxKind::ForKeyword => {
                 let checkpoint = self.builder.checkpoint();
@@ -1019,16 +1043,17 @@ impl<'a> Generator<'a> {
                 if let Some(start_token) = self.peek_raw_token() {
                     let text = &self.text[start_token.start..start_token.end];
                     if start_token.kind != TokenKind::Identifier {
-                        self.errors.push(Error { start: start_token.start, end: start_token.end, kind: ErrorKind::ExpectingName });
+                        self.error(start_token.start, start_token.end, ErrorKind::ExpectingName);
                     } else if str_to_keyword(text) != SyntaxKind::Name {
-                        self.errors.push(Error { start: start_token.start, end: start_token.end, kind: ErrorKind::UnexpectedKeyword });
+                        self.error(start_token.start, start_token.end, ErrorKind::UnexpectedKeyword);
                     } else {
                         self.next_raw_token();
+                        let name_checkpoint = self.builder.checkpoint();
+                        self.builder.token(to_raw(SyntaxKind::Name), text);
                         self.eat_whitespace();
                         if let Some(t) = self.peek_raw_token() {
                             if t.kind == TokenKind::Assign {
                                 self.builder.start_node_at(checkpoint, to_raw(SyntaxKind::ForCountLoop));
-                                self.builder.token(to_raw(SyntaxKind::Name), text);
                                 let text  =&self.text[t.start..t.end];
                                 self.builder.token(to_raw(SyntaxKind::Assign), text);
                                 self.next_raw_token();
@@ -1038,12 +1063,13 @@ impl<'a> Generator<'a> {
                                 self.builder.finish_node();
                             } else {
                                 self.builder.start_node_at(checkpoint, to_raw(SyntaxKind::ForInLoop));
-                                self.scan_name_list(&start_token, text);
+                                self.builder.start_node_at(name_checkpoint, to_raw(SyntaxKind::NameList));
+                                self.scan_name_list_rest();
                                 self.eat_whitespace();
                                 if let Some(t) = self.peek_raw_token() {
                                     let text  =&self.text[t.start..t.end];
                                     if t.kind != TokenKind::Identifier || str_to_keyword(text) != SyntaxKind::InKeyword {
-                                        self.errors.push(Error { start: start_token.start, end: start_token.end, kind: ErrorKind::ExpectingToken });
+                                        self.unfinished(t.start, t.end, ErrorKind::ExpectingToken, &[SyntaxKind::InKeyword], &token);
                                     } else {
                                         self.builder.token(to_raw(SyntaxKind::InKeyword), text);
                                         self.next_raw_token();
@@ -1054,17 +1080,9 @@ impl<'a> Generator<'a> {
                                 }
                             }
                             self.eat_whitespace();
-                            if let Some(t) = self.peek_raw_token() {
-                                let text  =&self.text[t.start..t.end];
-                                if t.kind != TokenKind::Identifier || str_to_keyword(text) != SyntaxKind::DoKeyword {
-                                    self.errors.push(Error { start: start_token.start, end: start_token.end, kind: ErrorKind::ExpectingDo });
-                                } else {
-                                    self.next_raw_token();
-                                    self.builder.token(to_raw(SyntaxKind::DoKeyword), text);
-                                    self.eat_whitespace();
-                                    self.scan_block(Some(SyntaxKind::EndKeyword), &token);
-                                }
-                            }
+                            self.scan_do(token);
+                            self.eat_whitespace();
+                            self.scan_block(Some(SyntaxKind::EndKeyword), &token);
                             self.builder.finish_node();
                         }
                     }
@@ -1072,17 +1090,17 @@ impl<'a> Generator<'a> {
             }
             SyntaxKind::LocalKeyword => {
                 let checkpoint = self.builder.checkpoint();
-                let keyword_token = token;
                 self.builder.token(to_raw(SyntaxKind::LocalKeyword), text);
                 self.eat_whitespace();
                 if let Some(t) = self.next_raw_token() {
                     let text = &self.text[t.start..t.end];
                     if t.kind != TokenKind::Identifier {
-                        self.errors.push(Error { start: token.start, end: t.end, kind: ErrorKind::UnexpectedOperator });
+                        self.error(token.start, t.end, ErrorKind::UnexpectedOperator);
                         return;
                     } else {
                         let keyword = str_to_keyword(text);
                         if keyword == SyntaxKind::FunctionKeyword {
+                            let function_token = t;
                             self.builder.start_node_at(checkpoint, to_raw(SyntaxKind::FunctionDefinition));
                             self.builder.token(to_raw(SyntaxKind::FunctionKeyword), text);
                             self.eat_whitespace();
@@ -1097,28 +1115,28 @@ impl<'a> Generator<'a> {
                                         self.builder.token(to_raw(SyntaxKind::Name), text);
                                     }
                                     if self.scan_parameters() {
-                                        self.scan_block(Some(SyntaxKind::EndKeyword), &keyword_token);
+                                        self.scan_block(Some(SyntaxKind::EndKeyword), &function_token);
                                     }
                                 }
                             }
                             self.builder.finish_node();
                         } else if keyword != SyntaxKind::Name {
                             self.builder.token(to_raw(keyword), text);
-                            self.errors.push(Error { start: token.start, end: t.end, kind: ErrorKind::UnexpectedKeyword });
+                            self.error(token.start, t.end, ErrorKind::UnexpectedKeyword);
                         } else {
                             self.scan_name_list(&t, &text);
                             self.eat_whitespace();
+                            self.builder.start_node_at(checkpoint, to_raw(SyntaxKind::LocalAssignStatement));
                             if let Some(t) = self.peek_raw_token() {
                                 if t.kind == TokenKind::Assign {
-                                    self.builder.start_node_at(checkpoint, to_raw(SyntaxKind::LocalAssignStatement));
                                     self.builder.token(to_raw(SyntaxKind::Assign), &self.text[t.start..t.end]);
                                     self.next_raw_token();
                                     self.builder.start_node(to_raw(SyntaxKind::ExpressionList));
                                     self.scan_expression_list();
                                     self.builder.finish_node();
-                                    self.builder.finish_node();
                                 }
                             }
+                            self.builder.finish_node();
                         }
                     }
                 }
@@ -1129,7 +1147,9 @@ impl<'a> Generator<'a> {
                 self.eat_whitespace();
                 if let Some(t) = self.peek_raw_token() {
                     if t.kind != TokenKind::Identifier || str_to_keyword(&self.text[t.start..t.end]) != SyntaxKind::EndKeyword {
+                        self.builder.start_node(to_raw(SyntaxKind::ExpressionList));
                         self.scan_expression_list();
+                        self.builder.finish_node();
                     }
                 }
                 self.builder.finish_node();
@@ -1139,7 +1159,7 @@ impl<'a> Generator<'a> {
             }
             _ => {
                 self.builder.token(to_raw(SyntaxKind::Invalid), text);
-                self.errors.push(Error { start: token.start, end: token.end, kind: ErrorKind::UnexpectedKeyword});
+                self.error(token.start, token.end, ErrorKind::UnexpectedKeyword);
             },
         }
     }
@@ -1191,17 +1211,25 @@ impl<'a> Generator<'a> {
                         if let Some(t) = self.peek_raw_token() {
                             let text = &self.text[t.start..t.end];
                             if t.kind != TokenKind::Identifier {
-                                self.errors.push(Error { start, end: t.end, kind: ErrorKind::ExpectingFunctionCall });
+                                self.error(start, t.end, ErrorKind::ExpectingFunctionCall);
                                 break
                             } else if str_to_keyword(text) != SyntaxKind::Name {
-                                self.errors.push(Error { start, end: t.end, kind: ErrorKind::ExpectingFunctionCall });
+                                self.error(start, t.end, ErrorKind::ExpectingFunctionCall);
                             }
                             self.builder.token(to_raw(SyntaxKind::Name), text);
                             self.next_raw_token();
-                            if !self.scan_arguments() {
-                                self.errors.push(Error { start, end: t.end, kind: ErrorKind::ExpectingFunctionCall });
+                            if started_group {
+                                self.builder.finish_node();
+                                started_group = false;
+                            }
+                            self.builder.start_node_at(checkpoint, to_raw(SyntaxKind::FunctionCall));
+                            let scanned = self.scan_arguments();
+                            self.builder.finish_node();
+                            if !scanned {
+                                self.error(start, t.end, ErrorKind::ExpectingFunctionCall);
                                 break
                             }
+                            kind = ExpressionKind::FunctionCall;
                         }
                     }
                     TokenKind::Comma => {
@@ -1214,7 +1242,7 @@ impl<'a> Generator<'a> {
                         self.builder.token(to_raw(SyntaxKind::Comma), &self.text[t.start..t.end]);
                         self.eat_whitespace();
                         if kind != ExpressionKind::Name && kind != ExpressionKind::Identifier {
-                            self.errors.push(Error { start, end: t.end, kind: ErrorKind::ExpectingName });
+                            self.error(start, t.end, ErrorKind::ExpectingName);
                             break
                         }
                         expecting_name = true;
@@ -1224,7 +1252,7 @@ impl<'a> Generator<'a> {
                             self.next_raw_token();
                             kind = self.scan_preexp(&t, text);
                         } else {
-                            self.errors.push(Error { start, end: t.end, kind: ErrorKind::ExpectingName });
+                            self.error(start, t.end, ErrorKind::ExpectingName);
                             break
                         }
                     }
@@ -1240,7 +1268,7 @@ impl<'a> Generator<'a> {
                         let scanned = self.scan_arguments();
                         self.builder.finish_node();
                         if !scanned {
-                            self.errors.push(Error { start, end: t.end, kind: ErrorKind::ExpectingFunctionCall });
+                            self.error(start, t.end, ErrorKind::ExpectingFunctionCall);
                             break;
                         }
                         kind = ExpressionKind::FunctionCall;
@@ -1257,7 +1285,7 @@ impl<'a> Generator<'a> {
         }
         if expecting_name && (kind != ExpressionKind::Name && kind != ExpressionKind::Identifier) || needs_indexing {
             let end = self.get_current_position();
-            self.errors.push(Error { start, end, kind: ErrorKind::ExpectingName });
+            self.error(start, end, ErrorKind::ExpectingName);
         }
         if kind == ExpressionKind::Name || kind == ExpressionKind::Identifier {
             self.builder.start_node_at(origin, to_raw(SyntaxKind::VariableList));
@@ -1270,16 +1298,16 @@ impl<'a> Generator<'a> {
                     self.next_raw_token();
                     self.builder.start_node(to_raw(SyntaxKind::ExpressionList));
                     if !self.scan_expression_list() {
-                        self.errors.push(Error { start: t.end, end: t.end + 1, kind: ErrorKind::ExpectingExpression });
+                        self.error(t.end, t.end + 1, ErrorKind::ExpectingExpression);
                     }
                     self.builder.finish_node();
                     self.builder.finish_node();
                 } else {
-                    self.errors.push(Error { start: t.start, end: t.end, kind: ErrorKind::ExpectingOperator });
+                    self.error(t.start, t.end, ErrorKind::ExpectingOperator);
                 }
             } else {
                 let end = self.get_current_position();
-                self.errors.push(Error { start: end, end: end, kind: ErrorKind::ExpectingOperator });
+                self.error(end, end, ErrorKind::ExpectingOperator);
             }
         }
 
@@ -1303,7 +1331,8 @@ impl<'a> Generator<'a> {
         }
     }
 
-    fn scan_block(&mut self, terminator: Option<SyntaxKind>, start_token: &Token) {
+    /// Scans statements up to the terminator, returns whether it was found
+    fn scan_block(&mut self, terminator: Option<SyntaxKind>, start_token: &Token) -> bool {
         self.builder.start_node(to_raw(SyntaxKind::Block));
 
         self.eat_whitespace();
@@ -1313,10 +1342,12 @@ impl<'a> Generator<'a> {
             t = token;
         } else {
             self.builder.finish_node();
-            if let Some(_) = terminator {
-                self.errors.push(Error{ start: start_token.start, end: start_token.end, kind: ErrorKind::NotClosedBlock });
+            if let Some(kind) = terminator {
+                let end = self.text.len();
+                self.unfinished(end, end, ErrorKind::NotClosedBlock, terminator_of(kind), start_token);
+                self.missing(kind);
             }
-            return
+            return false
         }
         let mut terminated = false;
         loop {
@@ -1331,20 +1362,20 @@ impl<'a> Generator<'a> {
                     self.scan_statement(&t, text);
                 },
                 TokenKind::Semicolon => {
-                    self.builder.token(to_raw(SyntaxKind::Semicolon), text)
-                }
-                _ => {
-                    self.builder.token(to_raw(SyntaxKind::Invalid), text);
-                    self.errors.push(Error { start: t.start, end: t.end, kind: ErrorKind::UnexpectedToken});
+                    self.builder.start_node(to_raw(SyntaxKind::EmptyStatement));
+                    self.builder.token(to_raw(SyntaxKind::Semicolon), text);
+                    self.builder.finish_node();
                 }
+                _ => self.skip_invalid(&t),
             }
             self.eat_whitespace();
 
             if let Some(token) = self.next_raw_token() {
                 t = token;
             } else {
-                if let Some(_) = terminator {
-                    self.errors.push(Error{ start: start_token.start, end: start_token.end, kind: ErrorKind::NotClosedBlock });
+                if let Some(kind) = terminator {
+                    let end = self.text.len();
+                    self.unfinished(end, end, ErrorKind::NotClosedBlock, terminator_of(kind), start_token);
                     break
                 }
                 break
@@ -1356,7 +1387,10 @@ impl<'a> Generator<'a> {
         if terminated {
             let text = &self.text[t.start .. t.end];
             self.builder.token(to_raw(str_to_keyword(text)), text);
+        } else if let Some(kind) = terminator {
+            self.missing(kind);
         }
+        terminated
     }
 
     fn scan_if_block(&mut self, start_token: &Token) {
@@ -1371,43 +1405,29 @@ impl<'a> Generator<'a> {
         self.builder.start_node(to_raw(SyntaxKind::Condition));
         if self.scan_expression() == ExpressionKind::None {
             let end = self.get_current_position();
-            self.errors.push(Error{ start: start_token.end, end, kind: ErrorKind::ExpectingExpression});
+            self.error(start_token.end, end, ErrorKind::ExpectingExpression);
         }
         self.builder.finish_node();
 
         self.eat_whitespace();
+        self.scan_then(start_token);
+        self.eat_whitespace();
+
+        self.builder.start_node(to_raw(SyntaxKind::Block)); //Block
 
         let mut t;
-        if let Some(token) = self.peek_raw_token() {
+        if let Some(token) = self.next_raw_token() {
             t = token;
-            if t.kind != TokenKind::Identifier || str_to_keyword(&self.text[t.start..t.end]) != SyntaxKind::ThenKeyword {
-                let end =  self.get_current_position();
-                self.errors.push(Error{ start: start_token.start, end, kind: ErrorKind::ExpectingThen });
-                self.builder.finish_node(); //IfBranch
-                self.builder.finish_node(); //IfChain
-                return
-            } else {
-                self.next_raw_token();
-                self.builder.token(to_raw(SyntaxKind::ThenKeyword), &self.text[t.start..t.end]);
-                self.eat_whitespace();
-                if let Some(token) = self.next_raw_token() {
-                    t = token;
-                } else {
-                    self.builder.finish_node(); //IfBranch
-                    self.builder.finish_node(); //IfChain
-                    self.errors.push(Error{ start: start_token.start, end: start_token.end, kind: ErrorKind::NotClosedBlock });
-                    return
-                }
-            }
         } else {
+            self.builder.finish_node(); //Block
+            self.missing(SyntaxKind::EndKeyword);
             self.builder.finish_node(); //IfBranch
             self.builder.finish_node(); //IfChain
-            self.errors.push(Error{ start: start_token.start, end: start_token.end, kind: ErrorKind::NotClosedBlock });
+            let end = self.text.len();
+            self.unfinished(end, end, ErrorKind::NotClosedBlock, &[SyntaxKind::EndKeyword], start_token);
             return
         }
 
-        self.builder.start_node(to_raw(SyntaxKind::Block)); //Block
-
         let mut terminated = false;
         let mut seen_else = false;
         loop {
@@ -1426,18 +1446,11 @@ impl<'a> Generator<'a> {
                                 self.builder.start_node(to_raw(SyntaxKind::Condition));
                                 if self.scan_expression() == ExpressionKind::None {
                                     let end = self.get_current_position();
-                                    self.errors.push(Error{ start: t.end, end, kind: ErrorKind::ExpectingExpression});
+                                    self.error(t.end, end, ErrorKind::ExpectingExpression);
                                 }
                                 self.builder.finish_node();
-                                if let Some(token) = self.next_raw_token() {
-                                    t = token;
-                                    self.eat_whitespace();
-                                    if t.kind != TokenKind::Identifier || str_to_keyword(&self.text[t.start..t.end]) != SyntaxKind::ThenKeyword {
-                                        self.errors.push(Error{ start: t.start, end: self.text.len(), kind: ErrorKind::ExpectingThen });
-                                    } else {
-                                        self.builder.token(to_raw(SyntaxKind::ThenKeyword), &self.text[t.start..t.end]);
-                                    }
-                                }
+                                self.eat_whitespace();
+                                self.scan_then(&t);
                                 self.builder.start_node(to_raw(SyntaxKind::Block)); //IfBranch
                             }
                             SyntaxKind::ElseKeyword => {
@@ -1475,30 +1488,57 @@ impl<'a> Generator<'a> {
                     }
                 },
                 TokenKind::Semicolon => {
-                    self.builder.token(to_raw(SyntaxKind::Semicolon), text)
+                    self.builder.start_node(to_raw(SyntaxKind::EmptyStatement));
+                    self.builder.token(to_raw(SyntaxKind::Semicolon), text);
+                    self.builder.finish_node();
                 },
                 TokenKind::LeftBracket => {
                     self.scan_statement(&t, text);
                 }
-                _ => {
-                    self.builder.token(to_raw(SyntaxKind::Invalid), text);
-                    self.errors.push(Error { start: t.start, end: t.end, kind: ErrorKind::UnexpectedToken});
-                }
+                _ => self.skip_invalid(&t),
             }
             self.eat_whitespace();
 
             if let Some(token) = self.next_raw_token() {
                 t = token;
             } else {
-                self.errors.push(Error{ start: start_token.start, end: start_token.end, kind: ErrorKind::NotClosedBlock });
+                let end = self.text.len();
+                self.unfinished(end, end, ErrorKind::NotClosedBlock, &[SyntaxKind::EndKeyword], start_token);
                 break
             }
         }
 
         if !terminated {
-            self.builder.finish_node();
-            self.builder.finish_node();
-            self.builder.finish_node();
+            self.builder.finish_node(); //Block
+            self.missing(SyntaxKind::EndKeyword);
+            self.builder.finish_node(); //IfBranch
+            self.builder.finish_node(); //IfChain
+        }
+    }
+
+    /// Scans the `then` after a condition, carrying on as if it was there when
+    /// it isn't
+    fn scan_then(&mut self, start_token: &Token) {
+        if self.peek_keyword() == Some(SyntaxKind::ThenKeyword) {
+            let t = self.next_raw_token().expect("peeked a token");
+            self.builder.token(to_raw(SyntaxKind::ThenKeyword), &self.text[t.start..t.end]);
+        } else {
+            let end = self.get_current_position();
+            self.unfinished(start_token.start, end, ErrorKind::ExpectingThen, &[SyntaxKind::ThenKeyword], start_token);
+            self.missing(SyntaxKind::ThenKeyword);
+        }
+    }
+
+    /// Scans the `do` starting a loop body, carrying on as if it was there when
+    /// it isn't
+    fn scan_do(&mut self, start_token: &Token) {
+        if self.peek_keyword() == Some(SyntaxKind::DoKeyword) {
+            let t = self.next_raw_token().expect("peeked a token");
+            self.builder.token(to_raw(SyntaxKind::DoKeyword), &self.text[t.start..t.end]);
+        } else {
+            let end = self.get_current_position();
+            self.unfinished(start_token.start, end, ErrorKind::ExpectingDo, &[SyntaxKind::DoKeyword], start_token);
+            self.missing(SyntaxKind::DoKeyword);
         }
     }
 
@@ -1513,7 +1553,9 @@ impl<'a> Generator<'a> {
                 self.next_raw_token();
             }
             self.builder.start_node(to_raw(SyntaxKind::ArgumentList));
-            self.builder.token(to_raw(SyntaxKind::LeftBracket), &self.text[t.start..t.end]);
+            if expecting_closing_bracket {
+                self.builder.token(to_raw(SyntaxKind::LeftBracket), &self.text[t.start..t.end]);
+            }
             self.scan_expression_list();
             self.eat_whitespace();
             let mut is_closed = false;
@@ -1525,7 +1567,9 @@ impl<'a> Generator<'a> {
                 }
             }
             if !is_closed && expecting_closing_bracket {
-                self.errors.push(Error{ start: t.start, end: t.end, kind: ErrorKind::ExpectingClosingBracket });
+                let end = self.get_current_position();
+                self.unfinished(end, end, ErrorKind::ExpectingClosingBracket, &[SyntaxKind::RightBracket], &t);
+                self.missing(SyntaxKind::RightBracket);
             }
             self.builder.finish_node();
         }
@@ -1551,18 +1595,18 @@ impl<'a> Generator<'a> {
                         match token.kind {
                             TokenKind::Identifier => {
                                 if expecting_terminator {
-                                    self.errors.push(Error { start: comma_point, end: comma_point + 1, kind: ErrorKind::ExpectingClosingBracket });
+                                    self.error(comma_point, comma_point + 1, ErrorKind::ExpectingClosingBracket);
                                     break;
                                 }
                                 if expecting_closure && seen_parameter {
-                                    self.errors.push(Error { start: comma_point, end: comma_point + 1, kind: ErrorKind::ExpectingCommaOrBracket });
+                                    self.error(comma_point, comma_point + 1, ErrorKind::ExpectingCommaOrBracket);
                                     break;
                                 }
                                 comma_point = token.end;
                                 seen_parameter = true;
                                 let keyword_type= str_to_keyword(text);
                                 if keyword_type != SyntaxKind::Name {
-                                    self.errors.push(Error { start: token.start, end: token.end, kind: ErrorKind::UnexpectedKeyword });
+                                    self.error(token.start, token.end, ErrorKind::UnexpectedKeyword);
                                     self.builder.token(to_raw(keyword_type), text);
                                 } else {
                                     self.builder.token(to_raw(SyntaxKind::Parameter), text);
@@ -1572,10 +1616,10 @@ impl<'a> Generator<'a> {
                             },
                             TokenKind::Comma => {
                                 if expecting_terminator {
-                                    self.errors.push(Error { start: token.start, end: token.end, kind: ErrorKind::ExpectingClosingBracket });
+                                    self.error(token.start, token.end, ErrorKind::ExpectingClosingBracket);
                                 }
                                 if !expecting_closure {
-                                    self.errors.push(Error { start: token.start, end: token.end, kind: ErrorKind::UnexpectedOperator });
+                                    self.error(token.start, token.end, ErrorKind::UnexpectedOperator);
                                 }
                                 expecting_closure = false;
                                 self.builder.token(to_raw(SyntaxKind::Comma), text);
@@ -1583,7 +1627,7 @@ impl<'a> Generator<'a> {
                             }
                             TokenKind::RightBracket => {
                                 if !expecting_closure && !expecting_terminator && seen_parameter {
-                                    self.errors.push(Error { start: token.start, end: token.end, kind: ErrorKind::ExpectingName });
+                                    self.error(token.start, token.end, ErrorKind::ExpectingName);
                                 }
                                 self.builder.token(to_raw(SyntaxKind::RightBracket), text);
                                 self.next_raw_token();
@@ -1592,14 +1636,14 @@ impl<'a> Generator<'a> {
                             }
                             TokenKind::TripleDot => {
                                 if expecting_closure && seen_parameter {
-                                    self.errors.push(Error { start: token.start, end: token.end, kind: ErrorKind::UnexpectedOperator });
+                                    self.error(token.start, token.end, ErrorKind::UnexpectedOperator);
                                 }
                                 self.builder.token(to_raw(SyntaxKind::ParameterVarArgs), text);
                                 expecting_terminator = true;
                                 self.next_raw_token();
                             }
                             _ => {
-                                self.errors.push(Error { start: token.start, end: token.end, kind: ErrorKind::UnexpectedOperator });
+                                self.error(token.start, token.end, ErrorKind::UnexpectedOperator);
                                 break;
                             }
                         };
@@ -1616,9 +1660,14 @@ impl<'a> Generator<'a> {
 
     fn scan_name_list(&mut self, _token: &Token, text: &str) {
         self.builder.start_node(to_raw(SyntaxKind::NameList));
-        let mut expecting_closure = true;
         self.builder.token(to_raw(SyntaxKind::Name), text);
         self.eat_whitespace();
+        self.scan_name_list_rest();
+    }
+
+    /// Continues an open NameList after its first name, then closes it
+    fn scan_name_list_rest(&mut self) {
+        let mut expecting_closure = true;
         while let Some(token) = self.peek_raw_token()  {
             let text = &self.text[token.start..token.end];
             match token.kind {
@@ -1628,7 +1677,7 @@ impl<'a> Generator<'a> {
                     }
                     let keyword_type= str_to_keyword(text);
                     if keyword_type != SyntaxKind::Name {
-                        self.errors.push(Error { start: token.start, end: token.end, kind: ErrorKind::UnexpectedKeyword });
+                        self.error(token.start, token.end, ErrorKind::UnexpectedKeyword);
                         self.builder.token(to_raw(keyword_type), text);
                     } else {
                         self.builder.token(to_raw(SyntaxKind::Name), text);
@@ -1638,7 +1687,7 @@ impl<'a> Generator<'a> {
                 },
                 TokenKind::Comma => {
                     if !expecting_closure {
-                        self.errors.push(Error { start: token.start, end: token.end, kind: ErrorKind::UnexpectedOperator });
+                        self.error(token.start, token.end, ErrorKind::UnexpectedOperator);
                     }
                     expecting_closure = false;
                     self.builder.token(to_raw(SyntaxKind::Comma), text);
@@ -1658,6 +1707,7 @@ impl<'a> Generator<'a> {
         let mut comma_expected = false;
         let mut field_open = false;
         self.eat_whitespace();
+        self.builder.start_node(to_raw(SyntaxKind::FieldList));
         while let Some(t) = self.peek_raw_token() {
             if !comma_expected {
                 let checkpoint = self.builder.checkpoint();
@@ -1678,17 +1728,18 @@ impl<'a> Generator<'a> {
             let text = &self.text[t.start..t.end];
             match t.kind {
                 TokenKind::LeftSquareBracket => {
+                    let opener = t;
                     if comma_expected {
-                        self.errors.push(Error{ start: t.start, end: t.end, kind: ErrorKind::ExpectingComma });
+                        self.error(t.start, t.end, ErrorKind::ExpectingComma);
                         break;
                     }
                     self.builder.start_node(to_raw(SyntaxKind::Field));
                     field_open = true;
                     self.next_raw_token();
-                    self.eat_whitespace();
                     self.builder.token(to_raw(SyntaxKind::LeftSquareBracket), text);
+                    self.eat_whitespace();
                     if self.scan_expression() == ExpressionKind::None {
-                        self.errors.push(Error{ start: t.start, end: t.end, kind: ErrorKind::ExpectingExpression });
+                        self.error(t.start, t.end, ErrorKind::ExpectingExpression);
                         break
                     }
                     self.eat_whitespace();
@@ -1698,7 +1749,7 @@ impl<'a> Generator<'a> {
                             self.next_raw_token();
                             self.builder.token(to_raw(SyntaxKind::RightSquareBracket), text)
                         } else {
-                            self.errors.push(Error{ start: t.start, end: t.end, kind: ErrorKind::ExpectingClosingBracket });
+                            self.unfinished(t.start, t.end, ErrorKind::ExpectingClosingBracket, &[SyntaxKind::RightSquareBracket], &opener);
                         }
                     }
 
@@ -1711,7 +1762,7 @@ impl<'a> Generator<'a> {
                         self.builder.finish_node();
                     }
                     if !comma_expected {
-                        self.errors.push(Error{ start: t.start, end: t.end, kind: ErrorKind::UnexpectedOperator });
+                        self.error(t.start, t.end, ErrorKind::UnexpectedOperator);
                         break
                     }
                     assign_expected = false;
@@ -1725,7 +1776,7 @@ impl<'a> Generator<'a> {
                 }
                 TokenKind::Assign => {
                     if !assign_expected {
-                        self.errors.push(Error{ start: t.start, end: t.end, kind: ErrorKind::UnexpectedOperator });
+                        self.error(t.start, t.end, ErrorKind::UnexpectedOperator);
                         break
                     }
                     self.next_raw_token();
@@ -1746,5 +1797,6 @@ impl<'a> Generator<'a> {
         if field_open {
             self.builder.finish_node();
         }
+        self.builder.finish_node();
     }
 }
@@ -0,0 +1,382 @@
+//Copyright (C) 2025-  plusmouse and other contributors
+//
+//This program is free software: you can redistribute it and/or modify
+//it under the terms of the GNU General Public License as published by
+//the Free Software Foundation, either version 3 of the License, or
+//(at your option) any later version.
+//
+//This program is distributed in the hope that it will be useful,
+//but WITHOUT ANY WARRANTY; without even the implied warranty of
+//MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//GNU General Public License for more details.
+//
+//You should have received a copy of the GNU General Public License
+//along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Checks the Lua 5.1 compiler does that the parser doesn't
+
+use std::collections::HashSet;
+
+use rowan::NodeOrToken;
+
+use crate::ast::{AstNode, ParameterList};
+use crate::diagnostics::{Diagnostic, DiagnosticKind};
+use crate::syntax::{SyntaxKind, SyntaxNode, SyntaxToken};
+use crate::syntax::literals::{self, EscapeErrorKind};
+
+/// LUAI_MAXVARS, active locals in one function
+const MAX_LOCALS: usize = 200;
+/// LUAI_MAXUPVALUES, distinct upvalues of one closure
+const MAX_UPVALUES: usize = 60;
+
+struct Function {
+    start: usize,
+    vararg: bool,
+    loops: usize,
+    /// Locals in each open block, innermost last, by name and unique id
+    scopes: Vec<Vec<(String, usize)>>,
+    upvalues: HashSet<usize>,
+    too_many_locals: bool,
+    too_many_upvalues: bool,
+}
+
+impl Function {
+    fn new(start: usize, vararg: bool) -> Function {
+        Function {
+            start,
+            vararg,
+            loops: 0,
+            scopes: vec![Vec::new()],
+            upvalues: HashSet::new(),
+            too_many_locals: false,
+            too_many_upvalues: false,
+        }
+    }
+
+    fn active_locals(&self) -> usize {
+        self.scopes.iter().map(|s| s.len()).sum()
+    }
+}
+
+struct Validator<'a> {
+    text: &'a str,
+    functions: Vec<Function>,
+    next_id: usize,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Validator<'_> {
+    fn function(&mut self) -> &mut Function {
+        self.functions.last_mut().expect("the main chunk is always open")
+    }
+
+    fn report(&mut self, kind: DiagnosticKind, start: usize, end: usize, message: String) {
+        self.diagnostics.push(Diagnostic::new(kind, start, end, message));
+    }
+
+    /// "main function" or "function at line n", as the Lua compiler says it
+    fn describe_function(&self, index: usize) -> String {
+        if index == 0 {
+            return String::from("main function")
+        }
+        let start = self.functions[index].start;
+        format!("function at line {}", self.text[..start].matches('\n').count() + 1)
+    }
+
+    fn declare(&mut self, name: &str, start: usize, end: usize) {
+        let id = self.next_id;
+        self.next_id += 1;
+        let function = self.function();
+        function.scopes.last_mut().expect("a function always has a scope").push((name.to_string(), id));
+        if function.active_locals() > MAX_LOCALS && !function.too_many_locals {
+            function.too_many_locals = true;
+            let message = format!("too many local variables (limit is {}) in {}", MAX_LOCALS, self.describe_function(self.functions.len() - 1));
+            self.report(DiagnosticKind::TooManyLocals, start, end, message);
+        }
+    }
+
+    /// The hidden locals the compiler keeps for a `for` loop's state
+    fn declare_hidden(&mut self, count: usize, start: usize, end: usize) {
+        for _ in 0..count {
+            self.declare("(for state)", start, end);
+        }
+    }
+
+    /// Marks a local from an enclosing function as an upvalue of every function
+    /// between there and here
+    fn reference(&mut self, token: &SyntaxToken) {
+        let name = token.text();
+        let mut found = None;
+        for (index, function) in self.functions.iter().enumerate().rev() {
+            let local = function.scopes.iter().rev().find_map(|s| s.iter().rev().find(|(n, _)| n == name));
+            if let Some((_, id)) = local {
+                found = Some((index, *id));
+                break
+            }
+        }
+        let Some((owner, id)) = found else {
+            return
+        };
+        let range = token.text_range();
+        for index in owner + 1..self.functions.len() {
+            let function = &mut self.functions[index];
+            function.upvalues.insert(id);
+            if function.upvalues.len() > MAX_UPVALUES && !function.too_many_upvalues {
+                function.too_many_upvalues = true;
+                let message = format!("too many upvalues (limit is {}) in {}", MAX_UPVALUES, self.describe_function(index));
+                self.report(DiagnosticKind::TooManyUpvalues, range.start().into(), range.end().into(), message);
+            }
+        }
+    }
+
+    fn walk(&mut self, node: &SyntaxNode) {
+        match node.kind() {
+            SyntaxKind::FunctionDefinition => self.walk_function(node),
+            SyntaxKind::LocalAssignStatement => {
+                for child in node.children().filter(|n| n.kind() != SyntaxKind::NameList) {
+                    self.walk(&child);
+                }
+                for name in names(node.children().find(|n| n.kind() == SyntaxKind::NameList)) {
+                    let range = name.text_range();
+                    self.declare(name.text(), range.start().into(), range.end().into());
+                }
+            }
+            SyntaxKind::ForCountLoop | SyntaxKind::ForInLoop => {
+                for child in node.children().filter(|n| n.kind() == SyntaxKind::ExpressionList) {
+                    self.walk(&child);
+                }
+                let range = node.text_range();
+                let start = range.start().into();
+                self.function().scopes.push(Vec::new());
+                self.declare_hidden(3, start, start + 3);
+                let loop_names = if node.kind() == SyntaxKind::ForCountLoop {
+                    names(Some(node.clone()))
+                } else {
+                    names(node.children().find(|n| n.kind() == SyntaxKind::NameList))
+                };
+                for name in loop_names {
+                    let range = name.text_range();
+                    self.declare(name.text(), range.start().into(), range.end().into());
+                }
+                self.function().loops += 1;
+                for child in node.children().filter(|n| n.kind() == SyntaxKind::Block) {
+                    self.walk(&child);
+                }
+                self.function().loops -= 1;
+                self.function().scopes.pop();
+            }
+            SyntaxKind::WhileLoop => {
+                for child in node.children() {
+                    if child.kind() == SyntaxKind::Block {
+                        self.function().loops += 1;
+                        self.walk(&child);
+                        self.function().loops -= 1;
+                    } else {
+                        self.walk(&child);
+                    }
+                }
+            }
+            SyntaxKind::RepeatUntilLoop => {
+                // The condition can see the block's locals, so they share a scope
+                self.function().scopes.push(Vec::new());
+                for child in node.children() {
+                    if child.kind() == SyntaxKind::Block {
+                        self.function().loops += 1;
+                        self.walk_statements(&child);
+                        self.function().loops -= 1;
+                    } else {
+                        self.walk(&child);
+                    }
+                }
+                self.function().scopes.pop();
+            }
+            SyntaxKind::Block => {
+                self.function().scopes.push(Vec::new());
+                self.walk_statements(node);
+                self.function().scopes.pop();
+            }
+            SyntaxKind::BreakStatement => {
+                if self.function().loops == 0 {
+                    let range = node.text_range();
+                    let message = String::from("'break' outside of a loop");
+                    self.report(DiagnosticKind::BreakOutsideLoop, range.start().into(), range.end().into(), message);
+                }
+            }
+            SyntaxKind::Identifier => {
+                let first = node.children_with_tokens().find(|n| !is_trivia(n.kind()));
+                if let Some(NodeOrToken::Token(token)) = first && token.kind() == SyntaxKind::Name {
+                    self.reference(&token);
+                }
+                self.walk_children(node);
+            }
+            SyntaxKind::Field => {
+                let named = node.children_with_tokens().any(|n| n.kind() == SyntaxKind::Assign)
+                    && !node.children_with_tokens().any(|n| n.kind() == SyntaxKind::LeftSquareBracket);
+                // The name of `name = value` is a key, not a variable
+                for child in node.children().skip(if named { 1 } else { 0 }) {
+                    self.walk(&child);
+                }
+            }
+            _ => self.walk_children(node),
+        }
+    }
+
+    fn walk_children(&mut self, node: &SyntaxNode) {
+        for child in node.children_with_tokens() {
+            match child {
+                NodeOrToken::Node(n) => self.walk(&n),
+                NodeOrToken::Token(t) if t.kind() == SyntaxKind::String => self.check_string(&t),
+                NodeOrToken::Token(t) => {
+                    if t.kind() == SyntaxKind::TripleDot && !self.function().vararg {
+                        let range = t.text_range();
+                        let message = String::from("cannot use '...' outside a vararg function");
+                        self.report(DiagnosticKind::VarargOutsideVarargFunction, range.start().into(), range.end().into(), message);
+                    }
+                }
+            }
+        }
+    }
+
+    fn check_string(&mut self, token: &SyntaxToken) {
+        let offset: usize = token.text_range().start().into();
+        for error in literals::decode_string(token.text()).errors {
+            let escape = &token.text()[error.start..error.end];
+            let (kind, message) = match error.kind {
+                EscapeErrorKind::Unknown => (DiagnosticKind::InvalidEscape, format!("invalid escape sequence '{}'", escape)),
+                EscapeErrorKind::TooLarge => (DiagnosticKind::EscapeTooLarge, format!("escape sequence '{}' is too large, the limit is 255", escape)),
+            };
+            self.report(kind, offset + error.start, offset + error.end, message);
+        }
+    }
+
+    /// Walks a block's statements without opening a scope for them
+    fn walk_statements(&mut self, block: &SyntaxNode) {
+        let mut last: Option<SyntaxKind> = None;
+        let mut reported = false;
+        for child in block.children() {
+            if let Some(kind) = last && !reported && child.kind() != SyntaxKind::EmptyStatement {
+                reported = true;
+                let start = child.text_range().start().into();
+                let end = block.children().last().map(|n| n.text_range().end().into()).unwrap_or(start);
+                if kind == SyntaxKind::ReturnStatement {
+                    let message = String::from("'return' must be the last statement of its block");
+                    self.report(DiagnosticKind::StatementAfterReturn, start, end, message);
+                } else {
+                    let message = String::from("'break' must be the last statement of its block");
+                    self.report(DiagnosticKind::StatementAfterBreak, start, end, message);
+                }
+            }
+            if matches!(child.kind(), SyntaxKind::ReturnStatement | SyntaxKind::BreakStatement) {
+                last = Some(child.kind());
+            }
+            self.walk(&child);
+        }
+    }
+
+    fn walk_function(&mut self, node: &SyntaxNode) {
+        let is_local = node.children_with_tokens().any(|n| n.kind() == SyntaxKind::LocalKeyword);
+        // `local function f` can call itself, so f exists before the body
+        if is_local && let Some(NodeOrToken::Token(name)) = node.children_with_tokens().find(|n| n.kind() == SyntaxKind::Name) {
+            let range = name.text_range();
+            self.declare(name.text(), range.start().into(), range.end().into());
+        }
+        let mut is_method = false;
+        if let Some(identifier) = node.children().find(|n| n.kind() == SyntaxKind::Identifier) {
+            is_method = identifier.children_with_tokens().any(|n| n.kind() == SyntaxKind::Colon);
+            self.walk(&identifier);
+        }
+
+        let params = node.children().find_map(ParameterList::cast);
+        let vararg = params.as_ref().is_some_and(|p| p.varargs().is_some());
+        let start = node.text_range().start().into();
+        self.functions.push(Function::new(start, vararg));
+        if is_method {
+            self.declare("self", start, start);
+        }
+        for param in params.map(|p| p.parameters()).unwrap_or_default() {
+            let range = param.syntax().text_range();
+            self.declare(param.name(), range.start().into(), range.end().into());
+        }
+        for child in node.children().filter(|n| n.kind() == SyntaxKind::Block) {
+            self.walk(&child);
+        }
+        self.functions.pop();
+    }
+}
+
+fn is_trivia(kind: SyntaxKind) -> bool {
+    matches!(kind, SyntaxKind::Whitespace | SyntaxKind::Newline | SyntaxKind::Comment)
+}
+
+/// The `Name` tokens directly inside a node
+fn names(node: Option<SyntaxNode>) -> Vec<SyntaxToken> {
+    let Some(node) = node else {
+        return Vec::new()
+    };
+    node.children_with_tokens()
+        .filter_map(|n| n.into_token())
+        .filter(|t| t.kind() == SyntaxKind::Name)
+        .collect()
+}
+
+pub fn check(root: &SyntaxNode, text: &str) -> Vec<Diagnostic> {
+    let mut validator = Validator {
+        text,
+        // The main chunk is a vararg function
+        functions: vec![Function::new(0, true)],
+        next_id: 0,
+        diagnostics: Vec::new(),
+    };
+    validator.walk_statements(root);
+    validator.diagnostics
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::syntax::syntax::Generator;
+
+    fn codes(text: &str) -> Vec<&'static str> {
+        let root = SyntaxNode::new_root(Generator::new(text).process_all());
+        check(&root, text).iter().map(|d| d.kind.code()).collect()
+    }
+
+    #[test]
+    fn varargs_need_a_vararg_function() {
+        assert_eq!(codes("local function f() return ... end\n"), ["unexpect-dots"]);
+        assert_eq!(codes("local function f(...) return ... end\n"), Vec::<&str>::new());
+        // The file itself takes `...`
+        assert_eq!(codes("local name = ...\n"), Vec::<&str>::new());
+    }
+
+    #[test]
+    fn break_needs_a_loop() {
+        assert_eq!(codes("if x then break end\n"), ["break-outside"]);
+        assert_eq!(codes("while x do if y then break end end\n"), Vec::<&str>::new());
+        assert_eq!(codes("for i = 1, 2 do local f = function() break end end\n"), ["break-outside"]);
+    }
+
+    #[test]
+    fn nothing_after_return_or_break() {
+        assert_eq!(codes("local function f()\n    return 1\n    f()\nend\n"), ["action-after-return"]);
+        assert_eq!(codes("while x do\n    break\n    f()\nend\n"), ["action-after-break"]);
+    }
+
+    #[test]
+    fn limits_locals() {
+        let declare = |n: usize| (0..n).map(|i| format!("local v{i} = {i}\n")).collect::<String>();
+        assert_eq!(codes(&declare(MAX_LOCALS)), Vec::<&str>::new());
+        assert_eq!(codes(&declare(MAX_LOCALS + 1)), ["local-limit"]);
+    }
+
+    #[test]
+    fn limits_upvalues() {
+        let closure = |n: usize| {
+            let locals: String = (0..n).map(|i| format!("local v{i} = {i}\n")).collect();
+            let uses: Vec<String> = (0..n).map(|i| format!("v{i}")).collect();
+            format!("{locals}local function f() return {} end\n", uses.join(", "))
+        };
+        assert_eq!(codes(&closure(MAX_UPVALUES)), Vec::<&str>::new());
+        assert_eq!(codes(&closure(MAX_UPVALUES + 1)), ["upvalue-limit"]);
+    }
+}
@@ -1,11 +1,14 @@
 use std::collections::HashMap;
+use std::rc::Rc;
 
 use rowan::GreenNode;
-use serde_json::Value;
+use crate::annotations;
 use crate::ast::*;
-use crate::syntax::SyntaxNode;
+use crate::diagnostics::{Diagnostic, DiagnosticKind};
+use crate::symbols::GlobalTypes;
+use crate::syntax::{SyntaxKind, SyntaxNode};
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 enum ValueType {
     Nil,
     Boolean,
@@ -16,74 +19,1447 @@ enum ValueType {
     Table,
     Missing,
     Relay(String),
+    Union(Vec<ValueType>),
+    /// No value at all, what's left of a name on a path a condition rules out
+    Never,
+}
+
+impl ValueType {
+    fn union(self, other: ValueType) -> ValueType {
+        match (&self, &other) {
+            (ValueType::Never, _) => return other,
+            (_, ValueType::Never) => return self,
+            _ => (),
+        }
+        let mut members: Vec<ValueType> = Vec::new();
+        for t in [self, other] {
+            match t {
+                ValueType::Union(inner) => members.extend(inner),
+                _ => members.push(t),
+            }
+        }
+        ValueType::from_members(members)
+    }
+
+    fn from_members(members: Vec<ValueType>) -> ValueType {
+        let mut unique: Vec<ValueType> = Vec::with_capacity(members.len());
+        for m in members {
+            if m != ValueType::Never && !unique.contains(&m) {
+                unique.push(m);
+            }
+        }
+        match unique.len() {
+            0 => ValueType::Missing,
+            1 => unique.pop().expect("one member"),
+            _ => ValueType::Union(unique),
+        }
+    }
+
+    fn members(&self) -> Vec<ValueType> {
+        match self {
+            ValueType::Union(inner) => inner.clone(),
+            _ => vec![self.clone()],
+        }
+    }
+
+    /// Type from the names in a `---@param` annotation
+    fn from_annotation(types: &[String], optional: bool) -> ValueType {
+        let mut members: Vec<ValueType> = types.iter().map(|t| {
+            match t.as_str() {
+                "nil" => ValueType::Nil,
+                "boolean" => ValueType::Boolean,
+                "number" | "integer" => ValueType::Number,
+                "string" => ValueType::String,
+                "thread" => ValueType::Thread,
+                "function" => ValueType::Function,
+                "table" => ValueType::Table,
+                _ if t.starts_with("fun") => ValueType::Function,
+                _ if t.starts_with("table<") || t.ends_with("[]") => ValueType::Table,
+                _ => ValueType::Missing,
+            }
+        }).collect();
+        if members.is_empty() {
+            members.push(ValueType::Missing);
+        }
+        if optional {
+            members.push(ValueType::Nil);
+        }
+        ValueType::from_members(members)
+    }
+
+    fn name(&self) -> String {
+        match self {
+            ValueType::Nil => String::from("nil"),
+            ValueType::Boolean => String::from("boolean"),
+            ValueType::Number => String::from("number"),
+            ValueType::String => String::from("string"),
+            ValueType::Function => String::from("function"),
+            ValueType::Thread => String::from("thread"),
+            ValueType::Table => String::from("table"),
+            ValueType::Missing => String::from("unknown"),
+            ValueType::Relay(r) => r.clone(),
+            ValueType::Union(inner) => inner.iter().map(|t| t.name()).collect::<Vec<String>>().join("|"),
+            ValueType::Never => String::from("never"),
+        }
+    }
+
+    /// Whether every possible value of this type is one of `types`
+    fn is_only(&self, types: &[ValueType]) -> bool {
+        match self {
+            ValueType::Union(inner) => inner.iter().all(|t| t.is_only(types)),
+            _ => types.contains(self),
+        }
+    }
+
+    /// Whether a value of this type could be `nil` or `false`
+    fn can_be_falsy(&self) -> bool {
+        match self {
+            ValueType::Nil | ValueType::Boolean | ValueType::Missing | ValueType::Relay(_) => true,
+            ValueType::Union(inner) => inner.iter().any(|t| t.can_be_falsy()),
+            _ => false,
+        }
+    }
+
+    /// Whether the value might be nil, without always being nil
+    fn may_be_nil(&self) -> bool {
+        match self {
+            ValueType::Union(inner) => inner.contains(&ValueType::Nil),
+            _ => false,
+        }
+    }
+
+    fn without_nil(&self) -> ValueType {
+        let members: Vec<ValueType> = self.members().into_iter().filter(|t| *t != ValueType::Nil).collect();
+        if members.is_empty() {
+            return ValueType::Never
+        }
+        ValueType::from_members(members)
+    }
+
+    /// The part of the type that lets an `and` stop at its left side
+    fn falsy_part(&self) -> ValueType {
+        let members: Vec<ValueType> = self.members().into_iter().filter(|t| t.can_be_falsy()).collect();
+        ValueType::from_members(members)
+    }
 }
 
 #[derive(Debug)]
-struct Identifier {
+struct Declaration {
     file: String,
+    name: String,
     block_index: Vec<usize>,
     offset_from_block: usize,
+    /// Where the declared name is in the file
+    range: (usize, usize),
     value_type: ValueType,
+    signature: Option<Rc<FunctionSignature>>,
+}
+
+#[derive(Debug, PartialEq)]
+struct FunctionSignature {
+    parameters: usize,
+    varargs: bool,
+    returns: Vec<ValueType>,
 }
 
-fn get_expression_type(expression: &Expression) -> ValueType {
-    match expression {
-        Expression::Literal(l) => {
-            if let Some(_) = l.get_string() {
-                return ValueType::String
-            } else if let Some(_) = l.get_bool() {
-                return ValueType::Boolean
-            } else if let Some(_) = l.get_number() {
-                return ValueType::Number
-            } else if l.is_nil() {
-                return ValueType::Nil
+#[derive(Debug, Clone)]
+struct Binding {
+    value_type: ValueType,
+    signature: Option<Rc<FunctionSignature>>,
+    /// Fields of a table built by a constructor, `None` once the table has been
+    /// somewhere that could add fields we can't see
+    fields: Option<HashMap<String, ValueType>>,
+}
+
+impl Binding {
+    fn new(value_type: ValueType) -> Binding {
+        Binding { value_type, signature: None, fields: None }
+    }
+
+    /// Whether a condition on the path ruled out every value the name or one
+    /// of its fields could have had, so the path can't really be taken
+    fn is_contradiction(&self) -> bool {
+        self.value_type == ValueType::Never
+            || self.fields.as_ref().is_some_and(|f| f.values().any(|t| *t == ValueType::Never))
+    }
+
+    /// Combines the bindings left by two paths through the code
+    fn merge(self, other: Binding) -> Binding {
+        let signature = match (&self.signature, &other.signature) {
+            (Some(a), Some(b)) if Rc::ptr_eq(a, b) => self.signature.clone(),
+            _ => None,
+        };
+        let fields = match (self.fields, other.fields) {
+            (Some(mut a), Some(b)) => {
+                for (k, v) in b {
+                    let merged = match a.remove(&k) {
+                        Some(existing) => existing.union(v),
+                        None => v,
+                    };
+                    a.insert(k, merged);
+                }
+                Some(a)
             }
+            _ => None,
+        };
+        Binding { value_type: self.value_type.union(other.value_type), signature, fields }
+    }
+}
+
+/// Values an expression list produces, `open` is set when the final expression
+/// could produce more values than are known
+struct ValueList {
+    values: Vec<ValueType>,
+    open: bool,
+}
+
+impl ValueList {
+    fn get(&self, index: usize) -> ValueType {
+        match self.values.get(index) {
+            Some(v) => v.clone(),
+            None if self.open => ValueType::Missing,
+            None => ValueType::Nil,
         }
-        Expression::Function(_) => return ValueType::Function,
-        Expression::TableConstructor(_) => return ValueType::Table,
-        _ => ()
     }
-    ValueType::Missing
 }
 
-pub fn get_types(green: GreenNode, filename: &str) {
-    let root = SyntaxNode::new_root(green);
-    let mut block_queue: Vec<SyntaxNode> = Vec::new();
-    block_queue.push(root.clone());
-    let mut indexes: Vec<usize> = Vec::new();
-    let mut block_indexes: Vec<usize> = Vec::new();
-    block_indexes.push(0);
-    indexes.push(0);
-
-    let mut block = Block::cast(root).expect("everything starts with a block");
-
-    loop {
-        let mut all_statements = block.statements();
-        if indexes[indexes.len() - 1] >= all_statements.len() {
-            indexes.pop();
-            block_queue.pop();
-            if block_queue.len() > 0 {
-                block = Block::cast(block_queue[block_queue.len() - 1].clone()).expect("block expected due to past queue");
-                all_statements = block.statements();
+/// Bindings from outside a path that the path changed, with their values from
+/// before it
+struct PathLog {
+    base: usize,
+    changes: Vec<(usize, String, Option<Binding>)>,
+}
+
+/// The bindings a path left behind, by scope level and name
+type Outcome = HashMap<(usize, String), Option<Binding>>;
+
+/// A name, or `name.field`, with the type a condition proves it has
+type Narrowing = (Vec<String>, ValueType);
+
+struct Scanner {
+    file: String,
+    block_index: Vec<usize>,
+    /// Local bindings visible at a point in the file, innermost scope last
+    scopes: Vec<HashMap<String, Binding>>,
+    /// Scope level where each function body being scanned starts
+    function_scopes: Vec<usize>,
+    /// Value lists from each `return` in the functions currently being scanned
+    returns: Vec<Vec<ValueList>>,
+    paths: Vec<PathLog>,
+    declarations: Vec<Declaration>,
+    diagnostics: Vec<Diagnostic>,
+    /// Globals set by the other files of the addon, for names this file never sets
+    globals: HashMap<String, ValueType>,
+}
+
+impl Scanner {
+    fn lookup(&self, name: &str) -> Option<&Binding> {
+        self.scopes.iter().rev().find_map(|s| s.get(name))
+    }
+
+    fn lookup_level(&self, name: &str) -> Option<usize> {
+        (0..self.scopes.len()).rev().find(|l| self.scopes[*l].contains_key(name))
+    }
+
+    /// Whether a binding at this level is from outside the function being
+    /// scanned, so may have changed by the time the function runs
+    fn is_upvalue(&self, level: usize) -> bool {
+        self.function_scopes.last().is_some_and(|start| level < *start)
+    }
+
+    fn declare(&mut self, name: String, binding: Binding) {
+        let len = self.scopes.len();
+        self.scopes[len - 1].insert(name, binding);
+    }
+
+    fn set_at(&mut self, level: usize, name: String, binding: Option<Binding>) {
+        if let Some(log) = self.paths.last_mut()
+            && level < log.base
+            && !log.changes.iter().any(|(l, n, _)| *l == level && *n == name) {
+            log.changes.push((level, name.clone(), self.scopes[level].get(&name).cloned()));
+        }
+        match binding {
+            Some(b) => self.scopes[level].insert(name, b),
+            None => self.scopes[level].remove(&name),
+        };
+    }
+
+    fn assign(&mut self, name: String, binding: Binding) {
+        match self.lookup_level(&name) {
+            Some(level) => self.set_at(level, name, Some(binding)),
+            // Not a local, so it's a global and anything could add fields to it
+            None => self.set_at(0, name, Some(Binding { fields: None, ..binding })),
+        }
+    }
+
+    fn update_binding(&mut self, name: &str, update: impl FnOnce(&mut Binding)) {
+        let Some(level) = self.lookup_level(name) else {
+            return
+        };
+        let mut binding = self.scopes[level][name].clone();
+        update(&mut binding);
+        self.set_at(level, String::from(name), Some(binding));
+    }
+
+    fn set_field(&mut self, name: &str, field: &str, value_type: ValueType) {
+        self.update_binding(name, |b| {
+            if let Some(fields) = &mut b.fields {
+                fields.insert(String::from(field), value_type);
+            }
+        });
+    }
+
+    /// Stops trusting the known fields of a table once it's passed around
+    fn forget_fields(&mut self, name: &str) {
+        if self.lookup(name).is_some_and(|b| b.fields.is_some()) {
+            self.update_binding(name, |b| b.fields = None);
+        }
+    }
+
+    /// Scans one of several paths the code could take, then undoes the changes
+    /// it made outside itself and returns them instead
+    fn scan_path(&mut self, scan: impl FnOnce(&mut Scanner)) -> Outcome {
+        self.paths.push(PathLog { base: self.scopes.len(), changes: Vec::new() });
+        scan(self);
+        let log = self.paths.pop().expect("pushed before scanning");
+        let mut outcome = HashMap::new();
+        for (level, name, previous) in log.changes {
+            let current = self.scopes[level].get(&name).cloned();
+            match previous {
+                Some(b) => self.scopes[level].insert(name.clone(), b),
+                None => self.scopes[level].remove(&name),
+            };
+            outcome.insert((level, name), current);
+        }
+        outcome
+    }
+
+    fn apply(&mut self, outcome: &Outcome) {
+        for ((level, name), binding) in outcome {
+            self.set_at(*level, name.clone(), binding.clone());
+        }
+    }
+
+    /// Sets each binding to the combination of what the paths left behind, a
+    /// path that didn't touch a binding leaves the current one. Paths that
+    /// contradict themselves are left out, unless that's all of them
+    fn merge_paths(&mut self, outcomes: Vec<Outcome>) {
+        let (possible, impossible): (Vec<Outcome>, Vec<Outcome>) = outcomes.into_iter()
+            .partition(|o| !o.values().any(|b| b.as_ref().is_some_and(Binding::is_contradiction)));
+        let outcomes = if possible.is_empty() { impossible } else { possible };
+        let mut keys: Vec<(usize, String)> = Vec::new();
+        for outcome in &outcomes {
+            for key in outcome.keys() {
+                if !keys.contains(key) {
+                    keys.push(key.clone());
+                }
+            }
+        }
+        for (level, name) in keys {
+            let current = self.scopes[level].get(&name).cloned();
+            let merged = outcomes.iter().map(|o| match o.get(&(level, name.clone())) {
+                Some(b) => b.clone(),
+                None => current.clone(),
+            }).reduce(|a, b| match (a, b) {
+                (Some(a), Some(b)) => Some(a.merge(b)),
+                (a, b) => a.or(b),
+            }).flatten();
+            self.set_at(level, name, merged);
+        }
+    }
+
+    fn narrow(&mut self, narrowing: Vec<Narrowing>) {
+        for (chain, value_type) in narrowing {
+            match chain.as_slice() {
+                [name] => self.update_binding(name, |b| b.value_type = value_type),
+                [name, field] => self.set_field(name, field, value_type),
+                _ => (),
+            }
+        }
+    }
+
+    /// Narrowing of `chain` to exclude nil, if it could be nil
+    fn not_nil(&self, chain: Vec<String>) -> Vec<Narrowing> {
+        let t = self.get_chain_type(&chain);
+        if !t.can_be_falsy() {
+            return Vec::new()
+        }
+        vec![(chain, t.without_nil())]
+    }
+
+    /// What the condition being truthy proves about names
+    fn truthy_narrowing(&self, condition: &Expression) -> Vec<Narrowing> {
+        match condition {
+            Expression::Identifier(i) => {
+                if let Some(chain) = i.name_chain() {
+                    return self.not_nil(chain)
+                }
+            }
+            Expression::GroupedExpression(g) => {
+                if let Some(e) = g.get_expression() {
+                    return self.truthy_narrowing(&e)
+                }
+            }
+            Expression::UnaryExpression(u) if u.kind() == Operator::Not => {
+                if let Some(e) = u.get_terms().first() {
+                    return self.falsy_narrowing(e)
+                }
+            }
+            Expression::BinaryExpression(b) => {
+                let terms = b.get_terms();
+                match b.kind() {
+                    Operator::And if terms.len() == 2 => {
+                        let mut narrowing = self.truthy_narrowing(&terms[0]);
+                        narrowing.extend(self.truthy_narrowing(&terms[1]));
+                        return narrowing
+                    }
+                    Operator::NotEquals => return self.nil_comparison(&terms),
+                    // `type(x) == "table"` rules out nil as well
+                    Operator::Equals if terms.len() == 2 => {
+                        for (call, other) in [(&terms[0], &terms[1]), (&terms[1], &terms[0])] {
+                            if let (Expression::FunctionCall(c), Expression::Literal(l)) = (call, other)
+                                && l.get_string().is_some_and(|s| !s.contains("nil"))
+                                && c.identifier().is_some_and(|i| i.names() == ["type"])
+                                && let Some(Expression::Identifier(i)) = c.arguments().and_then(|a| a.expressions().into_iter().next())
+                                && let Some(chain) = i.name_chain() {
+                                return self.not_nil(chain)
+                            }
+                        }
+                    }
+                    _ => (),
+                }
+            }
+            _ => (),
+        }
+        Vec::new()
+    }
+
+    /// What the condition being falsy proves about names
+    fn falsy_narrowing(&self, condition: &Expression) -> Vec<Narrowing> {
+        match condition {
+            Expression::GroupedExpression(g) => {
+                if let Some(e) = g.get_expression() {
+                    return self.falsy_narrowing(&e)
+                }
+            }
+            Expression::UnaryExpression(u) if u.kind() == Operator::Not => {
+                if let Some(e) = u.get_terms().first() {
+                    return self.truthy_narrowing(e)
+                }
+            }
+            Expression::BinaryExpression(b) => {
+                let terms = b.get_terms();
+                match b.kind() {
+                    Operator::Or if terms.len() == 2 => {
+                        let mut narrowing = self.falsy_narrowing(&terms[0]);
+                        narrowing.extend(self.falsy_narrowing(&terms[1]));
+                        return narrowing
+                    }
+                    Operator::Equals => return self.nil_comparison(&terms),
+                    _ => (),
+                }
+            }
+            _ => (),
+        }
+        Vec::new()
+    }
+
+    /// Narrowing for `x ~= nil` being true or `x == nil` being false
+    fn nil_comparison(&self, terms: &[Expression]) -> Vec<Narrowing> {
+        if terms.len() != 2 {
+            return Vec::new()
+        }
+        for (name, other) in [(&terms[0], &terms[1]), (&terms[1], &terms[0])] {
+            if let (Expression::Identifier(i), Expression::Literal(l)) = (name, other)
+                && l.is_nil()
+                && let Some(chain) = i.name_chain() {
+                return self.not_nil(chain)
+            }
+        }
+        Vec::new()
+    }
+
+    /// Type of `name` or `name.field`, unknown for anything longer
+    fn get_chain_type(&self, chain: &[String]) -> ValueType {
+        let Some(level) = chain.first().and_then(|n| self.lookup_level(n)) else {
+            return match chain.len() {
+                1 | 2 => self.globals.get(&chain.join(".")).cloned().unwrap_or(ValueType::Missing),
+                _ => ValueType::Missing,
+            }
+        };
+        let binding = &self.scopes[level][&chain[0]];
+        let upvalue = self.is_upvalue(level);
+        match chain.len() {
+            1 => {
+                // Something else may have set it before the function runs
+                if upvalue && binding.value_type.members().contains(&ValueType::Nil) {
+                    return ValueType::Missing
+                }
+                binding.value_type.clone()
+            }
+            2 => match &binding.fields {
+                Some(fields) => match fields.get(&chain[1]) {
+                    Some(t) => t.clone(),
+                    None if upvalue => ValueType::Missing,
+                    None => ValueType::Nil,
+                },
+                None => ValueType::Missing,
+            },
+            _ => ValueType::Missing,
+        }
+    }
+
+    fn get_callee(&self, call: &FunctionCall) -> Option<&Binding> {
+        let identifier = call.identifier()?;
+        if identifier.is_indexed_expression() || identifier.is_call_to_self() {
+            return None
+        }
+        let names = identifier.names();
+        if names.len() != 1 {
+            return None
+        }
+        self.lookup(&names[0])
+    }
+
+    /// What a call to one of Lua's or the game's functions returns, unless
+    /// the file has its own binding of the name
+    fn get_builtin_type(&self, call: &FunctionCall) -> ValueType {
+        let Some(identifier) = call.identifier() else {
+            return ValueType::Missing
+        };
+        if identifier.is_indexed_expression() || identifier.is_call_to_self() {
+            return ValueType::Missing
+        }
+        let names = identifier.names();
+        if names.first().is_none_or(|n| self.lookup(n).is_some()) {
+            return ValueType::Missing
+        }
+        builtin_return_type(&names.join("."))
+    }
+
+    fn get_call_values(&self, call: &FunctionCall) -> ValueList {
+        if let Some(signature) = self.get_callee(call).and_then(|b| b.signature.as_ref()) {
+            return ValueList { values: signature.returns.clone(), open: false }
+        }
+        match self.get_builtin_type(call) {
+            ValueType::Missing => ValueList { values: Vec::new(), open: true },
+            t => ValueList { values: vec![t], open: false },
+        }
+    }
+
+    fn get_expression_type(&self, expression: &Expression) -> ValueType {
+        match expression {
+            Expression::Literal(l) => {
+                if l.get_string().is_some() {
+                    return ValueType::String
+                } else if l.get_bool().is_some() {
+                    return ValueType::Boolean
+                } else if l.get_number().is_some() {
+                    return ValueType::Number
+                } else if l.is_nil() {
+                    return ValueType::Nil
+                }
+            }
+            Expression::Function(_) => return ValueType::Function,
+            Expression::TableConstructor(_) => return ValueType::Table,
+            Expression::GroupedExpression(g) => {
+                if let Some(e) = g.get_expression() {
+                    return self.get_expression_type(&e)
+                }
+            }
+            Expression::UnaryExpression(u) => {
+                match u.kind() {
+                    Operator::Not => return ValueType::Boolean,
+                    Operator::Subtract | Operator::ArrayLength => return ValueType::Number,
+                    _ => (),
+                }
+            }
+            Expression::BinaryExpression(b) => {
+                let terms = b.get_terms();
+                match b.kind() {
+                    Operator::Add | Operator::Subtract | Operator::Multiply | Operator::Divide
+                        | Operator::Modulo | Operator::Hat => return ValueType::Number,
+                    Operator::Concatenate => return ValueType::String,
+                    Operator::LessThan | Operator::GreaterThan | Operator::LessThanOrEquals
+                        | Operator::GreaterThanOrEquals | Operator::NotEquals | Operator::Equals => return ValueType::Boolean,
+                    Operator::And | Operator::Or if terms.len() == 2 => {
+                        let left = self.get_expression_type(&terms[0]);
+                        let right = self.get_expression_type(&terms[1]);
+                        // A truthy left side decides the result on its own
+                        if !left.can_be_falsy() {
+                            if b.kind() == Operator::And {
+                                return right
+                            }
+                            return left
+                        }
+                        if b.kind() == Operator::And {
+                            return left.falsy_part().union(right)
+                        }
+                        return left.without_nil().union(right)
+                    }
+                    _ => (),
+                }
+            }
+            Expression::Identifier(i) => {
+                if let Some(chain) = i.name_chain() {
+                    return self.get_chain_type(&chain)
+                }
+            }
+            Expression::PrefixExpression(p) => {
+                if let Some(e) = p.expression() {
+                    return self.get_expression_type(&e)
+                }
+            }
+            Expression::Index(_) => (),
+            // Only the first value survives outside the end of an expression list
+            Expression::FunctionCall(c) => return self.get_call_values(c).get(0),
+            Expression::MethodCall(m) => return self.get_call_values(&m.call()).get(0),
+        }
+        ValueType::Missing
+    }
+
+    fn get_binding(&self, expression: &Expression) -> Binding {
+        let value_type = self.get_expression_type(expression);
+        let signature = match expression {
+            Expression::Identifier(i) => {
+                let names = i.names();
+                if names.len() == 1 && !i.is_indexed_expression() {
+                    self.lookup(&names[0]).and_then(|b| b.signature.clone())
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        };
+        let fields = match expression {
+            Expression::TableConstructor(t) => self.get_constructor_fields(t),
+            _ => None,
+        };
+        Binding { value_type, signature, fields }
+    }
+
+    /// Named fields of `{ a = 1, b = "x" }`, or `None` when a `[key] = value`
+    /// field could be adding any name
+    fn get_constructor_fields(&self, table: &TableConstructor) -> Option<HashMap<String, ValueType>> {
+        let mut fields = HashMap::new();
+        for field in table.fields() {
+            if field.is_keyed() {
+                return None
+            }
+            if let (Some(name), Some(value)) = (field.name(), field.value()) {
+                fields.insert(name, self.get_expression_type(&value));
+            }
+        }
+        Some(fields)
+    }
+
+    /// Scans the function literals in the list, so their signatures are known
+    /// before any names are bound
+    fn get_list_bindings(&mut self, expression_list: Option<ExpressionList>) -> (Vec<Binding>, ValueList) {
+        let Some(list) = expression_list else {
+            return (Vec::new(), ValueList { values: Vec::new(), open: false })
+        };
+        let expressions = list.expressions();
+        let mut bindings = Vec::with_capacity(expressions.len());
+        for e in &expressions {
+            match e {
+                Expression::Function(f) => {
+                    let signature = self.scan_function(f);
+                    bindings.push(Binding { value_type: ValueType::Function, signature: Some(Rc::new(signature)), fields: None });
+                }
+                _ => {
+                    // Read the binding first, as checking a bare table name forgets its fields
+                    let binding = self.get_binding(e);
+                    self.check_expression(e);
+                    bindings.push(binding);
+                }
+            }
+        }
+        let values = self.get_list_values(&list);
+        (bindings, values)
+    }
+
+    fn get_list_values(&self, list: &ExpressionList) -> ValueList {
+        let expressions = list.expressions();
+        let mut values: Vec<ValueType> = Vec::with_capacity(expressions.len());
+        let mut open = list.ends_with_varargs();
+        for (i, e) in expressions.iter().enumerate() {
+            if i + 1 == expressions.len() && !open
+                && let Some(c) = e.as_call() {
+                let tail = self.get_call_values(&c);
+                values.extend(tail.values);
+                open = tail.open;
             } else {
-                break;
+                values.push(self.get_expression_type(e));
+            }
+        }
+        ValueList { values, open }
+    }
+
+    fn bind_values(bindings: Vec<Binding>, values: &ValueList, count: usize) -> Vec<Binding> {
+        let mut bindings = bindings;
+        bindings.truncate(count);
+        // Names past the last expression take the expanded values instead
+        let last_expanded = values.values.len() > bindings.len() || values.open;
+        if last_expanded && !bindings.is_empty() && bindings.len() < count {
+            bindings.pop();
+        }
+        while bindings.len() < count {
+            bindings.push(Binding::new(values.get(bindings.len())));
+        }
+        bindings
+    }
+
+    fn record(&mut self, name: &str, node: &SyntaxNode, block: &Block, binding: &Binding) {
+        let offset_from_block = usize::from(node.text_range().start() - block.syntax().text_range().start());
+        let range = node.descendants_with_tokens().filter_map(|n| n.into_token())
+            .find(|t| t.kind() == SyntaxKind::Name && t.text() == name)
+            .map(|t| (usize::from(t.text_range().start()), usize::from(t.text_range().end())))
+            .unwrap_or_else(|| trimmed_range(node));
+        let id = Declaration {
+            offset_from_block,
+            range,
+            file: self.file.clone(),
+            name: String::from(name),
+            block_index: self.block_index.clone(),
+            value_type: binding.value_type.clone(),
+            signature: binding.signature.clone(),
+        };
+        self.declarations.push(id);
+    }
+
+    fn report(&mut self, kind: DiagnosticKind, node: &SyntaxNode, message: String) {
+        let (start, end) = trimmed_range(node);
+        self.diagnostics.push(Diagnostic::new(kind, start, end, message));
+    }
+
+    fn check_arithmetic(&mut self, term: &Expression) {
+        let t = self.get_expression_type(term);
+        if t.is_only(&[ValueType::String, ValueType::Table, ValueType::Function, ValueType::Nil, ValueType::Boolean]) {
+            self.report(DiagnosticKind::ArithmeticOnNonNumber, term.syntax(), format!("Arithmetic on a {} value", t.name()));
+        }
+    }
+
+    fn check_expression(&mut self, expression: &Expression) {
+        match expression {
+            Expression::Literal(_) => (),
+            Expression::Function(f) => {
+                self.scan_function(f);
+            }
+            Expression::TableConstructor(t) => {
+                for field in t.fields() {
+                    if let Some(e) = field.key() {
+                        self.check_expression(&e);
+                    }
+                    if let Some(e) = field.value() {
+                        self.check_expression(&e);
+                    }
+                }
+            }
+            Expression::GroupedExpression(g) => {
+                if let Some(e) = g.get_expression() {
+                    self.check_expression(&e);
+                }
+            }
+            Expression::UnaryExpression(u) => {
+                let terms = u.get_terms();
+                for e in &terms {
+                    self.check_expression(e);
+                }
+                if u.kind() == Operator::Subtract && let Some(e) = terms.first() {
+                    self.check_arithmetic(e);
+                }
+            }
+            Expression::BinaryExpression(b) => {
+                let terms = b.get_terms();
+                // The right side only runs when the left side lets it
+                if matches!(b.kind(), Operator::And | Operator::Or) && terms.len() == 2 {
+                    self.check_expression(&terms[0]);
+                    let narrowing = match b.kind() {
+                        Operator::And => self.truthy_narrowing(&terms[0]),
+                        _ => self.falsy_narrowing(&terms[0]),
+                    };
+                    let right = &terms[1];
+                    let outcome = self.scan_path(|s| {
+                        s.narrow(narrowing);
+                        s.check_expression(right);
+                    });
+                    self.merge_paths(vec![outcome, Outcome::new()]);
+                    return
+                }
+                for e in &terms {
+                    self.check_expression(e);
+                }
+                match b.kind() {
+                    Operator::Add | Operator::Subtract | Operator::Multiply | Operator::Divide
+                        | Operator::Modulo | Operator::Hat => {
+                        for e in &terms {
+                            self.check_arithmetic(e);
+                        }
+                    }
+                    Operator::Concatenate => {
+                        for e in &terms {
+                            if self.get_expression_type(e) == ValueType::Table {
+                                self.report(DiagnosticKind::ConcatenatingTable, e.syntax(), String::from("Concatenating a table value"));
+                            }
+                        }
+                    }
+                    _ => (),
+                }
+            }
+            Expression::Identifier(i) => {
+                // A table used as a value could have fields added anywhere
+                if let Some([name]) = i.name_chain().as_deref() {
+                    self.forget_fields(name);
+                }
+                self.check_identifier(i)
+            }
+            Expression::PrefixExpression(p) => {
+                if let Some(e) = p.expression() {
+                    self.check_expression(&e);
+                }
+            }
+            Expression::Index(x) => self.check_identifier(&x.identifier()),
+            Expression::FunctionCall(c) => self.check_call(c),
+            Expression::MethodCall(m) => self.check_call(&m.call()),
+        }
+    }
+
+    fn check_list(&mut self, list: &ExpressionList) {
+        for e in list.expressions() {
+            self.check_expression(&e);
+        }
+    }
+
+    fn check_indexed(&mut self, name: &str, t: &ValueType, node: &SyntaxNode) {
+        if t.is_only(&[ValueType::Nil, ValueType::Number, ValueType::Boolean]) {
+            let message = format!("Indexing '{}', which is a {} value", name, t.name());
+            self.report(DiagnosticKind::IndexingNonTable, node, message);
+        } else if t.may_be_nil() {
+            self.report(DiagnosticKind::PossiblyNil, node, format!("'{}' may be nil", name));
+        }
+    }
+
+    fn check_identifier(&mut self, identifier: &Identifier) {
+        for child in identifier.syntax().children() {
+            if let Some(i) = Identifier::cast(child.clone()) {
+                self.check_identifier(&i);
+            } else if let Some(c) = FunctionCall::cast(child.clone()) {
+                self.check_call(&c);
+            } else if let Some(e) = Expression::cast(child) {
+                self.check_expression(&e);
             }
         }
-        for i in (indexes[indexes.len() - 1])..all_statements.len() { 
-            let len = indexes.len();
-            indexes[len - 1] = i + 1;
-            let statement = &all_statements[i];
+        if let Some(chain) = identifier.name_chain() {
+            for end in 1..chain.len() {
+                let t = self.get_chain_type(&chain[..end]);
+                self.check_indexed(&chain[..end].join("."), &t, identifier.syntax());
+            }
+            return
+        }
+        // Methods and `[key]` lookups could reach any field of a table
+        let chain = match identifier.prefix() {
+            Some(Expression::Identifier(p)) => p.name_chain(),
+            Some(_) => None,
+            // `a.b:m` indexes each name before the method
+            None => {
+                let mut names = identifier.names();
+                names.pop();
+                Some(names)
+            }
+        };
+        let Some(chain) = chain else {
+            return
+        };
+        for end in 1..=chain.len() {
+            let t = self.get_chain_type(&chain[..end]);
+            self.check_indexed(&chain[..end].join("."), &t, identifier.syntax());
+        }
+        if let [name] = chain.as_slice() {
+            self.forget_fields(name);
+        }
+    }
+
+    fn check_call(&mut self, call: &FunctionCall) {
+        if let Some(inner) = call.inner_call() {
+            self.check_call(&inner);
+        }
+        if let Some(identifier) = call.identifier() {
+            self.check_identifier(&identifier);
+        }
+        let arguments = match call.arguments() {
+            Some(a) => a.expressions(),
+            None => Vec::new(),
+        };
+        for e in &arguments {
+            self.check_expression(e);
+        }
+        let Some(chain) = call.identifier().and_then(|i| i.name_chain()) else {
+            return
+        };
+        let t = self.get_chain_type(&chain);
+        let name = chain.join(".");
+        if t.is_only(&[ValueType::Nil, ValueType::Boolean, ValueType::Number, ValueType::String, ValueType::Thread]) {
+            self.report(DiagnosticKind::CallingNonFunction, call.syntax(), format!("Calling '{}', which is a {} value", name, t.name()));
+            return
+        } else if t.may_be_nil() {
+            self.report(DiagnosticKind::PossiblyNil, call.syntax(), format!("'{}' may be nil", name));
+        }
+        let Some(binding) = self.get_callee(call).cloned() else {
+            return
+        };
+        let Some(signature) = &binding.signature else {
+            return
+        };
+        if signature.varargs {
+            return
+        }
+        // A trailing call or `...` may expand to nothing, so only count the fixed arguments
+        let expands = call.arguments().is_some_and(|a| a.ends_with_varargs())
+            || matches!(arguments.last(), Some(Expression::FunctionCall(_) | Expression::MethodCall(_)));
+        let fixed = if expands { arguments.len().saturating_sub(1) } else { arguments.len() };
+        if fixed > signature.parameters {
+            let message = format!("Function expects {} arguments but was given {}", signature.parameters, fixed);
+            self.report(DiagnosticKind::TooManyArguments, call.syntax(), message);
+        }
+    }
+
+    fn scan_block(&mut self, block: &Block) {
+        let statements = block.statements();
+        for (i, statement) in statements.iter().enumerate() {
+            self.block_index.push(i);
             match statement {
                 Statement::LocalAssign(a) => {
-                    let offset_from_block = usize::from(a.syntax().text_range().start() - block.syntax().text_range().start());
-                    let block_index = block_indexes.clone();
-                    let file = String::from(filename);
-                    let value_type = get_expression_type(&a.expression_list().expect("Normally something").expressions()[0]);
-                    let id = Identifier{offset_from_block, file, block_index, value_type};
-                    println!("{id:?}")
+                    let names = match a.name_list() {
+                        Some(n) => n.names(),
+                        None => Vec::new(),
+                    };
+                    let (bindings, values) = self.get_list_bindings(a.expression_list());
+                    let bindings = Scanner::bind_values(bindings, &values, names.len());
+                    for (name, binding) in names.into_iter().zip(bindings) {
+                        self.record(&name, a.syntax(), block, &binding);
+                        self.declare(name, binding);
+                    }
+                }
+                Statement::Assign(a) => {
+                    let identifiers = match a.variable_list() {
+                        Some(v) => v.identifiers(),
+                        None => Vec::new(),
+                    };
+                    for identifier in &identifiers {
+                        self.check_identifier(identifier);
+                    }
+                    let (bindings, values) = self.get_list_bindings(a.expression_list());
+                    let bindings = Scanner::bind_values(bindings, &values, identifiers.len());
+                    for (identifier, binding) in identifiers.iter().zip(bindings) {
+                        match identifier.name_chain().as_deref() {
+                            Some([name]) => self.assign(name.clone(), binding),
+                            Some([name, field]) => self.set_field(name, field, binding.value_type),
+                            _ => (),
+                        }
+                    }
+                }
+                Statement::FunctionDefinition(f) => {
+                    // Declare first so recursive calls resolve to the function
+                    let local_name = if f.is_local() { f.local_name() } else { None };
+                    if let Some(name) = &local_name {
+                        self.declare(name.clone(), Binding::new(ValueType::Function));
+                    }
+                    let signature = Rc::new(self.scan_function(f));
+                    let binding = Binding { value_type: ValueType::Function, signature: Some(signature), fields: None };
+                    if let Some(name) = local_name {
+                        self.record(&name, f.syntax(), block, &binding);
+                        self.declare(name, binding);
+                    } else if let Some(identifier) = f.identifier() {
+                        match identifier.name_chain().as_deref() {
+                            Some([name]) => {
+                                self.record(name, f.syntax(), block, &binding);
+                                self.assign(name.clone(), binding);
+                            }
+                            Some([name, field]) => self.set_field(name, field, ValueType::Function),
+                            _ => (),
+                        }
+                    }
+                }
+                Statement::Return(r) => {
+                    let values = match r.expression_list() {
+                        Some(l) => {
+                            self.check_list(&l);
+                            self.get_list_values(&l)
+                        }
+                        None => ValueList { values: Vec::new(), open: false },
+                    };
+                    if let Some(current) = self.returns.last_mut() {
+                        current.push(values);
+                    }
+                }
+                Statement::Do(d) => {
+                    if let Some(b) = d.block() {
+                        self.scan_nested_block(&b, HashMap::new());
+                    }
+                }
+                Statement::While(w) => {
+                    let mut narrowing = Vec::new();
+                    if let Some(e) = w.condition() {
+                        self.check_expression(&e);
+                        narrowing = self.truthy_narrowing(&e);
+                    }
+                    if let Some(b) = w.block() {
+                        self.scan_loop(&b, HashMap::new(), narrowing);
+                    }
+                }
+                Statement::Repeat(r) => {
+                    if let Some(b) = r.block() {
+                        self.scan_nested_block(&b, HashMap::new());
+                    }
+                    if let Some(e) = r.condition() {
+                        self.check_expression(&e);
+                    }
+                }
+                Statement::If(c) => self.scan_if(c),
+                Statement::ForCountLoop(l) => {
+                    if let Some(list) = l.expression_list() {
+                        self.check_list(&list);
+                    }
+                    let mut bindings = HashMap::new();
+                    if let Some(name) = l.name() {
+                        bindings.insert(name, Binding::new(ValueType::Number));
+                    }
+                    if let Some(b) = l.block() {
+                        self.scan_loop(&b, bindings, Vec::new());
+                    }
                 }
-                _ => {}
+                Statement::ForInLoop(l) => {
+                    if let Some(list) = l.expression_list() {
+                        self.check_list(&list);
+                    }
+                    let mut bindings = HashMap::new();
+                    if let Some(names) = l.name_list() {
+                        for name in names.names() {
+                            bindings.insert(name, Binding::new(ValueType::Missing));
+                        }
+                    }
+                    if let Some(b) = l.block() {
+                        self.scan_loop(&b, bindings, Vec::new());
+                    }
+                }
+                Statement::Break(_) | Statement::Empty(_) => (),
+                Statement::FunctionCall(c) => {
+                    self.check_call(c);
+                    // Code after `assert(x)` only runs when x is truthy
+                    if c.identifier().is_some_and(|i| i.names() == ["assert"])
+                        && let Some(e) = c.arguments().and_then(|a| a.expressions().into_iter().next()) {
+                        let narrowing = self.truthy_narrowing(&e);
+                        self.narrow(narrowing);
+                    }
+                }
+            }
+            self.block_index.pop();
+        }
+    }
+
+    /// Scans a loop body, which may run any number of times including none
+    fn scan_loop(&mut self, block: &Block, bindings: HashMap<String, Binding>, narrowing: Vec<Narrowing>) {
+        let outcome = self.scan_path(|s| {
+            s.narrow(narrowing);
+            s.scan_nested_block(block, bindings);
+        });
+        self.merge_paths(vec![outcome, Outcome::new()]);
+    }
+
+    fn scan_if(&mut self, chain: &IfChain) {
+        let mut outcomes = Vec::new();
+        // Each branch only runs when the conditions before it were all falsy
+        let mut previous = Outcome::new();
+        for branch in chain.if_branches() {
+            let condition = branch.expression();
+            let block = branch.block();
+            let outcome = self.scan_path(|s| {
+                s.apply(&previous);
+                if let Some(e) = &condition {
+                    s.check_expression(e);
+                    let narrowing = s.truthy_narrowing(e);
+                    s.narrow(narrowing);
+                }
+                if let Some(b) = &block {
+                    s.scan_nested_block(b, HashMap::new());
+                }
+            });
+            if !block.as_ref().is_some_and(always_exits) {
+                outcomes.push(outcome);
+            }
+            if let Some(e) = &condition {
+                previous = self.scan_path(|s| {
+                    s.apply(&previous);
+                    let narrowing = s.falsy_narrowing(e);
+                    s.narrow(narrowing);
+                });
+            }
+        }
+        let else_block = chain.else_branch().and_then(|e| e.block());
+        let outcome = self.scan_path(|s| {
+            s.apply(&previous);
+            if let Some(b) = &else_block {
+                s.scan_nested_block(b, HashMap::new());
+            }
+        });
+        if !else_block.as_ref().is_some_and(always_exits) {
+            outcomes.push(outcome);
+        }
+        if !outcomes.is_empty() {
+            self.merge_paths(outcomes);
+        }
+    }
+
+    fn scan_nested_block(&mut self, block: &Block, bindings: HashMap<String, Binding>) {
+        self.scopes.push(bindings);
+        self.scan_block(block);
+        self.scopes.pop();
+    }
+
+    fn scan_function(&mut self, function: &FunctionDefinition) -> FunctionSignature {
+        let mut bindings = HashMap::new();
+        let mut parameters = 0;
+        let mut varargs = false;
+        let annotated = match statement_of(function.syntax()) {
+            Some(s) => annotations::params(&s),
+            None => Vec::new(),
+        };
+        if let Some(params) = function.params() {
+            for name in params.names() {
+                let value_type = match annotated.iter().find(|p| p.name == name) {
+                    Some(p) => ValueType::from_annotation(&p.types, p.optional),
+                    None => ValueType::Missing,
+                };
+                bindings.insert(name, Binding::new(value_type));
+                parameters += 1;
+            }
+            varargs = params.ellipsis();
+        }
+        if function.identifier().is_some_and(|i| i.is_call_to_self()) {
+            bindings.insert(String::from("self"), Binding::new(ValueType::Table));
+        }
+        self.returns.push(Vec::new());
+        self.function_scopes.push(self.scopes.len());
+        let block = function.block();
+        let outcome = self.scan_path(|s| {
+            if let Some(b) = &block {
+                s.scan_nested_block(b, bindings);
             }
+        });
+        self.function_scopes.pop();
+        // The body runs at some unknown point, so anything it sets could be
+        // anything afterwards, though no longer assumed to still be nil
+        for (level, name) in outcome.into_keys() {
+            let mut members = match self.scopes[level].get(&name) {
+                Some(b) => b.value_type.members().into_iter().filter(|t| *t != ValueType::Nil).collect(),
+                None => Vec::new(),
+            };
+            members.push(ValueType::Missing);
+            self.set_at(level, name, Some(Binding::new(ValueType::from_members(members))));
+        }
+        let mut lists = self.returns.pop().expect("pushed before scanning");
+        // Reaching the end of the body is the same as an empty `return`
+        if !block.as_ref().is_some_and(always_returns) && !lists.is_empty() {
+            lists.push(ValueList { values: Vec::new(), open: false });
         }
+        FunctionSignature { parameters, varargs, returns: union_positions(&lists) }
+    }
+}
+
+/// Combines the values of each `return` position by position
+fn union_positions(lists: &[ValueList]) -> Vec<ValueType> {
+    let width = lists.iter().map(|l| l.values.len()).max().unwrap_or(0);
+    (0..width).map(|i| {
+        lists.iter().map(|l| l.get(i)).reduce(ValueType::union).expect("at least one list")
+    }).collect()
+}
+
+fn always_returns(block: &Block) -> bool {
+    match block.statements().into_iter().rfind(|s| !matches!(s, Statement::Empty(_))) {
+        Some(Statement::Return(_)) => true,
+        // `error` never returns to the caller
+        Some(Statement::FunctionCall(c)) => c.identifier().is_some_and(|i| i.names() == ["error"]),
+        Some(Statement::Do(d)) => d.block().is_some_and(|b| always_returns(&b)),
+        Some(Statement::If(c)) => {
+            c.else_branch().and_then(|e| e.block()).is_some_and(|b| always_returns(&b))
+                && c.if_branches().iter().all(|branch| branch.block().is_some_and(|b| always_returns(&b)))
+        }
+        _ => false,
+    }
+}
+
+/// Whether the code after the block can't be reached by running it
+fn always_exits(block: &Block) -> bool {
+    let last = block.statements().into_iter().rfind(|s| !matches!(s, Statement::Empty(_)));
+    matches!(last, Some(Statement::Break(_))) || always_returns(block)
+}
+
+/// The statement holding a function, which its doc comments sit above
+fn statement_of(node: &SyntaxNode) -> Option<SyntaxNode> {
+    node.ancestors().find(|n| n.parent().is_some_and(|p| p.kind() == SyntaxKind::Block))
+}
+
+fn builtin_return_type(name: &str) -> ValueType {
+    match name {
+        "tostring" | "type" | "string.format" | "format" | "string.rep" | "strrep"
+            | "string.sub" | "strsub" | "string.lower" | "strlower" | "string.upper" | "strupper"
+            | "string.char" | "table.concat" => ValueType::String,
+        "tonumber" => ValueType::Union(vec![ValueType::Number, ValueType::Nil]),
+        "string.len" | "strlen" | "math.floor" | "floor" | "math.ceil" | "ceil"
+            | "math.abs" | "abs" | "math.min" | "min" | "math.max" | "max" | "math.random"
+            | "random" | "math.sqrt" | "sqrt" | "GetTime" | "time" => ValueType::Number,
+        "setmetatable" => ValueType::Table,
+        "coroutine.create" => ValueType::Thread,
+        _ => ValueType::Missing,
+    }
+}
+
+/// Range of the node without leading or trailing whitespace and comments
+fn trimmed_range(node: &SyntaxNode) -> (usize, usize) {
+    let mut tokens = node.descendants_with_tokens().filter_map(|n| n.into_token()).filter(|t|
+        !matches!(t.kind(), SyntaxKind::Whitespace | SyntaxKind::Newline | SyntaxKind::Comment)
+    );
+    let range = node.text_range();
+    let Some(first) = tokens.next() else {
+        return (usize::from(range.start()), usize::from(range.end()))
+    };
+    let last = tokens.last().unwrap_or_else(|| first.clone());
+    (usize::from(first.text_range().start()), usize::from(last.text_range().end()))
+}
+
+fn scan(green: GreenNode, filename: &str, globals: &GlobalTypes) -> Scanner {
+    let root = SyntaxNode::new_root(green);
+    let block = Block::cast(root).expect("everything starts with a block");
+    let mut scanner = Scanner {
+        file: String::from(filename),
+        block_index: Vec::new(),
+        scopes: vec![HashMap::new()],
+        function_scopes: Vec::new(),
+        returns: Vec::new(),
+        paths: Vec::new(),
+        declarations: Vec::new(),
+        diagnostics: Vec::new(),
+        globals: globals.iter().map(|(name, t)| (name.clone(), ValueType::from_annotation(std::slice::from_ref(t), false))).collect(),
+    };
+    scanner.scan_block(&block);
+    scanner
+}
+
+pub fn get_types(green: GreenNode, filename: &str) {
+    let scanner = scan(green, filename, &GlobalTypes::new());
+    for declaration in &scanner.declarations {
+        println!("{declaration:?}");
+    }
+    for diagnostic in &scanner.diagnostics {
+        println!("{:?} {}..{}: {}", diagnostic.kind, diagnostic.start, diagnostic.end, diagnostic.message);
+    }
+}
+
+/// What the scanner decided a declaration's type is, for debugging from an editor
+pub struct TypeInfo {
+    pub name: String,
+    pub start: usize,
+    pub end: usize,
+    /// Index of each block entered on the way to the declaration
+    pub scope: Vec<usize>,
+    pub value_type: String,
+    /// Parameter count, whether it takes `...` and the return types, for functions
+    pub signature: Option<(usize, bool, Vec<String>)>,
+}
+
+pub fn get_type_table(green: GreenNode, filename: &str) -> Vec<TypeInfo> {
+    scan(green, filename, &GlobalTypes::new()).declarations.into_iter().map(|d| TypeInfo {
+        name: d.name,
+        start: d.range.0,
+        end: d.range.1,
+        scope: d.block_index,
+        value_type: d.value_type.name(),
+        signature: d.signature.map(|s| (s.parameters, s.varargs, s.returns.iter().map(|r| r.name()).collect())),
+    }).collect()
+}
+
+pub fn get_diagnostics(green: GreenNode, filename: &str, globals: &GlobalTypes) -> Vec<Diagnostic> {
+    scan(green, filename, globals).diagnostics
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::syntax::syntax::Generator;
+
+    /// The type of each local, by name
+    fn types(text: &str) -> Vec<(String, String)> {
+        let green = Generator::new(text).process_all();
+        get_type_table(green, "test.lua").into_iter().map(|t| (t.name, t.value_type)).collect()
+    }
+
+    fn returns_of(text: &str, name: &str) -> Vec<String> {
+        let green = Generator::new(text).process_all();
+        get_type_table(green, "test.lua").into_iter().rev().find(|t| t.name == name)
+            .and_then(|t| t.signature).map(|(_, _, returns)| returns).unwrap_or_default()
+    }
+
+    fn type_of(text: &str, name: &str) -> String {
+        types(text).into_iter().rev().find(|(n, _)| n == name).map(|(_, t)| t).unwrap_or_default()
+    }
+
+    fn codes(text: &str) -> Vec<&'static str> {
+        let green = Generator::new(text).process_all();
+        get_diagnostics(green, "test.lua", &GlobalTypes::new()).iter().map(|d| d.kind.code()).collect()
+    }
+
+    #[test]
+    fn and_narrows_a_comparison() {
+        let text = "---@param a? table\nlocal function f(a)\n    if a and a.b == 1 then return end\nend\nreturn f\n";
+        assert_eq!(codes(text), Vec::<&str>::new());
+    }
+
+    #[test]
+    fn nil_check_narrows_a_comparison() {
+        let text = "---@param a? table\nlocal function f(a)\n    if a ~= nil and a.c > 2 then return end\nend\nreturn f\n";
+        assert_eq!(codes(text), Vec::<&str>::new());
+    }
+
+    #[test]
+    fn optional_param_after_another_statement() {
+        let text = "local w = 1\n---@param p? string\nlocal function g(p) return p:upper() end\nreturn w, g\n";
+        assert_eq!(codes(text), ["need-check-nil"]);
+    }
+
+    #[test]
+    fn unchecked_access_is_reported() {
+        let text = "---@param a? table\nlocal function f(a)\n    if a.b == 1 then return end\nend\nreturn f\n";
+        assert_eq!(codes(text), ["need-check-nil"]);
+    }
+
+    #[test]
+    fn defaulting_with_or_removes_nil() {
+        assert_eq!(codes("local x\nx = x or {}\nreturn x.a\n"), Vec::<&str>::new());
+        assert_eq!(codes("local t = { k = nil }\nt.k = t.k or {}\nreturn t.k.a\n"), Vec::<&str>::new());
+    }
+
+    #[test]
+    fn setting_when_falsy_removes_nil() {
+        assert_eq!(codes("local x\nif not x then x = {} end\nreturn x.a\n"), Vec::<&str>::new());
+        assert_eq!(codes("local t = { k = nil }\nif not t.k then t.k = {} end\nreturn t.k.a\n"), Vec::<&str>::new());
+    }
+
+    #[test]
+    fn setting_when_nil_removes_nil() {
+        assert_eq!(codes("local x\nif x == nil then x = {} end\nreturn x.a\n"), Vec::<&str>::new());
+        let text = "---@param x? table\nlocal function f(x)\n    if x == nil then x = {} end\n    return x.a\nend\nreturn f\n";
+        assert_eq!(codes(text), Vec::<&str>::new());
+    }
+
+    #[test]
+    fn possible_paths_still_merge() {
+        let text = "---@param x? table\nlocal function f(x)\n    if x then x = 1 end\n    return x.a\nend\nreturn f\n";
+        assert_eq!(codes(text), ["need-check-nil"]);
+        assert_eq!(codes("local x\nif x == nil then print(1) end\nreturn x.a\n"), ["index-non-table"]);
+    }
+
+    #[test]
+    fn infers_operator_types() {
+        let text = "local n = 1\nlocal s = 'a'\nlocal t = {}\n\
+            local sum = n + 2\nlocal neg = -n\nlocal joined = s .. n\nlocal less = n < 2\n\
+            local negated = not t\nlocal length = #t\nlocal grouped = (n * 2)\n";
+        assert_eq!(type_of(text, "sum"), "number");
+        assert_eq!(type_of(text, "neg"), "number");
+        assert_eq!(type_of(text, "joined"), "string");
+        assert_eq!(type_of(text, "less"), "boolean");
+        assert_eq!(type_of(text, "negated"), "boolean");
+        assert_eq!(type_of(text, "length"), "number");
+        assert_eq!(type_of(text, "grouped"), "number");
+    }
+
+    #[test]
+    fn and_or_give_unions() {
+        let text = "local n = 1\nlocal s = 'a'\nlocal either = n == 1 and s or n\n";
+        let either = type_of(text, "either");
+        assert!(either.contains("string") && either.contains("number"), "{either}");
+        assert_eq!(type_of("local t = {}\nlocal x = t or 5\n", "x"), "table");
+    }
+
+    #[test]
+    fn identifiers_take_their_binding_type() {
+        assert_eq!(type_of("local a = 'x'\nlocal b = a\n", "b"), "string");
+        assert_eq!(type_of("local f = function() end\nlocal g = f\n", "g"), "function");
+    }
+
+    #[test]
+    fn builtins_give_their_types() {
+        assert_eq!(type_of("local n = math.floor(1.5)\n", "n"), "number");
+        assert_eq!(type_of("local s = tostring(1)\n", "s"), "string");
+        // Any number of values, so nothing is known past the call
+        assert_eq!(type_of("local a, b = string.byte('ab', 1, -1)\n", "b"), "unknown");
+    }
+
+    #[test]
+    fn shadowed_builtins_lose_their_types() {
+        assert_eq!(type_of("local floor = getmetatable('').__call\nlocal n = floor(1.5)\n", "n"), "unknown");
+        assert_eq!(type_of("local math = { floor = tostring }\nlocal n = math.floor(1.5)\n", "n"), "unknown");
+        assert_eq!(type_of("tostring = print\nlocal s = tostring(1)\n", "s"), "unknown");
+    }
+
+    #[test]
+    fn infers_return_types() {
+        let text = "local function f(x)\n    if x then return 1, 'a' end\n    return 2, 'b'\nend\n";
+        assert_eq!(returns_of(text, "f"), ["number", "string"]);
+    }
+
+    #[test]
+    fn unions_returns_per_position() {
+        let text = "local function f(x)\n    if x then return 1 end\n    return 'a'\nend\nlocal v = f(true)\n";
+        let returned = returns_of(text, "f");
+        assert_eq!(returned.len(), 1);
+        assert!(returned[0].contains("number") && returned[0].contains("string"), "{returned:?}");
+        assert_eq!(type_of(text, "v"), returned[0]);
+    }
+
+    #[test]
+    fn calls_carry_returns_into_locals() {
+        let text = "local function pair() return 1, 'a' end\nlocal a, b = pair()\n";
+        assert_eq!(type_of(text, "a"), "number");
+        assert_eq!(type_of(text, "b"), "string");
+    }
+
+    #[test]
+    fn tail_calls_forward_returns() {
+        let text = "local function inner() return 'a', {} end\nlocal function outer() return inner() end\nlocal s, t = outer()\n";
+        assert_eq!(returns_of(text, "outer"), ["string", "table"]);
+        assert_eq!(type_of(text, "t"), "table");
+    }
+
+    #[test]
+    fn reports_arithmetic_on_non_numbers() {
+        assert_eq!(codes("local s = 'a'\nlocal x = s * 2\nreturn x\n"), ["arithmetic-on-non-number"]);
+        assert_eq!(codes("local t = {}\nlocal x = -t\nreturn x\n"), ["arithmetic-on-non-number"]);
+        assert_eq!(codes("local n = 1\nlocal x = n * 2\nreturn x\n"), Vec::<&str>::new());
+    }
+
+    #[test]
+    fn reports_calling_non_functions() {
+        assert_eq!(codes("local n = 1\nn()\n"), ["call-non-function"]);
+        assert_eq!(codes("local f = function() end\nf()\n"), Vec::<&str>::new());
+    }
+
+    #[test]
+    fn reports_indexing_non_tables() {
+        assert_eq!(codes("local b = true\nlocal x = b.field\nreturn x\n"), ["index-non-table"]);
+        assert_eq!(codes("local n = 5\nn:foo()\n"), ["index-non-table"]);
+    }
+
+    #[test]
+    fn reports_concatenating_tables() {
+        assert_eq!(codes("local t = {}\nlocal s = 'a' .. t\nreturn s\n"), ["concat-table"]);
+    }
+
+    #[test]
+    fn reports_too_many_arguments() {
+        let text = "local function f(a, b) return a, b end\nf(1, 2, 3)\n";
+        assert_eq!(codes(text), ["redundant-parameter"]);
+        let text = "local function f(a, ...) return a end\nf(1, 2, 3)\n";
+        assert_eq!(codes(text), Vec::<&str>::new());
     }
 }
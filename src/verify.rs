@@ -0,0 +1,44 @@
+//Copyright (C) 2025-  plusmouse and other contributors
+//
+//This program is free software: you can redistribute it and/or modify
+//it under the terms of the GNU General Public License as published by
+//the Free Software Foundation, either version 3 of the License, or
+//(at your option) any later version.
+//
+//This program is distributed in the hope that it will be useful,
+//but WITHOUT ANY WARRANTY; without even the implied warranty of
+//MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//GNU General Public License for more details.
+//
+//You should have received a copy of the GNU General Public License
+//along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! `wow_ls verify <dir>`: parses every Lua file under a directory and checks
+//! the trees are lossless, to catch parser regressions on real addon code
+
+use std::path::Path;
+
+use crate::syntax::{self, SyntaxNode};
+
+/// Returns how many files failed
+pub fn run(dir: &Path) -> std::io::Result<usize> {
+    let files = crate::files::lua_files(dir)?;
+    let mut failed = 0;
+    for path in &files {
+        let bytes = std::fs::read(path)?;
+        // The parser works on text, so anything else can't round-trip anyway
+        let Ok(text) = String::from_utf8(bytes) else {
+            println!("{}: skipped, not UTF-8", path.display());
+            continue
+        };
+        let mut parser = syntax::syntax::Generator::new(&text);
+        let root = SyntaxNode::new_root(parser.process_all());
+        if let Err(mismatch) = syntax::debug::verify_tree(&root, &text) {
+            failed += 1;
+            println!("{}: {}", path.display(), mismatch.message);
+            print!("{}", mismatch.excerpt);
+        }
+    }
+    println!("verified {} files, {} failed", files.len(), failed);
+    Ok(failed)
+}
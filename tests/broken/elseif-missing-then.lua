@@ -0,0 +1,8 @@
+local v = 3
+if v == 1 then
+  print(1)
+elseif v == 2
+  print(2)
+else
+  print(3)
+end
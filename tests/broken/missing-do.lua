@@ -0,0 +1,8 @@
+local i = 0
+while i < 3
+  i = i + 1
+end
+for k = 1, 10
+  print(k)
+end
+print(i)
@@ -0,0 +1,6 @@
+local x = 1
+if x == 1
+  print("one")
+  x = 2
+end
+print(x)
@@ -0,0 +1,8 @@
+local function outer(x)
+  for i = 1, x
+    if i > 2
+      print(i)
+    end
+  end
+end
+outer(5)
@@ -0,0 +1,5 @@
+local function g()
+  return 1
+end
+end
+print(g())
@@ -0,0 +1,4 @@
+local a = 1
+) ) ] + * 5 6
+local b = 2
+print(a, b)
@@ -0,0 +1,3 @@
+print("a"
+local y = 2
+print(y)
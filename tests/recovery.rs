@@ -0,0 +1,44 @@
+//! Broken files should each report a handful of errors rather than one per token
+
+// The crate is only a binary, so pull in the parser directly
+#[allow(dead_code)]
+#[path = "../src/syntax/lexer.rs"]
+mod lexer;
+#[allow(dead_code)]
+#[path = "../src/syntax/syntax.rs"]
+mod parser;
+
+// Where the parser expects to find the lexer
+mod syntax {
+    pub(crate) use crate::lexer;
+}
+
+use parser::{Generator, SyntaxNode};
+
+fn error_count(name: &str) -> usize {
+    let path = format!("{}/tests/broken/{}", env!("CARGO_MANIFEST_DIR"), name);
+    let text = std::fs::read_to_string(&path).expect("corpus file exists");
+    let mut generator = Generator::new(&text);
+    let green = generator.process_all();
+    assert_eq!(SyntaxNode::new_root(green).text().to_string(), text, "{name} should round trip");
+    generator.errors().len()
+}
+
+#[test]
+fn broken_files_report_few_errors() {
+    let expected = [
+        ("bad-assignment.lua", 2),
+        ("elseif-missing-then.lua", 1),
+        ("missing-do.lua", 2),
+        ("missing-end.lua", 1),
+        ("missing-then.lua", 1),
+        ("nested-missing.lua", 2),
+        ("stray-end.lua", 1),
+        ("stray-tokens.lua", 1),
+        ("unclosed-call.lua", 1),
+        ("unclosed-table.lua", 1),
+    ];
+    for (name, count) in expected {
+        assert_eq!(error_count(name), count, "errors in {name}");
+    }
+}